@@ -2,6 +2,7 @@ use crate::config::AppConfig;
 use crate::error::ApiError;
 use anyhow::Context;
 use axum::http::StatusCode;
+use chrono::{DateTime, SecondsFormat, Utc};
 use reqwest::Client;
 use serde_json::Value;
 use std::time::Duration;
@@ -23,17 +24,34 @@ impl UpstreamClients {
         Ok(Self { client, cfg })
     }
 
-    pub async fn fetch_iss(&self) -> Result<Value, ApiError> {
-        let url = &self.cfg.where_iss_url;
+    /// A live position of a polled satellite from `url`.
+    pub async fn fetch_position(&self, url: &str) -> Result<Value, ApiError> {
         let req = self.client.get(url);
         self.request_json(req, "UPSTREAM_ISS").await
     }
 
-    pub async fn fetch_osdr(&self) -> Result<Value, ApiError> {
-        let req = self.client.get(&self.cfg.nasa_url);
+    /// The OSDR catalog; with `since` and `OSDR_SINCE_PARAM` set, asks the
+    /// upstream for items updated after it.
+    pub async fn fetch_osdr(&self, since: Option<DateTime<Utc>>) -> Result<Value, ApiError> {
+        let mut req = self.client.get(&self.cfg.nasa_url);
+        if let (Some(since), Some(param)) = (since, &self.cfg.osdr_since_param) {
+            req = req.query(&[(param.as_str(), since.to_rfc3339_opts(SecondsFormat::Secs, true))]);
+        }
         self.request_json(req, "UPSTREAM_OSDR").await
     }
 
+    pub async fn fetch_osdr_detail(&self, dataset_id: &str) -> Result<Value, ApiError> {
+        let url = self.cfg.osdr_detail_url.replace("{id}", &path_segment(dataset_id));
+        let req = self.client.get(url);
+        self.request_json(req, "UPSTREAM_OSDR_DETAIL").await
+    }
+
+    /// An element set catalog as text.
+    pub async fn fetch_tle(&self, url: &str) -> Result<String, ApiError> {
+        let req = self.client.get(url);
+        Ok(self.send(req, "UPSTREAM_TLE").await?.text().await?)
+    }
+
     pub async fn fetch_apod(&self) -> Result<Value, ApiError> {
         let mut req = self
             .client
@@ -59,7 +77,7 @@ impl UpstreamClients {
     pub async fn fetch_donki(&self, path: &str, start: &str, end: &str) -> Result<Value, ApiError> {
         let mut req = self
             .client
-            .get(&format!("https://api.nasa.gov/DONKI/{path}"))
+            .get(format!("https://api.nasa.gov/DONKI/{path}"))
             .query(&[("startDate", start), ("endDate", end)]);
         if !self.cfg.nasa_key.is_empty() {
             req = req.query(&[("api_key", &self.cfg.nasa_key)]);
@@ -75,6 +93,15 @@ impl UpstreamClients {
     }
 
     async fn request_json(&self, req: reqwest::RequestBuilder, code: &str) -> Result<Value, ApiError> {
+        Ok(self.send(req, code).await?.json().await?)
+    }
+
+    /// Sends `req` up to three times until the upstream answers with success.
+    async fn send(
+        &self,
+        req: reqwest::RequestBuilder,
+        code: &str,
+    ) -> Result<reqwest::Response, ApiError> {
         let mut last_err = None;
         for _ in 0..3 {
             match req.try_clone().expect("clone req").send().await {
@@ -88,8 +115,7 @@ impl UpstreamClients {
                             format!("{code} status {}", status.as_u16()),
                         ));
                     } else {
-                        let json: Value = resp.json().await?;
-                        return Ok(json);
+                        return Ok(resp);
                     }
                 }
                 Err(e) => last_err = Some(ApiError::Http(e)),
@@ -103,3 +129,17 @@ impl UpstreamClients {
     }
 }
 
+
+/// Percent-encodes `s` for use as one URL path segment: everything but the
+/// RFC 3986 unreserved characters, so `/`, `?` and `#` cannot reshape the URL.
+pub(crate) fn path_segment(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
@@ -1,70 +1,348 @@
 use std::time::Duration;
 
+use crate::domain::{
+    Geofence, GeofenceShape, OsdrFieldMap, RetentionPolicy, TrackMode, TrackedSatellite,
+};
+use crate::orbit::ISS_NORAD_ID;
+use crate::timeparse::{check_format, TimeParser};
+
 #[derive(Clone, Debug)]
 pub struct AppConfig {
     pub database_url: String,
     pub nasa_url: String,
     pub nasa_key: String,
-    pub where_iss_url: String,
+    pub satellites: Vec<TrackedSatellite>,
     pub every_osdr: u64,
-    pub every_iss: u64,
+    pub every_osdr_enrich: u64,
+    pub every_osdr_full: u64,
     pub every_apod: u64,
     pub every_neo: u64,
     pub every_donki: u64,
     pub every_spacex: u64,
+    pub every_retention: u64,
+    pub every_tle: u64,
     pub http_timeout: Duration,
     pub http_user_agent: String,
     pub db_max_connections: u32,
     pub osdr_list_limit: i64,
+    pub osdr_detail_url: String,
+    pub osdr_dataset_url: String,
+    pub osdr_enrich_batch: i64,
+    pub osdr_sync_batch: usize,
+    pub osdr_since_param: Option<String>,
+    pub osdr_field_map: OsdrFieldMap,
+    pub time_parser: TimeParser,
+    pub tle_url: String,
+    pub tle_file: Option<String>,
+    pub geofences: Vec<Geofence>,
     pub trend_limit_default: i64,
+    pub retention: Vec<RetentionPolicy>,
+    pub iss_partitions_ahead: u32,
 }
 
 impl AppConfig {
     pub fn from_env() -> anyhow::Result<Self> {
         dotenvy::dotenv().ok();
-        let database_url = std::env::var("DATABASE_URL")
-            .map_err(|_| anyhow::anyhow!("DATABASE_URL is required"))?;
+        Self::from_vars(|k| std::env::var(k).ok())
+    }
 
-        let nasa_url = env_str(
+    /// Builds the config from any variable source; tests pass a closure
+    /// over a fixed map instead of touching the process environment.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let env = Env(&var);
+        let database_url =
+            var("DATABASE_URL").ok_or_else(|| anyhow::anyhow!("DATABASE_URL is required"))?;
+
+        let nasa_url = env.str(
             "NASA_API_URL",
             "https://visualization.osdr.nasa.gov/biodata/api/v2/datasets/?format=json",
         );
-        let nasa_key = env_str("NASA_API_KEY", "");
+        let osdr_detail_url = env.str(
+            "OSDR_DETAIL_URL",
+            "https://visualization.osdr.nasa.gov/biodata/api/v2/dataset/{id}/?format=json",
+        );
+        let nasa_key = env.str("NASA_API_KEY", "");
         let where_iss_url =
-            env_str("WHERE_ISS_URL", "https://api.wheretheiss.at/v1/satellites/25544");
+            env.str("WHERE_ISS_URL", "https://api.wheretheiss.at/v1/satellites/25544");
+        let satellites = parse_tracked_satellites(
+            &env.str("TRACKED_SATELLITES", "25544:name=ISS,mode=poll"),
+            env.u64("ISS_EVERY_SECONDS", 120),
+            &where_iss_url,
+            &env.str("SATELLITE_POLL_URL", "https://api.wheretheiss.at/v1/satellites/{id}"),
+        )?;
+
+        let http_timeout = Duration::from_secs(env.u64("HTTP_TIMEOUT_SECONDS", 20));
+        let http_user_agent = env.str("HTTP_USER_AGENT", "rust_iss/1.0 (+github.com/cursor)");
+        let db_max_connections = env.u64("DB_MAX_CONNECTIONS", 8) as u32;
+        let retention = parse_retention_policies(&env.str(
+            "RETENTION_POLICIES",
+            "iss_fetch_log:raw=30d,bucket=1h;space_cache:raw=30d",
+        ))?;
+
+        let osdr_field_map = match (var("OSDR_FIELD_MAP"), var("OSDR_FIELD_MAP_FILE")) {
+            (Some(spec), _) => parse_osdr_field_map(&spec)?,
+            (None, Some(path)) => parse_osdr_field_map(
+                &std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("osdr field map: read `{path}`: {e}"))?,
+            )?,
+            (None, None) => OsdrFieldMap::default(),
+        };
+
+        let geofences = match (var("GEOFENCES"), var("GEOFENCES_FILE")) {
+            (Some(spec), _) => parse_geofences(&spec)?,
+            (None, Some(path)) => parse_geofences(
+                &std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("geofences: read `{path}`: {e}"))?,
+            )?,
+            (None, None) => Vec::new(),
+        };
 
-        let http_timeout = Duration::from_secs(env_u64("HTTP_TIMEOUT_SECONDS", 20));
-        let http_user_agent = env_str("HTTP_USER_AGENT", "rust_iss/1.0 (+github.com/cursor)");
-        let db_max_connections = env_u64("DB_MAX_CONNECTIONS", 8) as u32;
+        let time_formats: Vec<String> = env
+            .str("TIME_FORMATS", "")
+            .split(';')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(str::to_string)
+            .collect();
+        for f in &time_formats {
+            check_format(f)?;
+        }
 
         Ok(Self {
             database_url,
             nasa_url,
             nasa_key,
-            where_iss_url,
+            satellites,
             db_max_connections,
-            every_osdr: env_u64("FETCH_EVERY_SECONDS", 600),
-            every_iss: env_u64("ISS_EVERY_SECONDS", 120),
-            every_apod: env_u64("APOD_EVERY_SECONDS", 43_200),
-            every_neo: env_u64("NEO_EVERY_SECONDS", 7_200),
-            every_donki: env_u64("DONKI_EVERY_SECONDS", 3_600),
-            every_spacex: env_u64("SPACEX_EVERY_SECONDS", 3_600),
-            osdr_list_limit: env_u64("OSDR_LIST_LIMIT", 20) as i64,
-            trend_limit_default: env_u64("TREND_LIMIT", 240) as i64,
+            every_osdr: env.u64("FETCH_EVERY_SECONDS", 600),
+            every_osdr_enrich: env.u64("OSDR_ENRICH_EVERY_SECONDS", 900),
+            every_osdr_full: env.u64("OSDR_FULL_SYNC_EVERY_SECONDS", 86_400),
+            every_apod: env.u64("APOD_EVERY_SECONDS", 43_200),
+            every_neo: env.u64("NEO_EVERY_SECONDS", 7_200),
+            every_donki: env.u64("DONKI_EVERY_SECONDS", 3_600),
+            every_spacex: env.u64("SPACEX_EVERY_SECONDS", 3_600),
+            every_retention: env.u64("RETENTION_EVERY_SECONDS", 3_600),
+            every_tle: env.u64("TLE_EVERY_SECONDS", 21_600),
+            osdr_list_limit: env.u64("OSDR_LIST_LIMIT", 20) as i64,
+            osdr_detail_url,
+            osdr_dataset_url: env.str(
+                "OSDR_DATASET_URL",
+                "https://osdr.nasa.gov/bio/repo/data/studies/{id}",
+            ),
+            osdr_enrich_batch: env.u64("OSDR_ENRICH_BATCH", 25) as i64,
+            osdr_sync_batch: env.u64("OSDR_SYNC_BATCH", 500) as usize,
+            osdr_since_param: Some(env.str("OSDR_SINCE_PARAM", "")).filter(|p| !p.is_empty()),
+            osdr_field_map,
+            time_parser: TimeParser::new(time_formats),
+            tle_url: env.str(
+                "TLE_URL",
+                "https://celestrak.org/NORAD/elements/gp.php?CATNR={id}&FORMAT=TLE",
+            ),
+            tle_file: Some(env.str("TLE_FILE", "")).filter(|p| !p.is_empty()),
+            geofences,
+            trend_limit_default: env.u64("TREND_LIMIT", 240) as i64,
+            retention,
+            iss_partitions_ahead: env.u64("ISS_PARTITIONS_AHEAD", 3) as u32,
             http_timeout,
             http_user_agent,
         })
     }
 }
 
-fn env_str(key: &str, default: &str) -> String {
-    std::env::var(key).unwrap_or_else(|_| default.to_string())
+struct Env<'a>(&'a dyn Fn(&str) -> Option<String>);
+
+impl Env<'_> {
+    fn str(&self, key: &str, default: &str) -> String {
+        (self.0)(key).unwrap_or_else(|| default.to_string())
+    }
+
+    fn u64(&self, key: &str, default: u64) -> u64 {
+        (self.0)(key)
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(default)
+    }
 }
 
-fn env_u64(key: &str, default: u64) -> u64 {
-    std::env::var(key)
-        .ok()
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(default)
+/// Parses `RETENTION_POLICIES`, e.g.
+/// `iss_fetch_log:raw=30d,bucket=1h;space_cache:raw=30d;space_cache/apod:raw=365d`.
+///
+/// `raw` keeps every row for that long, `bucket` downsamples older rows to one
+/// per bucket instead of deleting them, `max` drops everything older.
+pub(crate) fn parse_retention_policies(spec: &str) -> anyhow::Result<Vec<RetentionPolicy>> {
+    let mut out = Vec::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (target, params) = entry.split_once(':').unwrap_or((entry, ""));
+        let (table, source) = match target.trim().split_once('/') {
+            Some((t, s)) => (t.trim(), Some(s.trim().to_lowercase())),
+            None => (target.trim(), None),
+        };
+        if !RetentionPolicy::TABLES.contains(&table) {
+            anyhow::bail!("retention: unknown table `{table}`");
+        }
+        if source.is_some() && table != "space_cache" {
+            anyhow::bail!("retention: `{table}` has no sources");
+        }
+        let mut policy = RetentionPolicy {
+            table: table.to_string(),
+            source,
+            raw: None,
+            bucket: None,
+            max: None,
+        };
+        for kv in params.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (k, v) = kv
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("retention: expected key=value, got `{kv}`"))?;
+            let d = parse_duration(v.trim())
+                .ok_or_else(|| anyhow::anyhow!("retention: bad duration `{v}`"))?;
+            match k.trim() {
+                "raw" => policy.raw = Some(d),
+                "bucket" => policy.bucket = Some(d),
+                "max" => policy.max = Some(d),
+                other => anyhow::bail!("retention: unknown key `{other}`"),
+            }
+        }
+        if policy.bucket.is_some() && policy.raw.is_none() {
+            anyhow::bail!("retention: `{target}` sets bucket without raw");
+        }
+        out.push(policy);
+    }
+    Ok(out)
 }
 
+/// Parses `TRACKED_SATELLITES`, e.g.
+/// `25544:name=ISS,mode=poll;48274:name=Tiangong;20580:name=Hubble,every=5m`.
+///
+/// `mode` is `propagate` (SGP4 from stored element sets, the default) or
+/// `poll`; polled satellites use `SATELLITE_POLL_URL` with `{id}` replaced,
+/// except the ISS, which keeps `WHERE_ISS_URL`. `every` defaults to
+/// `ISS_EVERY_SECONDS`.
+pub(crate) fn parse_tracked_satellites(
+    spec: &str,
+    default_every: u64,
+    iss_url: &str,
+    poll_url: &str,
+) -> anyhow::Result<Vec<TrackedSatellite>> {
+    let mut out: Vec<TrackedSatellite> = Vec::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (id, params) = entry.split_once(':').unwrap_or((entry, ""));
+        let norad_id: i64 = id
+            .trim()
+            .parse()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| anyhow::anyhow!("satellites: bad NORAD id `{id}`"))?;
+        if out.iter().any(|s| s.norad_id == norad_id) {
+            anyhow::bail!("satellites: {norad_id} is listed twice");
+        }
+        let mut sat = TrackedSatellite {
+            norad_id,
+            name: format!("NORAD {norad_id}"),
+            mode: TrackMode::Propagate,
+            every_seconds: default_every,
+            poll_url: None,
+        };
+        for kv in params.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (k, v) = kv
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| anyhow::anyhow!("satellites: expected key=value, got `{kv}`"))?;
+            match (k, v) {
+                ("name", v) if !v.is_empty() => sat.name = v.to_string(),
+                ("mode", "poll") => sat.mode = TrackMode::Poll,
+                ("mode", "propagate") => sat.mode = TrackMode::Propagate,
+                ("every", v) => {
+                    sat.every_seconds = parse_duration(v)
+                        .ok_or_else(|| anyhow::anyhow!("satellites: bad duration `{v}`"))?
+                        .as_secs()
+                }
+                _ => anyhow::bail!("satellites: bad `{kv}` for {norad_id}"),
+            }
+        }
+        if sat.mode == TrackMode::Poll {
+            sat.poll_url = Some(if norad_id == ISS_NORAD_ID {
+                iss_url.to_string()
+            } else {
+                poll_url.replace("{id}", &norad_id.to_string())
+            });
+        }
+        out.push(sat);
+    }
+    if out.is_empty() {
+        anyhow::bail!("satellites: TRACKED_SATELLITES lists no satellites");
+    }
+    Ok(out)
+}
+
+/// Parses `OSDR_FIELD_MAP`, a JSON object of JSON Pointer lists, e.g.
+/// `{"dataset_id": ["/accession", "/meta/id"], "time_formats": ["%d.%m.%Y"]}`.
+/// Fields left out keep their defaults.
+pub(crate) fn parse_osdr_field_map(spec: &str) -> anyhow::Result<OsdrFieldMap> {
+    let map: OsdrFieldMap =
+        serde_json::from_str(spec).map_err(|e| anyhow::anyhow!("osdr field map: {e}"))?;
+    for (field, paths) in [
+        ("items", &map.items),
+        ("dataset_id", &map.dataset_id),
+        ("title", &map.title),
+        ("status", &map.status),
+        ("updated_at", &map.updated_at),
+    ] {
+        if let Some(p) = paths.iter().find(|p| !p.is_empty() && !p.starts_with('/')) {
+            anyhow::bail!("osdr field map: `{field}` path `{p}` is not a JSON Pointer");
+        }
+    }
+    if map.dataset_id.is_empty() {
+        anyhow::bail!("osdr field map: `dataset_id` needs at least one path");
+    }
+    for f in &map.time_formats {
+        check_format(f).map_err(|e| anyhow::anyhow!("osdr field map: {e}"))?;
+    }
+    Ok(map)
+}
+
+/// Parses `GEOFENCES`, a JSON array such as
+/// `[{"name": "moscow", "circle": {"lat": 55.75, "lon": 37.62, "radius_km": 1000}},
+///   {"name": "box", "polygon": [[30, 50], [60, 50], [60, 70], [30, 70]]}]`.
+pub(crate) fn parse_geofences(spec: &str) -> anyhow::Result<Vec<Geofence>> {
+    let fences: Vec<Geofence> =
+        serde_json::from_str(spec).map_err(|e| anyhow::anyhow!("geofences: {e}"))?;
+    let on_map =
+        |lat: f64, lon: f64| (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon);
+    for (i, f) in fences.iter().enumerate() {
+        if f.name.trim().is_empty() {
+            anyhow::bail!("geofences: fence #{i} has no name");
+        }
+        if fences[..i].iter().any(|g| g.name == f.name) {
+            anyhow::bail!("geofences: `{}` is listed twice", f.name);
+        }
+        match &f.shape {
+            GeofenceShape::Circle { lat, lon, radius_km } => {
+                if !on_map(*lat, *lon) || !radius_km.is_finite() || *radius_km <= 0.0 {
+                    anyhow::bail!("geofences: `{}` needs a centre on the map and a radius", f.name);
+                }
+            }
+            GeofenceShape::Polygon(vertices) => {
+                if vertices.len() < 3 || vertices.iter().any(|[lon, lat]| !on_map(*lat, *lon)) {
+                    anyhow::bail!("geofences: `{}` needs at least 3 [lon, lat] vertices", f.name);
+                }
+            }
+        }
+    }
+    Ok(fences)
+}
+
+/// `90s`, `15m`, `1h`, `30d`, `2w`; a bare number is seconds.
+pub(crate) fn parse_duration(s: &str) -> Option<Duration> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let n: u64 = num.parse().ok()?;
+    let mult = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return None,
+    };
+    (n > 0).then(|| Duration::from_secs(n * mult))
+}
@@ -1,6 +1,8 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Health {
@@ -15,6 +17,8 @@ pub struct IssPoint {
     pub longitude: Option<f64>,
     pub altitude: Option<f64>,
     pub velocity: Option<f64>,
+    /// `daylight` or `eclipsed`, as wheretheiss.at reports it
+    pub visibility: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,6 +27,362 @@ pub struct IssTrend {
     pub delta_km: f64,
     pub dt_sec: f64,
     pub points: Vec<IssPoint>,
+    /// Set for `from`/`to` queries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<TrendWindow>,
+}
+
+/// The requested range of a trend and how it was thinned out.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrendWindow {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// positions stored in the range, before downsampling
+    pub total: usize,
+    pub series: TrendSeries,
+}
+
+/// The value whose shape downsampling preserves.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendSeries {
+    Latitude,
+    Altitude,
+    Velocity,
+}
+
+impl TrendSeries {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "latitude" => Some(Self::Latitude),
+            "altitude" => Some(Self::Altitude),
+            "velocity" => Some(Self::Velocity),
+            _ => None,
+        }
+    }
+
+    pub fn value(self, p: &IssPoint) -> Option<f64> {
+        match self {
+            Self::Latitude => p.latitude,
+            Self::Altitude => p.altitude,
+            Self::Velocity => p.velocity,
+        }
+    }
+}
+
+/// How a tracked satellite's positions are obtained.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackMode {
+    /// Fetched from a position API (`WHERE_ISS_URL`, `SATELLITE_POLL_URL`).
+    Poll,
+    /// Propagated with SGP4 from the stored element sets.
+    Propagate,
+}
+
+/// One entry of `TRACKED_SATELLITES`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct TrackedSatellite {
+    pub norad_id: i64,
+    pub name: String,
+    pub mode: TrackMode,
+    pub every_seconds: u64,
+    /// Position API of a polled satellite.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll_url: Option<String>,
+}
+
+/// A `/satellites` catalog entry.
+#[derive(Debug, Serialize, Clone)]
+pub struct SatelliteStatus {
+    #[serde(flatten)]
+    pub satellite: TrackedSatellite,
+    pub last_at: Option<DateTime<Utc>>,
+    pub tle_epoch: Option<DateTime<Utc>>,
+}
+
+/// A stored two-line element set.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TleSet {
+    pub norad_id: i64,
+    pub name: Option<String>,
+    pub epoch: DateTime<Utc>,
+    pub line1: String,
+    pub line2: String,
+    pub fetched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct TleRefreshReport {
+    pub source: String,
+    pub parsed: usize,
+    pub inserted: usize,
+    pub errors: Vec<String>,
+}
+
+/// Where `/iss/predict` positions come from.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PredictionModel {
+    /// SGP4 from the element set nearest in time
+    Sgp4,
+    /// a circular orbit fitted to the latest logged positions
+    Fit,
+}
+
+/// One point of a predicted ground track.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct TrackPoint {
+    pub at: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+    /// not in the umbra
+    pub sunlit: bool,
+    pub illumination: Illumination,
+}
+
+/// The future ground track of a satellite, one point per `step_seconds`.
+#[derive(Debug, Serialize, Clone)]
+pub struct IssPrediction {
+    pub norad_id: i64,
+    pub model: PredictionModel,
+    pub tle_epoch: Option<DateTime<Utc>>,
+    pub step_seconds: i64,
+    pub points: Vec<TrackPoint>,
+}
+
+/// A named region watched for satellites entering and leaving it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Geofence {
+    pub name: String,
+    #[serde(flatten)]
+    pub shape: GeofenceShape,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GeofenceShape {
+    /// `[lon, lat]` vertices in GeoJSON order; the ring closes by itself
+    Polygon(Vec<[f64; 2]>),
+    Circle { lat: f64, lon: f64, radius_km: f64 },
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GeofenceTransition {
+    Enter,
+    Exit,
+}
+
+impl GeofenceTransition {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Enter => "enter",
+            Self::Exit => "exit",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "enter" => Some(Self::Enter),
+            "exit" => Some(Self::Exit),
+            _ => None,
+        }
+    }
+}
+
+/// A satellite crossing a geofence border, at the first position logged on
+/// the new side.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct GeofenceEvent {
+    pub id: i64,
+    pub norad_id: i64,
+    pub fence: String,
+    pub kind: GeofenceTransition,
+    pub at: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// `/iss/events` filters; events come newest first.
+#[derive(Debug, Clone, Default)]
+pub struct GeofenceEventQuery {
+    pub norad_id: Option<i64>,
+    pub fence: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub limit: i64,
+}
+
+/// How much of the Sun a satellite sees past the Earth's limb.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Illumination {
+    Sunlit,
+    /// the Earth hides part of the solar disc
+    Penumbra,
+    /// the Earth hides all of it
+    Umbra,
+}
+
+impl Illumination {
+    /// The wheretheiss.at `visibility` value.
+    pub fn visibility(self) -> &'static str {
+        match self {
+            Self::Sunlit | Self::Penumbra => "daylight",
+            Self::Umbra => "eclipsed",
+        }
+    }
+}
+
+/// How bright a visible pass looks, by its peak magnitude.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MagnitudeClass {
+    /// brighter than -3
+    Brilliant,
+    /// -3 to -2
+    Bright,
+    /// -2 to -1
+    Moderate,
+    /// dimmer than -1
+    Faint,
+}
+
+impl MagnitudeClass {
+    pub fn of(magnitude: f64) -> Self {
+        match magnitude {
+            m if m < -3.0 => Self::Brilliant,
+            m if m < -2.0 => Self::Bright,
+            m if m < -1.0 => Self::Moderate,
+            _ => Self::Faint,
+        }
+    }
+}
+
+/// A moment of a pass and the direction to look, degrees clockwise from north.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PassPoint {
+    pub at: DateTime<Utc>,
+    pub azimuth: f64,
+}
+
+/// One pass of a satellite above the observer's horizon.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SatellitePass {
+    pub rise: PassPoint,
+    pub culmination: PassPoint,
+    pub set: PassPoint,
+    pub duration_seconds: i64,
+    pub max_elevation: f64,
+    /// the satellite is sunlit while the observer is in darkness
+    pub visible: bool,
+    /// peak magnitude while visible; only known for the ISS
+    pub magnitude: Option<f64>,
+    pub magnitude_class: Option<MagnitudeClass>,
+}
+
+/// `/iss/passes` response; observer altitude in metres.
+#[derive(Debug, Serialize, Clone)]
+pub struct PassPrediction {
+    pub norad_id: i64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+    pub tle_epoch: DateTime<Utc>,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub passes: Vec<SatellitePass>,
+}
+
+/// Position propagated with SGP4 from the element set nearest in time.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct OrbitPosition {
+    pub norad_id: i64,
+    pub at: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// km above the WGS-84 ellipsoid
+    pub altitude: f64,
+    /// km/h, as reported by wheretheiss.at
+    pub velocity: f64,
+    pub illumination: Illumination,
+    pub tle_epoch: DateTime<Utc>,
+}
+
+/// How a position at an arbitrary instant was obtained.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateMethod {
+    /// along the great circle between the logged positions around it
+    Interpolated,
+    /// SGP4 from the element set nearest in time
+    Propagated,
+}
+
+/// Position of a satellite at an exact instant, by whichever method has
+/// the smaller expected error.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PositionEstimate {
+    pub norad_id: i64,
+    pub at: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// km above the ellipsoid, when the logged positions carry it
+    pub altitude: Option<f64>,
+    pub method: EstimateMethod,
+    /// rough error of the ground position, km
+    pub uncertainty_km: f64,
+    /// the logged positions interpolated between
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub samples: Option<[DateTime<Utc>; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tle_epoch: Option<DateTime<Utc>>,
+}
+
+/// Orbit statistics derived from the logged positions of one satellite.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct IssStats {
+    pub norad_id: i64,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub samples: usize,
+    /// from the median time between ascending nodes
+    pub period_minutes: Option<f64>,
+    pub orbits_per_day: Option<f64>,
+    /// km, averaged over whole orbits
+    pub mean_altitude: Option<f64>,
+    /// change of the orbit-mean altitude between reboosts; negative while
+    /// the orbit decays
+    pub altitude_trend_m_per_day: Option<f64>,
+    pub reboosts: Vec<Reboost>,
+    pub days: Vec<DayStats>,
+}
+
+/// One UTC day of [`IssStats`]; sums cover only the logged stretches.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DayStats {
+    pub date: NaiveDate,
+    pub samples: usize,
+    /// hours of the day between positions no more than the interpolation gap
+    /// apart
+    pub covered_hours: f64,
+    pub ascending_nodes: usize,
+    /// along the orbit, from the reported speed
+    pub distance_km: Option<f64>,
+    pub ground_distance_km: f64,
+    pub mean_altitude: Option<f64>,
+}
+
+/// A sudden rise of the orbit-mean altitude.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Reboost {
+    /// start of the orbit during which the altitude rose
+    pub at: DateTime<Utc>,
+    /// km, orbit means before and after
+    pub altitude_before: f64,
+    pub altitude_after: f64,
+    pub delta_m: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -33,6 +393,8 @@ pub struct OsdrItem {
     pub status: Option<String>,
     pub updated_at: Option<DateTime<Utc>>,
     pub inserted_at: DateTime<Utc>,
+    /// Set when a full sync no longer saw the dataset upstream.
+    pub removed_at: Option<DateTime<Utc>>,
     pub raw: Value,
 }
 
@@ -45,10 +407,500 @@ pub struct OsdrUpsert {
     pub raw: Value,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OsdrSort {
+    #[default]
+    InsertedAt,
+    UpdatedAt,
+    Title,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OsdrFilter {
+    pub status: Option<String>,
+    pub updated_from: Option<DateTime<Utc>>,
+    pub updated_to: Option<DateTime<Utc>>,
+    pub dataset_id_prefix: Option<String>,
+    /// Tombstoned datasets are hidden unless set.
+    pub include_removed: bool,
+}
+
+/// Position after the last row of a page. `key` is the sort column of that
+/// row rendered as text (RFC 3339 for timestamps); `None` stands for NULL,
+/// which sorts before every value.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OsdrCursor {
+    pub sort: OsdrSort,
+    pub desc: bool,
+    pub key: Option<String>,
+    pub id: i64,
+}
+
+impl OsdrCursor {
+    pub fn key_time(&self) -> anyhow::Result<Option<DateTime<Utc>>> {
+        self.key
+            .as_deref()
+            .map(|k| k.parse::<DateTime<Utc>>())
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("cursor: {e}"))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OsdrListQuery {
+    pub filter: OsdrFilter,
+    pub sort: OsdrSort,
+    pub desc: bool,
+    pub limit: i64,
+    pub after: Option<OsdrCursor>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OsdrPage<T = OsdrItem> {
+    pub items: Vec<T>,
+    pub limit: i64,
+    pub next_cursor: Option<String>,
+    pub total_estimate: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OsdrExportFormat {
+    Csv,
+    Ndjson,
+    /// schema.org `DataCatalog` of `Dataset`s.
+    JsonLd,
+}
+
+impl OsdrExportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "csv" => Some(Self::Csv),
+            "ndjson" => Some(Self::Ndjson),
+            "jsonld" => Some(Self::JsonLd),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Ndjson => "application/x-ndjson",
+            Self::JsonLd => "application/ld+json",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+            Self::JsonLd => "jsonld",
+        }
+    }
+}
+
+/// File formats of `/iss/track`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackFormat {
+    GeoJson,
+    Kml,
+    Gpx,
+}
+
+impl TrackFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "geojson" => Some(Self::GeoJson),
+            "kml" => Some(Self::Kml),
+            "gpx" => Some(Self::Gpx),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::GeoJson => "application/geo+json",
+            Self::Kml => "application/vnd.google-earth.kml+xml",
+            Self::Gpx => "application/gpx+xml",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::GeoJson => "geojson",
+            Self::Kml => "kml",
+            Self::Gpx => "gpx",
+        }
+    }
+}
+
+/// Text search configuration; Postgres uses the matching `tsvector` column.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum SearchLang {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "ru")]
+    Russian,
+}
+
+impl SearchLang {
+    /// Russian when the text contains any Cyrillic letter.
+    pub fn detect(text: &str) -> Self {
+        if text.chars().any(|c| matches!(c, '\u{0400}'..='\u{04FF}')) {
+            SearchLang::Russian
+        } else {
+            SearchLang::English
+        }
+    }
+}
+
+/// Keyset position in search results. `rank` travels as its bit pattern:
+/// the next page must resume at exactly the stored value, which a decimal
+/// round trip through JSON does not promise.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct OsdrSearchCursor {
+    #[serde(serialize_with = "rank_bits", deserialize_with = "rank_from_bits")]
+    pub rank: f32,
+    pub id: i64,
+}
+
+fn rank_bits<S: serde::Serializer>(rank: &f32, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u32(rank.to_bits())
+}
+
+fn rank_from_bits<'de, D: serde::Deserializer<'de>>(d: D) -> Result<f32, D::Error> {
+    u32::deserialize(d).map(f32::from_bits)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OsdrSearchQuery {
+    pub text: String,
+    pub lang: SearchLang,
+    pub filter: OsdrFilter,
+    pub limit: i64,
+    pub after: Option<OsdrSearchCursor>,
+}
+
+/// A search result ordered by `rank` (higher is better). Highlights wrap
+/// matched words in `<b>…</b>`.
+#[derive(Debug, Serialize, Clone)]
+pub struct OsdrSearchHit {
+    #[serde(flatten)]
+    pub item: OsdrItem,
+    pub rank: f32,
+    pub title_highlight: Option<String>,
+    pub snippet: Option<String>,
+}
+
+/// What an upsert did to the stored row.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OsdrChange {
+    Created,
+    Updated,
+    /// A tombstoned dataset came back upstream.
+    Restored,
+    Unchanged,
+}
+
+impl OsdrChange {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OsdrChange::Created => "created",
+            OsdrChange::Updated => "updated",
+            OsdrChange::Restored => "restored",
+            OsdrChange::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// What a recorded version of a dataset did.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OsdrVersionKind {
+    Created,
+    Updated,
+    Restored,
+    /// The dataset disappeared upstream and was tombstoned.
+    Removed,
+}
+
+impl OsdrVersionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Restored => "restored",
+            Self::Removed => "removed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "created" => Some(Self::Created),
+            "updated" => Some(Self::Updated),
+            "restored" => Some(Self::Restored),
+            "removed" => Some(Self::Removed),
+            _ => None,
+        }
+    }
+}
+
+/// One recorded change of a dataset. `diff` holds `fields` (changed columns
+/// as `{old, new}`) and `raw` (JSON Pointer operations on the raw payload);
+/// `title` is the title as of this version.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OsdrVersion {
+    pub dataset_id: String,
+    pub version: i32,
+    pub changed_at: DateTime<Utc>,
+    pub kind: OsdrVersionKind,
+    pub title: Option<String>,
+    pub diff: Value,
+}
+
+/// Structured detail metadata of one dataset, extracted from the upstream
+/// per-dataset endpoint. `source_updated_at` is the list-level `updated_at`
+/// the detail was fetched for; enrichment reruns once that changes.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OsdrDetail {
+    pub dataset_id: String,
+    pub source_updated_at: Option<DateTime<Utc>>,
+    pub enriched_at: DateTime<Utc>,
+    pub mission_name: Option<String>,
+    pub mission: Option<Value>,
+    pub organisms: Vec<String>,
+    pub factors: Vec<String>,
+    pub assays: Vec<OsdrAssay>,
+    pub files: Vec<OsdrFile>,
+    pub raw: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OsdrAssay {
+    pub measurement: Option<String>,
+    pub technology: Option<String>,
+    pub platform: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OsdrFile {
+    pub name: String,
+    pub url: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub category: Option<String>,
+}
+
+/// Where OSDR sync finds each field of a catalog item: ordered JSON Pointer
+/// paths, the first one holding a usable value wins. `items` lists where the
+/// item arrays live (a top-level array is always accepted); `time_formats`
+/// are extra strftime formats for `updated_at`, tried after the built-in ones
+/// and `TIME_FORMATS`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OsdrFieldMap {
+    pub items: Vec<String>,
+    pub dataset_id: Vec<String>,
+    pub title: Vec<String>,
+    pub status: Vec<String>,
+    pub updated_at: Vec<String>,
+    pub time_formats: Vec<String>,
+}
+
+impl Default for OsdrFieldMap {
+    fn default() -> Self {
+        let paths = |keys: &[&str]| keys.iter().map(|k| format!("/{k}")).collect();
+        Self {
+            items: paths(&["items", "results"]),
+            dataset_id: paths(&["dataset_id", "id", "uuid", "studyId", "accession", "osdr_id"]),
+            title: paths(&["title", "name", "label"]),
+            status: paths(&["status", "state", "lifecycle"]),
+            updated_at: paths(&["updated", "updated_at", "modified", "lastUpdated", "timestamp"]),
+            time_formats: Vec::new(),
+        }
+    }
+}
+
+/// A full sync walks the whole catalog and tombstones what is gone; an
+/// incremental one only looks at items newer than the watermark.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OsdrSyncMode {
+    #[default]
+    Full,
+    Incremental,
+}
+
+/// Persisted progress of the OSDR sync. `watermark` is the highest upstream
+/// `updated_at` written so far.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct OsdrSyncState {
+    pub watermark: Option<DateTime<Utc>>,
+    pub last_full_at: Option<DateTime<Utc>>,
+    pub last_sync_at: Option<DateTime<Utc>>,
+}
+
+/// Outcome of one OSDR sync. `errors` names failed items by their position
+/// in the upstream catalog and is capped at [`OsdrSyncReport::MAX_ERRORS`];
+/// `failed` counts all of them. `skipped` items were not newer than the
+/// previous watermark.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct OsdrSyncReport {
+    pub mode: OsdrSyncMode,
+    pub watermark: Option<DateTime<Utc>>,
+    pub skipped: usize,
+    pub inserted: usize,
+    pub updated: usize,
+    pub restored: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    pub errors: Vec<OsdrSyncError>,
+    /// Items written with a rejected field value, e.g. an unparsable time.
+    pub warnings: Vec<OsdrSyncError>,
+    pub removed: Vec<String>,
+    /// Items per field whose mapped paths held no value.
+    pub unmapped: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct OsdrSyncError {
+    pub index: usize,
+    pub dataset_id: Option<String>,
+    pub message: String,
+}
+
+impl OsdrSyncReport {
+    pub const MAX_ERRORS: usize = 100;
+
+    pub fn record(&mut self, change: OsdrChange) {
+        match change {
+            OsdrChange::Created => self.inserted += 1,
+            OsdrChange::Updated => self.updated += 1,
+            OsdrChange::Restored => self.restored += 1,
+            OsdrChange::Unchanged => self.unchanged += 1,
+        }
+    }
+
+    pub fn fail(&mut self, index: usize, dataset_id: Option<String>, message: &str) {
+        self.failed += 1;
+        if self.errors.len() < Self::MAX_ERRORS {
+            self.errors.push(OsdrSyncError {
+                index,
+                dataset_id,
+                message: message.to_string(),
+            });
+        }
+    }
+
+    pub fn warn(&mut self, index: usize, dataset_id: Option<String>, message: String) {
+        if self.warnings.len() < Self::MAX_ERRORS {
+            self.warnings.push(OsdrSyncError {
+                index,
+                dataset_id,
+                message,
+            });
+        }
+    }
+}
+
+/// A dataset due for enrichment; `attempts` counts its failures in a row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsdrEnrichCandidate {
+    pub dataset_id: String,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub attempts: i32,
+}
+
+/// The last failed enrichment of a dataset; it is not retried before
+/// `retry_at`. Cleared once a detail is saved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsdrEnrichFailure {
+    pub dataset_id: String,
+    pub attempts: i32,
+    pub failed_at: DateTime<Utc>,
+    pub retry_at: DateTime<Utc>,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct OsdrEnrichRun {
+    pub candidates: usize,
+    pub enriched: usize,
+    pub failed: usize,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SpaceCacheItem {
     pub source: String,
     pub fetched_at: DateTime<Utc>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub check_count: i32,
+    pub payload_hash: Option<String>,
     pub payload: Value,
 }
 
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct RetentionPolicy {
+    pub table: String,
+    pub source: Option<String>,
+    #[serde(serialize_with = "ser_secs")]
+    pub raw: Option<Duration>,
+    #[serde(serialize_with = "ser_secs")]
+    pub bucket: Option<Duration>,
+    #[serde(serialize_with = "ser_secs")]
+    pub max: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    pub const TABLES: [&'static str; 2] = ["iss_fetch_log", "space_cache"];
+
+    pub fn target(&self) -> String {
+        match &self.source {
+            Some(s) => format!("{}/{}", self.table, s),
+            None => self.table.clone(),
+        }
+    }
+}
+
+fn ser_secs<S: serde::Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+    match d {
+        Some(d) => s.serialize_some(&d.as_secs()),
+        None => s.serialize_none(),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetentionRun {
+    pub target: String,
+    pub ran_at: DateTime<Utc>,
+    pub downsampled: i64,
+    pub expired: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TableStats {
+    pub table: String,
+    pub rows: i64,
+    pub total_bytes: i64,
+    pub reclaimed_rows: i64,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssStorage {
+    Plain,
+    Partitioned,
+    Hypertable,
+    Sqlite,
+    Memory,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RetentionReport {
+    pub iss_storage: Option<IssStorage>,
+    pub policies: Vec<RetentionPolicy>,
+    pub tables: Vec<TableStats>,
+    pub last_runs: Vec<RetentionRun>,
+}
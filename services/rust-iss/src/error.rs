@@ -52,6 +52,9 @@ pub enum ApiError {
     UpstreamStatus(StatusCode, String),
     #[error("invalid: {0}")]
     Invalid(String),
+    /// A local file the service depends on could not be read; `{0}` says which.
+    #[error("io: {0}: {1}")]
+    Io(String, #[source] std::io::Error),
 }
 
 impl ApiError {
@@ -61,6 +64,7 @@ impl ApiError {
             ApiError::Http(_) => "HTTP_ERROR",
             ApiError::UpstreamStatus(_, _) => "UPSTREAM_STATUS",
             ApiError::Invalid(_) => "INVALID_INPUT",
+            ApiError::Io(_, _) => "IO_ERROR",
         }
     }
 
@@ -70,6 +74,7 @@ impl ApiError {
             ApiError::Http(e) => e.to_string(),
             ApiError::UpstreamStatus(_, m) => m.clone(),
             ApiError::Invalid(m) => m.clone(),
+            ApiError::Io(what, e) => format!("{what}: {e}"),
         }
     }
 }
@@ -0,0 +1,67 @@
+//! Geofences: named polygons and circles on the map, and the transitions of
+//! a satellite's ground position across their borders.
+
+use crate::domain::{Geofence, GeofenceShape, GeofenceTransition};
+use crate::services::haversine_km;
+
+impl Geofence {
+    /// Whether the ground point is inside. Polygons may cross the
+    /// antimeridian but must not enclose a pole.
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        match &self.shape {
+            GeofenceShape::Circle { lat: c_lat, lon: c_lon, radius_km } => {
+                haversine_km(*c_lat, *c_lon, lat, lon) <= *radius_km
+            }
+            GeofenceShape::Polygon(vertices) => {
+                let ring = unwrap_longitudes(vertices);
+                [lon, lon + 360.0, lon - 360.0].iter().any(|x| in_ring(&ring, *x, lat))
+            }
+        }
+    }
+}
+
+/// Fences whose state changes when the position moves to `lat`/`lon`, given
+/// the names of the fences it was inside before.
+pub fn transitions<'a>(
+    fences: &'a [Geofence],
+    inside: &[String],
+    lat: f64,
+    lon: f64,
+) -> Vec<(&'a Geofence, GeofenceTransition)> {
+    fences
+        .iter()
+        .filter_map(|f| match (inside.contains(&f.name), f.contains(lat, lon)) {
+            (false, true) => Some((f, GeofenceTransition::Enter)),
+            (true, false) => Some((f, GeofenceTransition::Exit)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Shifts `[lon, lat]` vertices by whole turns so that no edge spans more
+/// than 180° of longitude.
+fn unwrap_longitudes(vertices: &[[f64; 2]]) -> Vec<[f64; 2]> {
+    let mut out: Vec<[f64; 2]> = Vec::with_capacity(vertices.len());
+    for &[lon, lat] in vertices {
+        let lon = match out.last() {
+            Some(prev) => lon + 360.0 * ((prev[0] - lon) / 360.0).round(),
+            None => lon,
+        };
+        out.push([lon, lat]);
+    }
+    out
+}
+
+/// Even-odd rule on the plate carrée plane.
+fn in_ring(ring: &[[f64; 2]], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    let mut j = ring.len() - 1;
+    for i in 0..ring.len() {
+        let ([xi, yi], [xj, yj]) = (ring[i], ring[j]);
+        if (yi > y) != (yj > y) && x < xi + (y - yi) / (yj - yi) * (xj - xi) {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
@@ -2,28 +2,62 @@ mod clients;
 mod config;
 mod domain;
 mod error;
+mod geofence;
+mod orbit;
 mod repo;
 mod routes;
 mod scheduler;
 mod services;
+mod solar;
+mod stats;
+mod timeparse;
+mod track;
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
 
 use axum::Router;
 use config::AppConfig;
-use repo::{CacheRepo, IssRepo, OsdrRepo};
-use services::{IssService, OsdrService, SpaceService};
-use sqlx::postgres::PgPoolOptions;
+use repo::{JobLocks, Repos};
+use services::{IssService, OrbitService, OsdrService, RetentionService, SpaceService};
 use std::sync::Arc;
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
 #[derive(Clone)]
 pub struct AppState {
     pub cfg: AppConfig,
-    pub pool: sqlx::PgPool,
+    pub locks: Arc<dyn JobLocks>,
     pub iss: Arc<IssService>,
+    pub orbit: Arc<OrbitService>,
     pub osdr: Arc<OsdrService>,
     pub space: Arc<SpaceService>,
+    pub retention: Arc<RetentionService>,
+}
+
+impl AppState {
+    pub fn new(cfg: AppConfig, repos: Repos, clients: clients::UpstreamClients) -> Self {
+        let orbit = Arc::new(OrbitService::new(repos.tle, clients.clone(), cfg.clone()));
+        Self {
+            iss: Arc::new(IssService::new(
+                repos.iss.clone(),
+                repos.events,
+                clients.clone(),
+                orbit.clone(),
+                cfg.geofences.clone(),
+            )),
+            orbit,
+            osdr: Arc::new(OsdrService::new(repos.osdr, clients.clone(), cfg.clone())),
+            space: Arc::new(SpaceService::new(repos.cache, clients)),
+            retention: Arc::new(RetentionService::new(
+                repos.retention,
+                repos.iss,
+                cfg.retention.clone(),
+                cfg.iss_partitions_ahead,
+            )),
+            locks: repos.locks,
+            cfg,
+        }
+    }
 }
 
 #[tokio::main]
@@ -35,32 +69,17 @@ async fn main() -> anyhow::Result<()> {
 
     let cfg = AppConfig::from_env()?;
 
-    let pool = PgPoolOptions::new()
-        .max_connections(cfg.db_max_connections)
-        .connect(&cfg.database_url)
-        .await?;
+    let repos = Repos::connect(&cfg.database_url, cfg.db_max_connections).await?;
 
     // init schema
-    let iss_repo = IssRepo::new(pool.clone());
-    let osdr_repo = OsdrRepo::new(pool.clone());
-    let cache_repo = CacheRepo::new(pool.clone());
-    iss_repo.ensure_schema().await?;
-    osdr_repo.ensure_schema().await?;
-    cache_repo.ensure_schema().await?;
+    repos.ensure_schema().await?;
+    repos
+        .iss
+        .ensure_partitions(chrono::Utc::now(), cfg.iss_partitions_ahead)
+        .await?;
 
     let clients = clients::UpstreamClients::new(cfg.clone())?;
-
-    let iss_service = Arc::new(IssService::new(iss_repo, clients.clone(), cfg.clone()));
-    let osdr_service = Arc::new(OsdrService::new(osdr_repo, clients.clone()));
-    let space_service = Arc::new(SpaceService::new(cache_repo, clients.clone()));
-
-    let state = AppState {
-        cfg: cfg.clone(),
-        pool: pool.clone(),
-        iss: iss_service.clone(),
-        osdr: osdr_service.clone(),
-        space: space_service.clone(),
-    };
+    let state = AppState::new(cfg, repos, clients);
 
     scheduler::spawn_jobs(state.clone());
 
@@ -0,0 +1,708 @@
+//! Two-line element sets and SGP4 propagation, following Spacetrack Report #3
+//! as revised by Vallado et al. (2006) with WGS-72 constants. Only near-Earth
+//! orbits (period under 225 minutes) are propagated, which covers the ISS and
+//! every other crewed station.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::f64::consts::{PI, TAU};
+use thiserror::Error;
+
+pub const ISS_NORAD_ID: i64 = 25544;
+
+// WGS-72, as the element sets are fitted with it
+const EARTH_RADIUS_KM: f64 = 6378.135;
+const MU: f64 = 398_600.8;
+const J2: f64 = 0.001_082_616;
+const J3: f64 = -0.000_002_538_81;
+const J4: f64 = -0.000_001_655_97;
+
+// WGS-84 ellipsoid for the geodetic output, matching wheretheiss.at
+pub(crate) const WGS84_A_KM: f64 = 6378.137;
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// Why a pair of lines is not a usable element set.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum TleError {
+    #[error("line {line}: {message}")]
+    Format { line: u8, message: String },
+    #[error("line {line}: checksum is {found}, expected {expected}")]
+    Checksum { line: u8, expected: u32, found: u32 },
+    #[error("catalog numbers differ: {0} and {1}")]
+    Mismatch(i64, i64),
+}
+
+/// Why an element set cannot be propagated to the requested time.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum OrbitError {
+    #[error("period of {0:.0} minutes needs deep-space SGP4, which is not supported")]
+    DeepSpace(f64),
+    #[error("mean eccentricity {0} is out of range")]
+    Eccentricity(f64),
+    #[error("mean motion is not positive")]
+    MeanMotion,
+    #[error("semi-latus rectum is negative")]
+    SemiLatus,
+    #[error("satellite has decayed")]
+    Decayed,
+}
+
+/// A parsed two-line element set. Angles are in degrees, mean motion in
+/// revolutions per day, as printed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tle {
+    pub norad_id: i64,
+    pub name: Option<String>,
+    pub epoch: DateTime<Utc>,
+    pub line1: String,
+    pub line2: String,
+    pub bstar: f64,
+    pub inclination: f64,
+    pub raan: f64,
+    pub eccentricity: f64,
+    pub arg_perigee: f64,
+    pub mean_anomaly: f64,
+    pub mean_motion: f64,
+}
+
+impl Tle {
+    pub fn parse(name: Option<&str>, line1: &str, line2: &str) -> Result<Self, TleError> {
+        let (l1, l2) = (line1.trim_end(), line2.trim_end());
+        check_line(l1, 1)?;
+        check_line(l2, 2)?;
+        let norad_id = field::<i64>(l1, 1, 2..7, "catalog number")?;
+        let norad_2 = field::<i64>(l2, 2, 2..7, "catalog number")?;
+        if norad_id != norad_2 {
+            return Err(TleError::Mismatch(norad_id, norad_2));
+        }
+        let year = field::<i32>(l1, 1, 18..20, "epoch year")?;
+        let day = field::<f64>(l1, 1, 20..32, "epoch day")?;
+        Ok(Self {
+            norad_id,
+            name: name
+                .map(|n| n.trim().trim_start_matches("0 ").trim().to_string())
+                .filter(|n| !n.is_empty()),
+            epoch: tle_epoch(year, day).ok_or_else(|| TleError::Format {
+                line: 1,
+                message: format!("epoch day {day} is out of range"),
+            })?,
+            line1: l1.to_string(),
+            line2: l2.to_string(),
+            bstar: implied_decimal(&l1[53..61]).ok_or_else(|| TleError::Format {
+                line: 1,
+                message: format!("bad B* `{}`", l1[53..61].trim()),
+            })?,
+            inclination: field(l2, 2, 8..16, "inclination")?,
+            raan: field(l2, 2, 17..25, "right ascension")?,
+            eccentricity: field::<f64>(l2, 2, 26..33, "eccentricity")? * 1e-7,
+            arg_perigee: field(l2, 2, 34..42, "argument of perigee")?,
+            mean_anomaly: field(l2, 2, 43..51, "mean anomaly")?,
+            mean_motion: field(l2, 2, 52..63, "mean motion")?,
+        })
+    }
+}
+
+/// Parses a catalog in the two- or three-line format; name lines are
+/// optional and may carry the `0 ` prefix. Broken sets are returned as
+/// errors and do not stop the rest of the file.
+pub fn parse_tle_text(text: &str) -> (Vec<Tle>, Vec<TleError>) {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim_end)
+        .filter(|l| !l.trim().is_empty())
+        .collect();
+    let (mut sets, mut errors) = (Vec::new(), Vec::new());
+    let mut i = 0;
+    while i < lines.len() {
+        let (name, start) = if lines[i].starts_with("1 ") {
+            (None, i)
+        } else {
+            (Some(lines[i]), i + 1)
+        };
+        let (Some(l1), Some(l2)) = (lines.get(start), lines.get(start + 1)) else {
+            if lines[i..].iter().any(|l| l.starts_with("1 ") || l.starts_with("2 ")) {
+                errors.push(TleError::Format {
+                    line: 2,
+                    message: "truncated element set at end of input".to_string(),
+                });
+            }
+            break;
+        };
+        if let Some((line, got)) = [(1, l1), (2, l2)]
+            .into_iter()
+            .find(|(n, l)| !l.starts_with(&format!("{n} ")))
+        {
+            errors.push(TleError::Format {
+                line,
+                message: format!("expected an element line, got `{got}`"),
+            });
+            i += 1;
+            continue;
+        }
+        match Tle::parse(name, l1, l2) {
+            Ok(t) => sets.push(t),
+            Err(e) => errors.push(e),
+        }
+        i = start + 2;
+    }
+    (sets, errors)
+}
+
+fn check_line(l: &str, line: u8) -> Result<(), TleError> {
+    if l.len() != 69 || !l.is_ascii() {
+        return Err(TleError::Format {
+            line,
+            message: format!("expected 69 ASCII columns, got {}", l.chars().count()),
+        });
+    }
+    let expected = l[..68]
+        .bytes()
+        .map(|b| match b {
+            b'0'..=b'9' => (b - b'0') as u32,
+            b'-' => 1,
+            _ => 0,
+        })
+        .sum::<u32>()
+        % 10;
+    let found = (l.as_bytes()[68] as char).to_digit(10).ok_or_else(|| TleError::Format {
+        line,
+        message: "checksum column is not a digit".to_string(),
+    })?;
+    if expected != found {
+        return Err(TleError::Checksum { line, expected, found });
+    }
+    Ok(())
+}
+
+fn field<T: std::str::FromStr>(
+    l: &str,
+    line: u8,
+    cols: std::ops::Range<usize>,
+    what: &str,
+) -> Result<T, TleError> {
+    let raw = l[cols].trim();
+    raw.parse().map_err(|_| TleError::Format {
+        line,
+        message: format!("bad {what} `{raw}`"),
+    })
+}
+
+/// `-11606-4` style fields: a mantissa with an implied leading `0.` and a
+/// power-of-ten exponent.
+fn implied_decimal(s: &str) -> Option<f64> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Some(0.0);
+    }
+    let (mantissa, exp) = s.split_at(s.rfind(['-', '+']).filter(|&i| i > 0)?);
+    let (sign, digits) = match mantissa.strip_prefix('-') {
+        Some(d) => (-1.0, d),
+        None => (1.0, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+    };
+    let m: f64 = format!("0.{}", digits.trim()).parse().ok()?;
+    Some(sign * m * 10f64.powi(exp.parse().ok()?))
+}
+
+/// Two-digit years 57–99 are 1957–1999, the rest 2000–2056.
+fn tle_epoch(year: i32, day: f64) -> Option<DateTime<Utc>> {
+    let year = if year < 57 { 2000 + year } else { 1900 + year };
+    if !(1.0..367.0).contains(&day) {
+        return None;
+    }
+    let jan1 = NaiveDate::from_ymd_opt(year, 1, 1)?.and_hms_opt(0, 0, 0)?.and_utc();
+    let micros = ((day - 1.0) * 86_400e6).round() as i64;
+    Some(jan1 + Duration::microseconds(micros))
+}
+
+/// Position and velocity in the TEME frame, in km and km/s.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StateVector {
+    pub position: [f64; 3],
+    pub velocity: [f64; 3],
+}
+
+impl StateVector {
+    pub fn speed_kms(&self) -> f64 {
+        norm(self.velocity)
+    }
+}
+
+/// SGP4 propagator initialised from one element set.
+#[derive(Debug, Clone)]
+pub struct Sgp4 {
+    epoch: DateTime<Utc>,
+    isimp: bool,
+    bstar: f64,
+    ecco: f64,
+    inclo: f64,
+    nodeo: f64,
+    argpo: f64,
+    mo: f64,
+    no_unkozai: f64,
+    con41: f64,
+    x1mth2: f64,
+    x7thm1: f64,
+    cc1: f64,
+    cc4: f64,
+    cc5: f64,
+    d2: f64,
+    d3: f64,
+    d4: f64,
+    delmo: f64,
+    eta: f64,
+    sinmao: f64,
+    argpdot: f64,
+    omgcof: f64,
+    xmcof: f64,
+    xlcof: f64,
+    aycof: f64,
+    mdot: f64,
+    nodedot: f64,
+    nodecf: f64,
+    t2cof: f64,
+    t3cof: f64,
+    t4cof: f64,
+    t5cof: f64,
+}
+
+fn xke() -> f64 {
+    60.0 / (EARTH_RADIUS_KM.powi(3) / MU).sqrt()
+}
+
+impl Sgp4 {
+    pub fn new(tle: &Tle) -> Result<Self, OrbitError> {
+        let xke = xke();
+        let no_kozai = tle.mean_motion * TAU / 1440.0;
+        if no_kozai <= 0.0 {
+            return Err(OrbitError::MeanMotion);
+        }
+        let ecco = tle.eccentricity;
+        let inclo = tle.inclination.to_radians();
+        let (argpo, mo) = (tle.arg_perigee.to_radians(), tle.mean_anomaly.to_radians());
+        let bstar = tle.bstar;
+
+        // recover the original mean motion and semi-major axis
+        let ak = (xke / no_kozai).powf(2.0 / 3.0);
+        let omeosq = 1.0 - ecco * ecco;
+        let rteosq = omeosq.sqrt();
+        let cosio = inclo.cos();
+        let cosio2 = cosio * cosio;
+        let d1 = 0.75 * J2 * (3.0 * cosio2 - 1.0) / (rteosq * omeosq);
+        let del = d1 / (ak * ak);
+        let adel = ak * (1.0 - del * del - del * (1.0 / 3.0 + 134.0 * del * del / 81.0));
+        let del = d1 / (adel * adel);
+        let no_unkozai = no_kozai / (1.0 + del);
+        let period = TAU / no_unkozai;
+        if period >= 225.0 {
+            return Err(OrbitError::DeepSpace(period));
+        }
+        let ao = (xke / no_unkozai).powf(2.0 / 3.0);
+        let sinio = inclo.sin();
+        let po = ao * omeosq;
+        let con42 = 1.0 - 5.0 * cosio2;
+        let con41 = -con42 - cosio2 - cosio2;
+        let posq = po * po;
+        let rp = ao * (1.0 - ecco);
+
+        // perigees below 220 km drop the higher-order drag terms
+        let isimp = rp < 220.0 / EARTH_RADIUS_KM + 1.0;
+        let mut sfour = 78.0 / EARTH_RADIUS_KM + 1.0;
+        let mut qzms24 = ((120.0 - 78.0) / EARTH_RADIUS_KM).powi(4);
+        let perige = (rp - 1.0) * EARTH_RADIUS_KM;
+        if perige < 156.0 {
+            sfour = if perige < 98.0 { 20.0 } else { perige - 78.0 };
+            qzms24 = ((120.0 - sfour) / EARTH_RADIUS_KM).powi(4);
+            sfour = sfour / EARTH_RADIUS_KM + 1.0;
+        }
+        let pinvsq = 1.0 / posq;
+        let tsi = 1.0 / (ao - sfour);
+        let eta = ao * ecco * tsi;
+        let etasq = eta * eta;
+        let eeta = ecco * eta;
+        let psisq = (1.0 - etasq).abs();
+        let coef = qzms24 * tsi.powi(4);
+        let coef1 = coef / psisq.powf(3.5);
+        let cc2 = coef1
+            * no_unkozai
+            * (ao * (1.0 + 1.5 * etasq + eeta * (4.0 + etasq))
+                + 0.375 * J2 * tsi / psisq * con41 * (8.0 + 3.0 * etasq * (8.0 + etasq)));
+        let cc1 = bstar * cc2;
+        let cc3 = if ecco > 1.0e-4 {
+            -2.0 * coef * tsi * (J3 / J2) * no_unkozai * sinio / ecco
+        } else {
+            0.0
+        };
+        let x1mth2 = 1.0 - cosio2;
+        let cc4 = 2.0
+            * no_unkozai
+            * coef1
+            * ao
+            * omeosq
+            * (eta * (2.0 + 0.5 * etasq) + ecco * (0.5 + 2.0 * etasq)
+                - J2 * tsi / (ao * psisq)
+                    * (-3.0 * con41 * (1.0 - 2.0 * eeta + etasq * (1.5 - 0.5 * eeta))
+                        + 0.75 * x1mth2 * (2.0 * etasq - eeta * (1.0 + etasq)) * (2.0 * argpo).cos()));
+        let cc5 = 2.0 * coef1 * ao * omeosq * (1.0 + 2.75 * (etasq + eeta) + eeta * etasq);
+        let cosio4 = cosio2 * cosio2;
+        let temp1 = 1.5 * J2 * pinvsq * no_unkozai;
+        let temp2 = 0.5 * temp1 * J2 * pinvsq;
+        let temp3 = -0.46875 * J4 * pinvsq * pinvsq * no_unkozai;
+        let mdot = no_unkozai
+            + 0.5 * temp1 * rteosq * con41
+            + 0.0625 * temp2 * rteosq * (13.0 - 78.0 * cosio2 + 137.0 * cosio4);
+        let argpdot = -0.5 * temp1 * con42
+            + 0.0625 * temp2 * (7.0 - 114.0 * cosio2 + 395.0 * cosio4)
+            + temp3 * (3.0 - 36.0 * cosio2 + 49.0 * cosio4);
+        let xhdot1 = -temp1 * cosio;
+        let nodedot = xhdot1
+            + (0.5 * temp2 * (4.0 - 19.0 * cosio2) + 2.0 * temp3 * (3.0 - 7.0 * cosio2)) * cosio;
+        let xmcof = if ecco > 1.0e-4 {
+            -2.0 / 3.0 * coef * bstar / eeta
+        } else {
+            0.0
+        };
+        // avoid the division by zero for inclinations of exactly 180°
+        let xlcof_den = if (cosio + 1.0).abs() > 1.5e-12 { 1.0 + cosio } else { 1.5e-12 };
+
+        let mut s = Self {
+            epoch: tle.epoch,
+            isimp,
+            bstar,
+            ecco,
+            inclo,
+            nodeo: tle.raan.to_radians(),
+            argpo,
+            mo,
+            no_unkozai,
+            con41,
+            x1mth2,
+            x7thm1: 7.0 * cosio2 - 1.0,
+            cc1,
+            cc4,
+            cc5,
+            d2: 0.0,
+            d3: 0.0,
+            d4: 0.0,
+            delmo: (1.0 + eta * mo.cos()).powi(3),
+            eta,
+            sinmao: mo.sin(),
+            argpdot,
+            omgcof: bstar * cc3 * argpo.cos(),
+            xmcof,
+            xlcof: -0.25 * (J3 / J2) * sinio * (3.0 + 5.0 * cosio) / xlcof_den,
+            aycof: -0.5 * (J3 / J2) * sinio,
+            mdot,
+            nodedot,
+            nodecf: 3.5 * omeosq * xhdot1 * cc1,
+            t2cof: 1.5 * cc1,
+            t3cof: 0.0,
+            t4cof: 0.0,
+            t5cof: 0.0,
+        };
+        if !isimp {
+            let cc1sq = cc1 * cc1;
+            s.d2 = 4.0 * ao * tsi * cc1sq;
+            let temp = s.d2 * tsi * cc1 / 3.0;
+            s.d3 = (17.0 * ao + sfour) * temp;
+            s.d4 = 0.5 * temp * ao * tsi * (221.0 * ao + 31.0 * sfour) * cc1;
+            s.t3cof = s.d2 + 2.0 * cc1sq;
+            s.t4cof = 0.25 * (3.0 * s.d3 + cc1 * (12.0 * s.d2 + 10.0 * cc1sq));
+            s.t5cof = 0.2
+                * (3.0 * s.d4 + 12.0 * cc1 * s.d3 + 6.0 * s.d2 * s.d2 + 15.0 * cc1sq * (2.0 * s.d2 + cc1sq));
+        }
+        Ok(s)
+    }
+
+    /// State at `at`, which may lie before or after the epoch.
+    pub fn at(&self, at: DateTime<Utc>) -> Result<StateVector, OrbitError> {
+        let minutes = (at - self.epoch).num_microseconds().map_or_else(
+            || (at - self.epoch).num_seconds() as f64 / 60.0,
+            |us| us as f64 / 60e6,
+        );
+        self.propagate(minutes)
+    }
+
+    /// State `t` minutes after the epoch.
+    pub fn propagate(&self, t: f64) -> Result<StateVector, OrbitError> {
+        let xke = xke();
+
+        // secular gravity and atmospheric drag
+        let xmdf = self.mo + self.mdot * t;
+        let argpdf = self.argpo + self.argpdot * t;
+        let nodedf = self.nodeo + self.nodedot * t;
+        let mut argpm = argpdf;
+        let mut mm = xmdf;
+        let t2 = t * t;
+        let mut nodem = nodedf + self.nodecf * t2;
+        let mut tempa = 1.0 - self.cc1 * t;
+        let mut tempe = self.bstar * self.cc4 * t;
+        let mut templ = self.t2cof * t2;
+        if !self.isimp {
+            let delomg = self.omgcof * t;
+            let delm = self.xmcof * ((1.0 + self.eta * xmdf.cos()).powi(3) - self.delmo);
+            let temp = delomg + delm;
+            mm = xmdf + temp;
+            argpm = argpdf - temp;
+            let t3 = t2 * t;
+            let t4 = t3 * t;
+            tempa -= self.d2 * t2 + self.d3 * t3 + self.d4 * t4;
+            tempe += self.bstar * self.cc5 * (mm.sin() - self.sinmao);
+            templ += self.t3cof * t3 + t4 * (self.t4cof + t * self.t5cof);
+        }
+        let nm = self.no_unkozai;
+        if nm <= 0.0 {
+            return Err(OrbitError::MeanMotion);
+        }
+        let am = (xke / nm).powf(2.0 / 3.0) * tempa * tempa;
+        let nm = xke / am.powf(1.5);
+        let mut em = self.ecco - tempe;
+        if !(-0.001..1.0).contains(&em) {
+            return Err(OrbitError::Eccentricity(em));
+        }
+        em = em.max(1.0e-6);
+        mm += self.no_unkozai * templ;
+        let xlm = mm + argpm + nodem;
+        nodem %= TAU;
+        argpm %= TAU;
+        let xlm = xlm % TAU;
+        let mm = (xlm - argpm - nodem) % TAU;
+        let (sinip, cosip) = self.inclo.sin_cos();
+
+        // long-period periodics
+        let axnl = em * argpm.cos();
+        let temp = 1.0 / (am * (1.0 - em * em));
+        let aynl = em * argpm.sin() + temp * self.aycof;
+        let xl = mm + argpm + nodem + temp * self.xlcof * axnl;
+
+        // Kepler's equation
+        let u = (xl - nodem) % TAU;
+        let mut eo1 = u;
+        let (mut sineo1, mut coseo1) = (0.0, 0.0);
+        let mut tem5: f64 = 9999.9;
+        let mut ktr = 1;
+        while tem5.abs() >= 1.0e-12 && ktr <= 10 {
+            (sineo1, coseo1) = eo1.sin_cos();
+            tem5 = (u - aynl * coseo1 + axnl * sineo1 - eo1)
+                / (1.0 - coseo1 * axnl - sineo1 * aynl);
+            tem5 = tem5.clamp(-0.95, 0.95);
+            eo1 += tem5;
+            ktr += 1;
+        }
+
+        // short-period periodics
+        let ecose = axnl * coseo1 + aynl * sineo1;
+        let esine = axnl * sineo1 - aynl * coseo1;
+        let el2 = axnl * axnl + aynl * aynl;
+        let pl = am * (1.0 - el2);
+        if pl < 0.0 {
+            return Err(OrbitError::SemiLatus);
+        }
+        let rl = am * (1.0 - ecose);
+        let rdotl = am.sqrt() * esine / rl;
+        let rvdotl = pl.sqrt() / rl;
+        let betal = (1.0 - el2).sqrt();
+        let temp = esine / (1.0 + betal);
+        let sinu = am / rl * (sineo1 - aynl - axnl * temp);
+        let cosu = am / rl * (coseo1 - axnl + aynl * temp);
+        let su = sinu.atan2(cosu);
+        let sin2u = (cosu + cosu) * sinu;
+        let cos2u = 1.0 - 2.0 * sinu * sinu;
+        let temp = 1.0 / pl;
+        let temp1 = 0.5 * J2 * temp;
+        let temp2 = temp1 * temp;
+
+        let mrt = rl * (1.0 - 1.5 * temp2 * betal * self.con41) + 0.5 * temp1 * self.x1mth2 * cos2u;
+        let su = su - 0.25 * temp2 * self.x7thm1 * sin2u;
+        let xnode = nodem + 1.5 * temp2 * cosip * sin2u;
+        let xinc = self.inclo + 1.5 * temp2 * cosip * sinip * cos2u;
+        let mvt = rdotl - nm * temp1 * self.x1mth2 * sin2u / xke;
+        let rvdot = rvdotl + nm * temp1 * (self.x1mth2 * cos2u + 1.5 * self.con41) / xke;
+        if mrt < 1.0 {
+            return Err(OrbitError::Decayed);
+        }
+
+        // orientation vectors
+        let (sinsu, cossu) = su.sin_cos();
+        let (snod, cnod) = xnode.sin_cos();
+        let (sini, cosi) = xinc.sin_cos();
+        let xmx = -snod * cosi;
+        let xmy = cnod * cosi;
+        let uv = [xmx * sinsu + cnod * cossu, xmy * sinsu + snod * cossu, sini * sinsu];
+        let vv = [xmx * cossu - cnod * sinsu, xmy * cossu - snod * sinsu, sini * cossu];
+        let vkmpersec = EARTH_RADIUS_KM * xke / 60.0;
+        Ok(StateVector {
+            position: uv.map(|c| mrt * c * EARTH_RADIUS_KM),
+            velocity: [0, 1, 2].map(|i| (mvt * uv[i] + rvdot * vv[i]) * vkmpersec),
+        })
+    }
+}
+
+/// Greenwich mean sidereal time (IAU 1982) in radians.
+pub fn gmst(at: DateTime<Utc>) -> f64 {
+    let tut1 = (julian_date(at) - 2_451_545.0) / 36_525.0;
+    let secs = -6.2e-6 * tut1.powi(3)
+        + 0.093_104 * tut1 * tut1
+        + (876_600.0 * 3600.0 + 8_640_184.812_866) * tut1
+        + 67_310.548_41;
+    (secs * PI / 180.0 / 240.0).rem_euclid(TAU)
+}
+
+pub fn julian_date(at: DateTime<Utc>) -> f64 {
+    at.timestamp_micros() as f64 / 86_400e6 + 2_440_587.5
+}
+
+/// Latitude and longitude in degrees and altitude in km above the WGS-84
+/// ellipsoid of a TEME position at `at` (polar motion is ignored).
+pub fn geodetic(teme: [f64; 3], at: DateTime<Utc>) -> (f64, f64, f64) {
+    let (s, c) = gmst(at).sin_cos();
+    let x = c * teme[0] + s * teme[1];
+    let y = -s * teme[0] + c * teme[1];
+    let z = teme[2];
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let p = x.hypot(y);
+    let mut lat = z.atan2(p * (1.0 - e2));
+    let mut alt = 0.0;
+    for _ in 0..6 {
+        let n = WGS84_A_KM / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+        alt = if lat.cos().abs() > 1e-9 {
+            p / lat.cos() - n
+        } else {
+            z.abs() - n * (1.0 - e2)
+        };
+        lat = z.atan2(p * (1.0 - e2 * n / (n + alt)));
+    }
+    (lat.to_degrees(), y.atan2(x).to_degrees(), alt)
+}
+
+/// The TEME position of a point given by WGS-84 latitude and longitude in
+/// degrees and altitude in km at `at`; the inverse of [`geodetic`].
+pub fn teme_from_geodetic(lat: f64, lon: f64, alt: f64, at: DateTime<Utc>) -> [f64; 3] {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let (slat, clat) = lat.to_radians().sin_cos();
+    let n = WGS84_A_KM / (1.0 - e2 * slat * slat).sqrt();
+    let (slon, clon) = (lon.to_radians() + gmst(at)).sin_cos();
+    [
+        (n + alt) * clat * clon,
+        (n + alt) * clat * slon,
+        (n * (1.0 - e2) + alt) * slat,
+    ]
+}
+
+/// A ground observer on the WGS-84 ellipsoid: degrees and km.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Observer {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+}
+
+/// Where a target appears to an [`Observer`]: azimuth in degrees clockwise
+/// from north, elevation in degrees above the horizon, range in km.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LookAngles {
+    pub azimuth: f64,
+    pub elevation: f64,
+    pub range: f64,
+}
+
+impl Observer {
+    /// The observer's TEME position at `at`.
+    pub fn position(&self, at: DateTime<Utc>) -> [f64; 3] {
+        teme_from_geodetic(self.latitude, self.longitude, self.altitude, at)
+    }
+
+    /// Look angles of a TEME position at `at`.
+    pub fn look(&self, target: [f64; 3], at: DateTime<Utc>) -> LookAngles {
+        let rho = sub(target, self.position(at));
+        let (slat, clat) = self.latitude.to_radians().sin_cos();
+        let (st, ct) = (self.longitude.to_radians() + gmst(at)).sin_cos();
+        let south = slat * ct * rho[0] + slat * st * rho[1] - clat * rho[2];
+        let east = -st * rho[0] + ct * rho[1];
+        let up = clat * ct * rho[0] + clat * st * rho[1] + slat * rho[2];
+        let range = norm(rho);
+        LookAngles {
+            azimuth: east.atan2(-south).to_degrees().rem_euclid(360.0),
+            elevation: (up / range).asin().to_degrees(),
+            range,
+        }
+    }
+}
+
+/// Apparent magnitude of a satellite seen from `observer`, given its
+/// `standard` magnitude at 1000 km and half phase (the convention of visual
+/// satellite catalogs), treating it as a diffusely reflecting sphere.
+pub fn magnitude(standard: f64, target: [f64; 3], sun: [f64; 3], observer: [f64; 3]) -> f64 {
+    let (to_sun, to_obs) = (sub(sun, target), sub(observer, target));
+    let phase = (dot(to_sun, to_obs) / (norm(to_sun) * norm(to_obs))).clamp(-1.0, 1.0).acos();
+    // Lambert sphere phase function, 1 at phase 90°
+    let lit = (phase.sin() + (PI - phase) * phase.cos()).max(1e-6);
+    standard + 5.0 * (norm(to_obs) / 1000.0).log10() - 2.5 * lit.log10()
+}
+
+/// A circular orbit through two observed positions, for when no element set
+/// is stored. Good for a few revolutions: drag and nodal regression are
+/// ignored.
+#[derive(Debug, Clone)]
+pub struct CircularOrbit {
+    at: DateTime<Utc>,
+    position: [f64; 3],
+    normal: [f64; 3],
+    /// rad/s
+    rate: f64,
+}
+
+impl CircularOrbit {
+    /// Fits the orbit through TEME positions `a` and `b`, which must be less
+    /// than half a revolution apart.
+    pub fn fit(
+        a: (DateTime<Utc>, [f64; 3]),
+        b: (DateTime<Utc>, [f64; 3]),
+    ) -> Option<Self> {
+        let dt = (b.0 - a.0).num_milliseconds() as f64 / 1000.0;
+        let n = cross(a.1, b.1);
+        let angle = norm(n).atan2(dot(a.1, b.1));
+        if dt <= 0.0 || !(1e-4..=PI - 1e-2).contains(&angle) {
+            return None;
+        }
+        let radius = (norm(a.1) + norm(b.1)) / 2.0;
+        Some(Self {
+            at: b.0,
+            position: scale(b.1, radius / norm(b.1)),
+            normal: scale(n, 1.0 / norm(n)),
+            rate: angle / dt,
+        })
+    }
+
+    /// Revolutions between the fitted position and `at`, either way.
+    pub fn revolutions(&self, at: DateTime<Utc>) -> f64 {
+        (self.rate * (at - self.at).num_milliseconds() as f64 / 1000.0).abs() / (2.0 * PI)
+    }
+
+    /// The TEME position at `at`.
+    pub fn at(&self, at: DateTime<Utc>) -> [f64; 3] {
+        let theta = self.rate * (at - self.at).num_milliseconds() as f64 / 1000.0;
+        let (s, c) = theta.sin_cos();
+        let side = cross(self.normal, self.position);
+        [0, 1, 2].map(|i| self.position[i] * c + side[i] * s)
+    }
+}
+
+pub(crate) fn norm(v: [f64; 3]) -> f64 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+pub(crate) fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+pub(crate) fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn scale(v: [f64; 3], k: f64) -> [f64; 3] {
+    v.map(|x| x * k)
+}
+
@@ -0,0 +1,796 @@
+use super::{
+    batch_ids, closest_epoch, fallback_search, iss_point, osdr_search_body, plan_osdr_batch,
+    payload_hash, removed_diff, CacheRepo, EventRepo, IssRepo, JobLocks, OsdrRepo, RetentionRepo,
+    TleRepo,
+};
+use crate::domain::{
+    GeofenceEvent, GeofenceEventQuery, GeofenceTransition, IssPoint, IssStorage, OsdrChange,
+    OsdrCursor, OsdrDetail, OsdrEnrichCandidate, OsdrEnrichFailure, OsdrFilter, OsdrItem,
+    OsdrListQuery, OsdrSearchHit, OsdrSearchQuery, OsdrSort, OsdrSyncState, OsdrUpsert, OsdrVersion,
+    OsdrVersionKind, RetentionPolicy, RetentionRun, SpaceCacheItem, TableStats, TleSet,
+};
+use crate::orbit::ISS_NORAD_ID;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Process-local backend implementing every repository trait over shared
+/// vectors. Mirrors the Postgres semantics closely enough for service and
+/// route tests; nothing is persisted.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    inner: Arc<Mutex<Tables>>,
+}
+
+#[derive(Default)]
+struct Tables {
+    next_id: i64,
+    iss: Vec<IssRow>,
+    osdr: Vec<OsdrItem>,
+    osdr_details: HashMap<String, OsdrDetail>,
+    osdr_enrich_failures: HashMap<String, OsdrEnrichFailure>,
+    osdr_versions: Vec<OsdrVersion>,
+    osdr_sync: OsdrSyncState,
+    cache: Vec<CacheRow>,
+    runs: Vec<RetentionRun>,
+    tle: Vec<TleSet>,
+    events: Vec<GeofenceEvent>,
+    locks: HashSet<i64>,
+}
+
+impl Tables {
+    fn id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+struct IssRow {
+    id: i64,
+    norad_id: i64,
+    fetched_at: DateTime<Utc>,
+    source_url: String,
+    payload: Value,
+}
+
+struct CacheRow {
+    id: i64,
+    source: String,
+    fetched_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    check_count: i32,
+    payload_hash: String,
+    payload: Value,
+}
+
+impl CacheRow {
+    fn item(&self) -> SpaceCacheItem {
+        SpaceCacheItem {
+            source: self.source.clone(),
+            fetched_at: self.fetched_at,
+            last_seen_at: Some(self.last_seen_at),
+            check_count: self.check_count,
+            payload_hash: Some(self.payload_hash.clone()),
+            payload: self.payload.clone(),
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn search_rows(&self, filter: &OsdrFilter) -> Vec<(OsdrItem, String)> {
+        self.tables()
+            .osdr
+            .iter()
+            .filter(|r| osdr_matches(r, filter))
+            .map(|r| (r.clone(), osdr_search_body(&r.raw)))
+            .collect()
+    }
+
+    /// Seeds an ISS log row with an explicit timestamp.
+    pub fn push_iss(&self, fetched_at: DateTime<Utc>, source_url: &str, payload: Value) {
+        self.push_sat(ISS_NORAD_ID, fetched_at, source_url, payload);
+    }
+
+    /// Seeds a position row of any tracked satellite.
+    pub fn push_sat(
+        &self,
+        norad_id: i64,
+        fetched_at: DateTime<Utc>,
+        source_url: &str,
+        payload: Value,
+    ) {
+        let mut t = self.tables();
+        let id = t.id();
+        t.iss.push(IssRow {
+            id,
+            norad_id,
+            fetched_at,
+            source_url: source_url.to_string(),
+            payload,
+        });
+        t.iss.sort_by_key(|r| (r.fetched_at, r.id));
+    }
+}
+
+#[async_trait]
+impl IssRepo for MemoryStore {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn insert_log(
+        &self,
+        norad_id: i64,
+        source_url: &str,
+        payload: &Value,
+    ) -> anyhow::Result<DateTime<Utc>> {
+        let fetched_at = Utc::now();
+        self.push_sat(norad_id, fetched_at, source_url, payload.clone());
+        Ok(fetched_at)
+    }
+
+    async fn last(
+        &self,
+        norad_id: i64,
+    ) -> anyhow::Result<Option<(i64, DateTime<Utc>, String, Value)>> {
+        Ok(self
+            .tables()
+            .iss
+            .iter()
+            .rev()
+            .find(|r| r.norad_id == norad_id)
+            .map(|r| (r.id, r.fetched_at, r.source_url.clone(), r.payload.clone())))
+    }
+
+    async fn trend(&self, norad_id: i64, limit: i64) -> anyhow::Result<Vec<IssPoint>> {
+        let t = self.tables();
+        let rows: Vec<&IssRow> = t.iss.iter().filter(|r| r.norad_id == norad_id).collect();
+        let skip = rows.len().saturating_sub(limit.max(2) as usize);
+        Ok(rows[skip..]
+            .iter()
+            .map(|r| iss_point(r.fetched_at, &r.payload))
+            .collect())
+    }
+
+    async fn range(
+        &self,
+        norad_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<IssPoint>> {
+        let t = self.tables();
+        let mut rows: Vec<&IssRow> = t
+            .iss
+            .iter()
+            .filter(|r| r.norad_id == norad_id && r.fetched_at >= from && r.fetched_at <= to)
+            .collect();
+        rows.sort_by_key(|r| r.fetched_at);
+        Ok(rows.iter().map(|r| iss_point(r.fetched_at, &r.payload)).collect())
+    }
+
+    async fn storage(&self) -> anyhow::Result<Option<IssStorage>> {
+        Ok(Some(IssStorage::Memory))
+    }
+}
+
+#[async_trait]
+impl OsdrRepo for MemoryStore {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn upsert(&self, item: OsdrUpsert) -> anyhow::Result<OsdrChange> {
+        if item.dataset_id.is_some() {
+            let changes = self.upsert_batch(std::slice::from_ref(&item)).await?;
+            return Ok(changes[0]);
+        }
+        let mut t = self.tables();
+        let id = t.id();
+        t.osdr.push(OsdrItem {
+            id,
+            dataset_id: None,
+            title: item.title,
+            status: item.status,
+            updated_at: item.updated_at,
+            inserted_at: Utc::now(),
+            removed_at: None,
+            raw: item.raw,
+        });
+        Ok(OsdrChange::Created)
+    }
+
+    async fn upsert_batch(&self, items: &[OsdrUpsert]) -> anyhow::Result<Vec<OsdrChange>> {
+        let mut t = self.tables();
+        let ids: HashSet<String> = batch_ids(items).into_iter().collect();
+        let old: HashMap<String, OsdrItem> = t
+            .osdr
+            .iter()
+            .filter_map(|r| Some((r.dataset_id.clone().filter(|ds| ids.contains(ds))?, r.clone())))
+            .collect();
+        let (changes, writes) = plan_osdr_batch(&old, items)?;
+        let now = Utc::now();
+        for w in writes {
+            let item = w.item.clone();
+            match t.osdr.iter_mut().find(|r| r.dataset_id.as_deref() == Some(w.dataset_id)) {
+                Some(existing) => {
+                    existing.title = item.title.clone();
+                    existing.status = item.status;
+                    existing.updated_at = item.updated_at;
+                    existing.raw = item.raw;
+                    existing.removed_at = None;
+                }
+                None => {
+                    let id = t.id();
+                    t.osdr.push(OsdrItem {
+                        id,
+                        dataset_id: item.dataset_id,
+                        title: item.title.clone(),
+                        status: item.status,
+                        updated_at: item.updated_at,
+                        inserted_at: now,
+                        removed_at: None,
+                        raw: item.raw,
+                    });
+                }
+            }
+            let version = t
+                .osdr_versions
+                .iter()
+                .filter(|v| v.dataset_id == w.dataset_id)
+                .map(|v| v.version)
+                .max()
+                .unwrap_or(0)
+                + 1;
+            t.osdr_versions.push(OsdrVersion {
+                dataset_id: w.dataset_id.to_string(),
+                version,
+                changed_at: now,
+                kind: w.kind,
+                title: item.title,
+                diff: w.diff,
+            });
+        }
+        Ok(changes)
+    }
+
+    async fn list(&self, q: &OsdrListQuery) -> anyhow::Result<Vec<OsdrItem>> {
+        let after = match &q.after {
+            Some(c) => Some((cursor_key(c)?, c.id)),
+            None => None,
+        };
+        let mut items: Vec<OsdrItem> = self
+            .tables()
+            .osdr
+            .iter()
+            .filter(|r| osdr_matches(r, &q.filter))
+            .filter(|r| match &after {
+                Some(pos) => {
+                    let ord = (sort_key(r, q.sort), r.id).cmp(pos);
+                    if q.desc {
+                        ord.is_lt()
+                    } else {
+                        ord.is_gt()
+                    }
+                }
+                None => true,
+            })
+            .cloned()
+            .collect();
+        items.sort_by(|a, b| {
+            let ord = (sort_key(a, q.sort), a.id).cmp(&(sort_key(b, q.sort), b.id));
+            if q.desc {
+                ord.reverse()
+            } else {
+                ord
+            }
+        });
+        items.truncate(q.limit.max(0) as usize);
+        Ok(items)
+    }
+
+    async fn count(&self, filter: &OsdrFilter) -> anyhow::Result<i64> {
+        Ok(self
+            .tables()
+            .osdr
+            .iter()
+            .filter(|r| osdr_matches(r, filter))
+            .count() as i64)
+    }
+
+    async fn get(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrItem>> {
+        Ok(self
+            .tables()
+            .osdr
+            .iter()
+            .find(|r| r.dataset_id.as_deref() == Some(dataset_id))
+            .cloned())
+    }
+
+    async fn enrich_candidates(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<OsdrEnrichCandidate>> {
+        let t = self.tables();
+        let mut out: Vec<_> = t
+            .osdr
+            .iter()
+            .filter(|r| r.removed_at.is_none())
+            .filter_map(|r| {
+                let ds = r.dataset_id.as_ref()?;
+                let fresh = t
+                    .osdr_details
+                    .get(ds)
+                    .is_some_and(|d| d.source_updated_at == r.updated_at);
+                let failure = t.osdr_enrich_failures.get(ds);
+                if fresh || failure.is_some_and(|f| f.retry_at > now) {
+                    return None;
+                }
+                let attempts = failure.map_or(0, |f| f.attempts);
+                Some((r.updated_at.is_none(), r.updated_at, r.id, ds.clone(), attempts))
+            })
+            .collect();
+        out.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(b.2.cmp(&a.2)));
+        Ok(out
+            .into_iter()
+            .take(limit.max(0) as usize)
+            .map(|(_, updated_at, _, dataset_id, attempts)| OsdrEnrichCandidate {
+                dataset_id,
+                updated_at,
+                attempts,
+            })
+            .collect())
+    }
+
+    async fn save_detail(&self, d: &OsdrDetail) -> anyhow::Result<()> {
+        let mut t = self.tables();
+        t.osdr_enrich_failures.remove(&d.dataset_id);
+        t.osdr_details.insert(d.dataset_id.clone(), d.clone());
+        Ok(())
+    }
+
+    async fn record_enrich_failure(&self, f: &OsdrEnrichFailure) -> anyhow::Result<()> {
+        self.tables()
+            .osdr_enrich_failures
+            .insert(f.dataset_id.clone(), f.clone());
+        Ok(())
+    }
+
+    async fn enrich_failure(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrEnrichFailure>> {
+        Ok(self.tables().osdr_enrich_failures.get(dataset_id).cloned())
+    }
+
+    async fn detail(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrDetail>> {
+        Ok(self.tables().osdr_details.get(dataset_id).cloned())
+    }
+
+    async fn search(&self, q: &OsdrSearchQuery) -> anyhow::Result<Vec<OsdrSearchHit>> {
+        Ok(fallback_search(self.search_rows(&q.filter), q).0)
+    }
+
+    async fn search_count(&self, q: &OsdrSearchQuery) -> anyhow::Result<i64> {
+        Ok(fallback_search(self.search_rows(&q.filter), q).1)
+    }
+
+    async fn mark_removed(
+        &self,
+        seen: &[String],
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut t = self.tables();
+        let mut removed = Vec::new();
+        for r in t.osdr.iter_mut().filter(|r| r.removed_at.is_none()) {
+            if let Some(ds) = r.dataset_id.as_ref().filter(|ds| !seen.contains(ds)) {
+                r.removed_at = Some(at);
+                removed.push((ds.clone(), r.title.clone()));
+            }
+        }
+        for (ds, title) in &removed {
+            let version = t
+                .osdr_versions
+                .iter()
+                .filter(|v| &v.dataset_id == ds)
+                .map(|v| v.version)
+                .max()
+                .unwrap_or(0)
+                + 1;
+            t.osdr_versions.push(OsdrVersion {
+                dataset_id: ds.clone(),
+                version,
+                changed_at: at,
+                kind: OsdrVersionKind::Removed,
+                title: title.clone(),
+                diff: removed_diff(at),
+            });
+        }
+        Ok(removed.into_iter().map(|(ds, _)| ds).collect())
+    }
+
+    async fn history(&self, dataset_id: &str, limit: i64) -> anyhow::Result<Vec<OsdrVersion>> {
+        Ok(self
+            .tables()
+            .osdr_versions
+            .iter()
+            .rev()
+            .filter(|v| v.dataset_id == dataset_id)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn recent_changes(
+        &self,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<OsdrVersion>> {
+        let t = self.tables();
+        let mut latest: HashMap<&str, &OsdrVersion> = HashMap::new();
+        for v in &t.osdr_versions {
+            latest.insert(&v.dataset_id, v);
+        }
+        let mut out: Vec<OsdrVersion> = latest
+            .into_values()
+            .filter(|v| since.is_none_or(|s| v.changed_at >= s))
+            .cloned()
+            .collect();
+        out.sort_by(|a, b| {
+            b.changed_at
+                .cmp(&a.changed_at)
+                .then_with(|| a.dataset_id.cmp(&b.dataset_id))
+        });
+        out.truncate(limit.max(0) as usize);
+        Ok(out)
+    }
+
+    async fn sync_state(&self) -> anyhow::Result<OsdrSyncState> {
+        Ok(self.tables().osdr_sync.clone())
+    }
+
+    async fn save_sync_state(&self, state: &OsdrSyncState) -> anyhow::Result<()> {
+        let stored = &mut self.tables().osdr_sync;
+        stored.watermark = stored.watermark.max(state.watermark);
+        stored.last_full_at = stored.last_full_at.max(state.last_full_at);
+        stored.last_sync_at = stored.last_sync_at.max(state.last_sync_at);
+        Ok(())
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Time(Option<DateTime<Utc>>),
+    Text(String),
+}
+
+fn sort_key(item: &OsdrItem, sort: OsdrSort) -> SortKey {
+    match sort {
+        OsdrSort::InsertedAt => SortKey::Time(Some(item.inserted_at)),
+        OsdrSort::UpdatedAt => SortKey::Time(item.updated_at),
+        OsdrSort::Title => SortKey::Text(item.title.clone().unwrap_or_default()),
+    }
+}
+
+fn cursor_key(c: &OsdrCursor) -> anyhow::Result<SortKey> {
+    Ok(match c.sort {
+        OsdrSort::InsertedAt | OsdrSort::UpdatedAt => SortKey::Time(c.key_time()?),
+        OsdrSort::Title => SortKey::Text(c.key.clone().unwrap_or_default()),
+    })
+}
+
+fn osdr_matches(item: &OsdrItem, f: &OsdrFilter) -> bool {
+    if !f.include_removed && item.removed_at.is_some() {
+        return false;
+    }
+    if f.status.is_some() && item.status != f.status {
+        return false;
+    }
+    if f.updated_from.is_some_and(|from| item.updated_at.is_none_or(|at| at < from)) {
+        return false;
+    }
+    if f.updated_to.is_some_and(|to| item.updated_at.is_none_or(|at| at >= to)) {
+        return false;
+    }
+    match &f.dataset_id_prefix {
+        Some(prefix) => item
+            .dataset_id
+            .as_deref()
+            .is_some_and(|ds| ds.starts_with(prefix.as_str())),
+        None => true,
+    }
+}
+
+#[async_trait]
+impl CacheRepo for MemoryStore {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn write(&self, source: &str, payload: Value) -> anyhow::Result<bool> {
+        let hash = payload_hash(&payload);
+        let now = Utc::now();
+        let mut t = self.tables();
+        if let Some(latest) = t.cache.iter_mut().rev().find(|r| r.source == source) {
+            if latest.payload_hash == hash {
+                latest.last_seen_at = now;
+                latest.check_count += 1;
+                return Ok(false);
+            }
+        }
+        let id = t.id();
+        t.cache.push(CacheRow {
+            id,
+            source: source.to_string(),
+            fetched_at: now,
+            last_seen_at: now,
+            check_count: 1,
+            payload_hash: hash,
+            payload,
+        });
+        Ok(true)
+    }
+
+    async fn latest(&self, source: &str) -> anyhow::Result<Option<SpaceCacheItem>> {
+        Ok(self
+            .tables()
+            .cache
+            .iter()
+            .rev()
+            .find(|r| r.source == source)
+            .map(CacheRow::item))
+    }
+
+    async fn history(&self, source: &str, limit: i64) -> anyhow::Result<Vec<SpaceCacheItem>> {
+        Ok(self
+            .tables()
+            .cache
+            .iter()
+            .rev()
+            .filter(|r| r.source == source)
+            .take(limit.max(0) as usize)
+            .map(CacheRow::item)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl RetentionRepo for MemoryStore {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn apply(
+        &self,
+        policy: &RetentionPolicy,
+        skip_sources: &[String],
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<(i64, i64)> {
+        let mut t = self.tables();
+        // iss rows are partitioned per satellite the way cache rows are per source
+        let rows: Vec<(i64, DateTime<Utc>, String)> = match policy.table.as_str() {
+            "iss_fetch_log" => t
+                .iss
+                .iter()
+                .map(|r| (r.id, r.fetched_at, r.norad_id.to_string()))
+                .collect(),
+            "space_cache" => t
+                .cache
+                .iter()
+                .map(|r| (r.id, r.fetched_at, r.source.clone()))
+                .collect(),
+            other => anyhow::bail!("retention: unknown table `{other}`"),
+        };
+        let rows: Vec<(i64, DateTime<Utc>, &str)> =
+            rows.iter().map(|(id, at, key)| (*id, *at, key.as_str())).collect();
+        let (downsampled, expired) = retention_victims(&rows, policy, skip_sources, now)?;
+        let (n_down, n_exp) = (downsampled.len() as i64, expired.len() as i64);
+        let gone = |id: &i64| downsampled.contains(id) || expired.contains(id);
+        t.iss.retain(|r| policy.table != "iss_fetch_log" || !gone(&r.id));
+        t.cache.retain(|r| policy.table != "space_cache" || !gone(&r.id));
+        Ok((n_down, n_exp))
+    }
+
+    async fn record(
+        &self,
+        target: &str,
+        downsampled: i64,
+        expired: i64,
+    ) -> anyhow::Result<RetentionRun> {
+        let run = RetentionRun {
+            target: target.to_string(),
+            ran_at: Utc::now(),
+            downsampled,
+            expired,
+        };
+        self.tables().runs.push(run.clone());
+        Ok(run)
+    }
+
+    async fn table_stats(&self) -> anyhow::Result<Vec<TableStats>> {
+        let t = self.tables();
+        let reclaimed = |table: &str| -> i64 {
+            t.runs
+                .iter()
+                .filter(|r| r.target == table || r.target.starts_with(&format!("{table}/")))
+                .map(|r| r.downsampled + r.expired)
+                .sum()
+        };
+        let bytes = |payloads: Vec<&Value>| payloads.iter().map(|p| p.to_string().len() as i64).sum();
+        Ok(vec![
+            TableStats {
+                table: "iss_fetch_log".to_string(),
+                rows: t.iss.len() as i64,
+                total_bytes: bytes(t.iss.iter().map(|r| &r.payload).collect()),
+                reclaimed_rows: reclaimed("iss_fetch_log"),
+            },
+            TableStats {
+                table: "space_cache".to_string(),
+                rows: t.cache.len() as i64,
+                total_bytes: bytes(t.cache.iter().map(|r| &r.payload).collect()),
+                reclaimed_rows: reclaimed("space_cache"),
+            },
+        ])
+    }
+
+    async fn last_runs(&self) -> anyhow::Result<Vec<RetentionRun>> {
+        let mut latest: HashMap<String, RetentionRun> = HashMap::new();
+        for run in &self.tables().runs {
+            latest.insert(run.target.clone(), run.clone());
+        }
+        let mut out: Vec<_> = latest.into_values().collect();
+        out.sort_by(|a, b| a.target.cmp(&b.target));
+        Ok(out)
+    }
+}
+
+#[async_trait]
+impl TleRepo for MemoryStore {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn insert(&self, sets: &[TleSet]) -> anyhow::Result<usize> {
+        let mut t = self.tables();
+        let mut inserted = 0;
+        for s in sets {
+            if !t.tle.iter().any(|x| x.norad_id == s.norad_id && x.epoch == s.epoch) {
+                t.tle.push(s.clone());
+                inserted += 1;
+            }
+        }
+        Ok(inserted)
+    }
+
+    async fn nearest(&self, norad_id: i64, at: DateTime<Utc>) -> anyhow::Result<Option<TleSet>> {
+        let t = self.tables();
+        Ok(closest_epoch(t.tle.iter().filter(|s| s.norad_id == norad_id).cloned(), at))
+    }
+
+    async fn history(&self, norad_id: i64, limit: i64) -> anyhow::Result<Vec<TleSet>> {
+        let mut sets: Vec<TleSet> = self
+            .tables()
+            .tle
+            .iter()
+            .filter(|s| s.norad_id == norad_id)
+            .cloned()
+            .collect();
+        sets.sort_by_key(|s| std::cmp::Reverse(s.epoch));
+        sets.truncate(limit.max(0) as usize);
+        Ok(sets)
+    }
+}
+
+#[async_trait]
+impl EventRepo for MemoryStore {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn insert(&self, events: &[GeofenceEvent]) -> anyhow::Result<()> {
+        let mut t = self.tables();
+        for e in events {
+            let id = t.id();
+            t.events.push(GeofenceEvent { id, ..e.clone() });
+        }
+        Ok(())
+    }
+
+    async fn inside(&self, norad_id: i64) -> anyhow::Result<Vec<String>> {
+        let t = self.tables();
+        let mut latest: HashMap<&str, GeofenceTransition> = HashMap::new();
+        for e in t.events.iter().filter(|e| e.norad_id == norad_id) {
+            latest.insert(&e.fence, e.kind);
+        }
+        Ok(latest
+            .into_iter()
+            .filter(|(_, kind)| *kind == GeofenceTransition::Enter)
+            .map(|(fence, _)| fence.to_string())
+            .collect())
+    }
+
+    async fn list(&self, q: &GeofenceEventQuery) -> anyhow::Result<Vec<GeofenceEvent>> {
+        Ok(self
+            .tables()
+            .events
+            .iter()
+            .rev()
+            .filter(|e| q.norad_id.is_none_or(|id| e.norad_id == id))
+            .filter(|e| q.fence.as_ref().is_none_or(|f| &e.fence == f))
+            .filter(|e| q.since.is_none_or(|s| e.at >= s))
+            .take(q.limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl JobLocks for MemoryStore {
+    async fn try_lock(&self, key: i64) -> bool {
+        self.tables().locks.insert(key)
+    }
+
+    async fn unlock(&self, key: i64) -> bool {
+        self.tables().locks.remove(&key)
+    }
+}
+
+/// Ids to downsample and to expire under `policy`, following the same rules
+/// as the SQL in the Postgres backend. `rows` are `(id, fetched_at, source)`.
+fn retention_victims(
+    rows: &[(i64, DateTime<Utc>, &str)],
+    policy: &RetentionPolicy,
+    skip_sources: &[String],
+    now: DateTime<Utc>,
+) -> anyhow::Result<(HashSet<i64>, HashSet<i64>)> {
+    let mut newest: HashMap<&str, i64> = HashMap::new();
+    for (id, _, src) in rows {
+        let e = newest.entry(src).or_insert(*id);
+        *e = (*e).max(*id);
+    }
+    let in_scope = |id: i64, src: &str| {
+        let scoped = match &policy.source {
+            Some(s) => s == src,
+            None => !skip_sources.iter().any(|s| s == src),
+        };
+        scoped && newest.get(src) != Some(&id)
+    };
+
+    let mut downsampled = HashSet::new();
+    if let (Some(raw), Some(bucket)) = (policy.raw, policy.bucket) {
+        let cut = now - chrono::Duration::from_std(raw)?;
+        let bucket = bucket.as_secs() as i64;
+        let mut keep: HashMap<(&str, i64), (DateTime<Utc>, i64)> = HashMap::new();
+        for (id, at, src) in rows.iter().filter(|r| r.1 < cut) {
+            let k = (*src, at.timestamp().div_euclid(bucket));
+            let e = keep.entry(k).or_insert((*at, *id));
+            if (*at, *id) < *e {
+                *e = (*at, *id);
+            }
+        }
+        let kept: HashSet<i64> = keep.values().map(|(_, id)| *id).collect();
+        downsampled = rows
+            .iter()
+            .filter(|(id, at, src)| *at < cut && in_scope(*id, src) && !kept.contains(id))
+            .map(|r| r.0)
+            .collect();
+    }
+
+    let mut expired = HashSet::new();
+    if let Some(max) = policy.max.or(if policy.bucket.is_none() { policy.raw } else { None }) {
+        let cut = now - chrono::Duration::from_std(max)?;
+        expired = rows
+            .iter()
+            .filter(|(id, at, src)| *at < cut && in_scope(*id, src) && !downsampled.contains(id))
+            .map(|r| r.0)
+            .collect();
+    }
+    Ok((downsampled, expired))
+}
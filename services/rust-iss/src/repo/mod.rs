@@ -0,0 +1,680 @@
+//! Storage layer. Services only see the traits below; `pg` is the production
+//! backend, `sqlite` (behind the `sqlite` feature) serves laptops and edge
+//! boxes, and `memory` keeps everything in process for tests.
+
+mod memory;
+mod pg;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use memory::MemoryStore;
+pub use pg::{
+    PgCacheRepo, PgEventRepo, PgIssRepo, PgJobLocks, PgOsdrRepo, PgRetentionRepo, PgTleRepo,
+};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{
+    SqliteCacheRepo, SqliteEventRepo, SqliteIssRepo, SqliteJobLocks, SqliteOsdrRepo,
+    SqliteRetentionRepo, SqliteTleRepo,
+};
+
+use crate::domain::{
+    GeofenceEvent, GeofenceEventQuery, IssPoint, IssStorage, OsdrChange, OsdrDetail,
+    OsdrEnrichCandidate, OsdrEnrichFailure, OsdrFilter, OsdrItem, OsdrListQuery, OsdrSearchHit,
+    OsdrSearchQuery, OsdrSyncState, OsdrUpsert, OsdrVersion, OsdrVersionKind, RetentionPolicy,
+    RetentionRun, SpaceCacheItem, TableStats, TleSet,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+#[async_trait]
+pub trait IssRepo: Send + Sync {
+    async fn ensure_schema(&self) -> anyhow::Result<()>;
+
+    /// Logs a fetched position and returns the `fetched_at` it was stored with.
+    async fn insert_log(&self, norad_id: i64, source_url: &str, payload: &Value)
+        -> anyhow::Result<DateTime<Utc>>;
+
+    async fn last(&self, norad_id: i64)
+        -> anyhow::Result<Option<(i64, DateTime<Utc>, String, Value)>>;
+
+    /// The latest `limit` points of `norad_id`, oldest first.
+    async fn trend(&self, norad_id: i64, limit: i64) -> anyhow::Result<Vec<IssPoint>>;
+
+    /// All positions fetched between `from` and `to` inclusive, oldest first.
+    async fn range(
+        &self,
+        norad_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<IssPoint>>;
+
+    /// How `iss_fetch_log` is stored, or `None` before it is created.
+    async fn storage(&self) -> anyhow::Result<Option<IssStorage>>;
+
+    /// Creates the partitions for the month of `now` and `ahead` months after
+    /// it. Returns the names of the partitions that did not exist yet.
+    async fn ensure_partitions(
+        &self,
+        _now: DateTime<Utc>,
+        _ahead: u32,
+    ) -> anyhow::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Drops whole partitions that end before `before`. Returns the dropped
+    /// names and the number of rows they held.
+    async fn drop_partitions_before(
+        &self,
+        _before: DateTime<Utc>,
+    ) -> anyhow::Result<(Vec<String>, i64)> {
+        Ok((Vec::new(), 0))
+    }
+}
+
+#[async_trait]
+pub trait OsdrRepo: Send + Sync {
+    async fn ensure_schema(&self) -> anyhow::Result<()>;
+
+    /// Inserts or updates by `dataset_id`, clearing any tombstone. A real
+    /// change also records an `osdr_versions` row with the diff from
+    /// [`osdr_diff`]; identical data is left untouched.
+    async fn upsert(&self, item: OsdrUpsert) -> anyhow::Result<OsdrChange>;
+
+    /// [`OsdrRepo::upsert`] for a page of items in one transaction, using
+    /// multi-row statements. Every item needs a distinct `dataset_id`; the
+    /// changes are returned in input order.
+    async fn upsert_batch(&self, items: &[OsdrUpsert]) -> anyhow::Result<Vec<OsdrChange>>;
+
+    /// Up to `q.limit` rows matching `q.filter`, ordered by `q.sort` then
+    /// `id`, strictly after `q.after` when set.
+    async fn list(&self, q: &OsdrListQuery) -> anyhow::Result<Vec<OsdrItem>>;
+
+    /// Number of rows matching `filter`.
+    async fn count(&self, filter: &OsdrFilter) -> anyhow::Result<i64>;
+
+    async fn get(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrItem>>;
+
+    /// Datasets with no stored detail, or whose `updated_at` moved since the
+    /// detail was fetched, leaving out failed ones whose retry is after
+    /// `now`; most recently updated first.
+    async fn enrich_candidates(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<OsdrEnrichCandidate>>;
+
+    /// Replaces the stored detail and its child rows for `d.dataset_id` and
+    /// clears its enrichment failure.
+    async fn save_detail(&self, d: &OsdrDetail) -> anyhow::Result<()>;
+
+    /// Stores `f` in place of the dataset's previous failure.
+    async fn record_enrich_failure(&self, f: &OsdrEnrichFailure) -> anyhow::Result<()>;
+
+    /// The failure recorded for `dataset_id`, unless a detail was saved since.
+    async fn enrich_failure(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrEnrichFailure>>;
+
+    async fn detail(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrDetail>>;
+
+    /// Up to `q.limit` matches of `q.text`, best rank first, strictly after
+    /// `q.after` when set.
+    async fn search(&self, q: &OsdrSearchQuery) -> anyhow::Result<Vec<OsdrSearchHit>>;
+
+    /// Number of matches of `q.text` and `q.filter`, ignoring the cursor.
+    async fn search_count(&self, q: &OsdrSearchQuery) -> anyhow::Result<i64>;
+
+    /// Tombstones every live dataset whose id is not in `seen` and records a
+    /// `removed` version for each. Returns the affected ids.
+    async fn mark_removed(&self, seen: &[String], at: DateTime<Utc>)
+        -> anyhow::Result<Vec<String>>;
+
+    /// Versions of one dataset, newest first.
+    async fn history(&self, dataset_id: &str, limit: i64) -> anyhow::Result<Vec<OsdrVersion>>;
+
+    /// Latest version of each recently changed dataset, newest first.
+    async fn recent_changes(
+        &self,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<OsdrVersion>>;
+
+    /// The stored sync progress, or the default before the first sync.
+    async fn sync_state(&self) -> anyhow::Result<OsdrSyncState>;
+
+    /// Merges `state` into the stored progress. Each timestamp only moves
+    /// forward, so the incremental and the full pass, which run on separate
+    /// schedules, do not undo each other's progress.
+    async fn save_sync_state(&self, state: &OsdrSyncState) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+pub trait CacheRepo: Send + Sync {
+    async fn ensure_schema(&self) -> anyhow::Result<()>;
+
+    /// Stores `payload` unless it is identical to the latest row of `source`,
+    /// in which case that row's `last_seen_at`/`check_count` are bumped.
+    /// Returns `true` when a new row was written.
+    async fn write(&self, source: &str, payload: Value) -> anyhow::Result<bool>;
+
+    async fn latest(&self, source: &str) -> anyhow::Result<Option<SpaceCacheItem>>;
+
+    /// Distinct payload versions of `source`, newest first.
+    async fn history(&self, source: &str, limit: i64) -> anyhow::Result<Vec<SpaceCacheItem>>;
+}
+
+#[async_trait]
+pub trait RetentionRepo: Send + Sync {
+    async fn ensure_schema(&self) -> anyhow::Result<()>;
+
+    /// Applies one policy and returns `(downsampled, expired)` row counts.
+    /// `skip_sources` lists the sources that have their own policy when
+    /// `policy` is table-wide. The newest row of a table (or of each cache
+    /// source) is never removed.
+    async fn apply(
+        &self,
+        policy: &RetentionPolicy,
+        skip_sources: &[String],
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<(i64, i64)>;
+
+    async fn record(
+        &self,
+        target: &str,
+        downsampled: i64,
+        expired: i64,
+    ) -> anyhow::Result<RetentionRun>;
+
+    async fn table_stats(&self) -> anyhow::Result<Vec<TableStats>>;
+
+    /// The most recent run of every target.
+    async fn last_runs(&self) -> anyhow::Result<Vec<RetentionRun>>;
+}
+
+#[async_trait]
+pub trait TleRepo: Send + Sync {
+    async fn ensure_schema(&self) -> anyhow::Result<()>;
+
+    /// Stores the sets whose `(norad_id, epoch)` is not stored yet and
+    /// returns how many were new.
+    async fn insert(&self, sets: &[TleSet]) -> anyhow::Result<usize>;
+
+    /// The set of `norad_id` whose epoch is closest to `at`.
+    async fn nearest(&self, norad_id: i64, at: DateTime<Utc>) -> anyhow::Result<Option<TleSet>>;
+
+    /// The latest `limit` sets of `norad_id`, newest epoch first.
+    async fn history(&self, norad_id: i64, limit: i64) -> anyhow::Result<Vec<TleSet>>;
+}
+
+#[async_trait]
+pub trait EventRepo: Send + Sync {
+    async fn ensure_schema(&self) -> anyhow::Result<()>;
+
+    /// Stores the events; their `id` is assigned by the store.
+    async fn insert(&self, events: &[GeofenceEvent]) -> anyhow::Result<()>;
+
+    /// Fences whose latest event for `norad_id` is an entry.
+    async fn inside(&self, norad_id: i64) -> anyhow::Result<Vec<String>>;
+
+    /// Events matching `q`, newest first.
+    async fn list(&self, q: &GeofenceEventQuery) -> anyhow::Result<Vec<GeofenceEvent>>;
+}
+
+/// Cross-instance mutual exclusion for scheduled jobs.
+#[async_trait]
+pub trait JobLocks: Send + Sync {
+    async fn try_lock(&self, key: i64) -> bool;
+
+    async fn unlock(&self, key: i64) -> bool;
+}
+
+/// One backend's worth of repositories.
+#[derive(Clone)]
+pub struct Repos {
+    pub iss: Arc<dyn IssRepo>,
+    pub osdr: Arc<dyn OsdrRepo>,
+    pub cache: Arc<dyn CacheRepo>,
+    pub retention: Arc<dyn RetentionRepo>,
+    pub tle: Arc<dyn TleRepo>,
+    pub events: Arc<dyn EventRepo>,
+    pub locks: Arc<dyn JobLocks>,
+}
+
+impl Repos {
+    /// Picks the backend from the `DATABASE_URL` scheme.
+    pub async fn connect(database_url: &str, max_connections: u32) -> anyhow::Result<Self> {
+        if database_url.starts_with("sqlite:") {
+            return Self::connect_sqlite(database_url, max_connections).await;
+        }
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(database_url)
+            .await?;
+        Ok(Self::postgres(pool))
+    }
+
+    #[cfg(feature = "sqlite")]
+    async fn connect_sqlite(database_url: &str, max_connections: u32) -> anyhow::Result<Self> {
+        use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+        use std::str::FromStr;
+
+        let opts = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .busy_timeout(std::time::Duration::from_secs(10));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .connect_with(opts)
+            .await?;
+        Self::sqlite(pool).await
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    async fn connect_sqlite(_database_url: &str, _max_connections: u32) -> anyhow::Result<Self> {
+        anyhow::bail!("DATABASE_URL is sqlite but rust_iss was built without the `sqlite` feature")
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub async fn sqlite(pool: sqlx::SqlitePool) -> anyhow::Result<Self> {
+        Ok(Self {
+            iss: Arc::new(SqliteIssRepo::new(pool.clone())),
+            osdr: Arc::new(SqliteOsdrRepo::new(pool.clone())),
+            cache: Arc::new(SqliteCacheRepo::new(pool.clone())),
+            retention: Arc::new(SqliteRetentionRepo::new(pool.clone())),
+            tle: Arc::new(SqliteTleRepo::new(pool.clone())),
+            events: Arc::new(SqliteEventRepo::new(pool.clone())),
+            locks: Arc::new(SqliteJobLocks::new(pool).await?),
+        })
+    }
+
+    pub fn postgres(pool: sqlx::PgPool) -> Self {
+        Self {
+            iss: Arc::new(PgIssRepo::new(pool.clone())),
+            osdr: Arc::new(PgOsdrRepo::new(pool.clone())),
+            cache: Arc::new(PgCacheRepo::new(pool.clone())),
+            retention: Arc::new(PgRetentionRepo::new(pool.clone())),
+            tle: Arc::new(PgTleRepo::new(pool.clone())),
+            events: Arc::new(PgEventRepo::new(pool.clone())),
+            locks: Arc::new(PgJobLocks::new(pool)),
+        }
+    }
+
+    pub fn memory(store: MemoryStore) -> Self {
+        Self {
+            iss: Arc::new(store.clone()),
+            osdr: Arc::new(store.clone()),
+            cache: Arc::new(store.clone()),
+            retention: Arc::new(store.clone()),
+            tle: Arc::new(store.clone()),
+            events: Arc::new(store.clone()),
+            locks: Arc::new(store),
+        }
+    }
+
+    pub async fn ensure_schema(&self) -> anyhow::Result<()> {
+        self.iss.ensure_schema().await?;
+        self.osdr.ensure_schema().await?;
+        self.cache.ensure_schema().await?;
+        self.retention.ensure_schema().await?;
+        self.tle.ensure_schema().await?;
+        self.events.ensure_schema().await?;
+        Ok(())
+    }
+}
+
+/// SHA-256 of the compact JSON encoding with object keys sorted, so equal
+/// documents hash equally regardless of upstream key order.
+pub(crate) fn payload_hash(payload: &Value) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(canonical_json(payload).to_string().as_bytes()))
+}
+
+/// `v` with the keys of every object in sorted order. `serde_json` sorts
+/// them already unless its `preserve_order` feature is enabled somewhere in
+/// the dependency graph; this does not rely on that.
+fn canonical_json(v: &Value) -> Value {
+    match v {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            let map = entries.into_iter().map(|(k, v)| (k.clone(), canonical_json(v))).collect();
+            Value::Object(map)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(canonical_json).collect()),
+        other => other.clone(),
+    }
+}
+
+pub(crate) fn month_start(t: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(t.year(), t.month(), 1, 0, 0, 0).unwrap()
+}
+
+pub(crate) fn add_months(t: DateTime<Utc>, n: u32) -> DateTime<Utc> {
+    let total = t.year() * 12 + t.month0() as i32 + n as i32;
+    Utc.with_ymd_and_hms(total.div_euclid(12), total.rem_euclid(12) as u32 + 1, 1, 0, 0, 0)
+        .unwrap()
+}
+
+pub(crate) fn partition_name(month: DateTime<Utc>) -> String {
+    format!("iss_fetch_log_y{:04}m{:02}", month.year(), month.month())
+}
+
+pub(crate) fn parse_partition_name(name: &str) -> Option<DateTime<Utc>> {
+    let rest = name.strip_prefix("iss_fetch_log_y")?;
+    let (y, m) = rest.split_once('m')?;
+    Utc.with_ymd_and_hms(y.parse().ok()?, m.parse().ok()?, 1, 0, 0, 0)
+        .single()
+}
+
+/// The candidate whose epoch is closest to `at`; ties go to the earlier set.
+pub(crate) fn closest_epoch(
+    sets: impl IntoIterator<Item = TleSet>,
+    at: DateTime<Utc>,
+) -> Option<TleSet> {
+    sets.into_iter().min_by_key(|s| ((s.epoch - at).abs(), s.epoch))
+}
+
+pub(crate) fn iss_point(at: DateTime<Utc>, payload: &Value) -> IssPoint {
+    IssPoint {
+        at,
+        latitude: pick_f64(payload, &["latitude", "lat"]),
+        longitude: pick_f64(payload, &["longitude", "lon", "lng"]),
+        altitude: pick_f64(payload, &["altitude", "alt"]),
+        velocity: pick_f64(payload, &["velocity", "vel"]),
+        visibility: payload.get("visibility").and_then(Value::as_str).map(str::to_string),
+    }
+}
+
+/// Diff between the stored row (if any) and an incoming upsert, or `None`
+/// when nothing changed.
+pub(crate) fn osdr_diff(old: Option<&OsdrItem>, new: &OsdrUpsert) -> Option<Value> {
+    let mut fields = serde_json::Map::new();
+    let mut field = |name: &str, old: Value, new: Value| {
+        if old != new {
+            fields.insert(name.to_string(), json!({"old": old, "new": new}));
+        }
+    };
+    field("title", json!(old.and_then(|o| o.title.as_ref())), json!(new.title));
+    field("status", json!(old.and_then(|o| o.status.as_ref())), json!(new.status));
+    field("updated_at", json!(old.and_then(|o| o.updated_at)), json!(new.updated_at));
+    // an upsert always revives a tombstoned row
+    field("removed_at", json!(old.and_then(|o| o.removed_at)), Value::Null);
+    let mut raw = Vec::new();
+    json_diff(old.map_or(&Value::Null, |o| &o.raw), &new.raw, &mut String::new(), &mut raw);
+    if fields.is_empty() && raw.is_empty() {
+        return None;
+    }
+    Some(json!({"fields": fields, "raw": raw}))
+}
+
+/// What an upsert does to `old`, assuming [`osdr_diff`] found a change.
+pub(crate) fn osdr_change(old: Option<&OsdrItem>) -> (OsdrChange, OsdrVersionKind) {
+    match old {
+        None => (OsdrChange::Created, OsdrVersionKind::Created),
+        Some(o) if o.removed_at.is_some() => (OsdrChange::Restored, OsdrVersionKind::Restored),
+        Some(_) => (OsdrChange::Updated, OsdrVersionKind::Updated),
+    }
+}
+
+/// A row of a batch that needs writing, with its version diff.
+pub(crate) struct OsdrWrite<'a> {
+    pub dataset_id: &'a str,
+    pub item: &'a OsdrUpsert,
+    pub kind: OsdrVersionKind,
+    pub diff: Value,
+}
+
+/// Plans a batch upsert against the stored rows `old` (keyed by
+/// `dataset_id`): the change of every item in input order, and the rows that
+/// actually need writing. Fails on a missing or repeated `dataset_id`.
+pub(crate) fn plan_osdr_batch<'a>(
+    old: &HashMap<String, OsdrItem>,
+    items: &'a [OsdrUpsert],
+) -> anyhow::Result<(Vec<OsdrChange>, Vec<OsdrWrite<'a>>)> {
+    let mut seen = HashSet::new();
+    let mut changes = Vec::with_capacity(items.len());
+    let mut writes = Vec::new();
+    for item in items {
+        let Some(ds) = item.dataset_id.as_deref() else {
+            anyhow::bail!("batch upsert: item without dataset_id");
+        };
+        if !seen.insert(ds) {
+            anyhow::bail!("batch upsert: duplicate dataset_id {ds}");
+        }
+        let prev = old.get(ds);
+        match osdr_diff(prev, item) {
+            Some(diff) => {
+                let (change, kind) = osdr_change(prev);
+                changes.push(change);
+                writes.push(OsdrWrite { dataset_id: ds, item, kind, diff });
+            }
+            None => changes.push(OsdrChange::Unchanged),
+        }
+    }
+    Ok((changes, writes))
+}
+
+/// Dataset ids of a batch, for loading the stored rows.
+pub(crate) fn batch_ids(items: &[OsdrUpsert]) -> Vec<String> {
+    items.iter().filter_map(|i| i.dataset_id.clone()).collect()
+}
+
+/// Appends JSON Pointer `add`/`remove`/`replace` operations turning `old`
+/// into `new`. Objects are compared key by key and arrays by index.
+pub(crate) fn json_diff(old: &Value, new: &Value, path: &mut String, out: &mut Vec<Value>) {
+    match (old, new) {
+        (a, b) if a == b => {}
+        (Value::Object(a), Value::Object(b)) => {
+            for (k, va) in a {
+                let len = push_token(path, k);
+                match b.get(k) {
+                    Some(vb) => json_diff(va, vb, path, out),
+                    None => out.push(json!({"op": "remove", "path": path, "old": va})),
+                }
+                path.truncate(len);
+            }
+            for (k, vb) in b.iter().filter(|(k, _)| !a.contains_key(*k)) {
+                let len = push_token(path, k);
+                out.push(json!({"op": "add", "path": path, "new": vb}));
+                path.truncate(len);
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            let common = a.len().min(b.len());
+            for (i, (va, vb)) in a.iter().zip(b).enumerate() {
+                let len = push_token(path, &i.to_string());
+                json_diff(va, vb, path, out);
+                path.truncate(len);
+            }
+            for (i, vb) in b.iter().enumerate().skip(common) {
+                let len = push_token(path, &i.to_string());
+                out.push(json!({"op": "add", "path": path, "new": vb}));
+                path.truncate(len);
+            }
+            // trailing removals last-first, so indices stay valid when applied in order
+            for (i, va) in a.iter().enumerate().skip(common).rev() {
+                let len = push_token(path, &i.to_string());
+                out.push(json!({"op": "remove", "path": path, "old": va}));
+                path.truncate(len);
+            }
+        }
+        (Value::Null, b) if path.is_empty() => out.push(json!({"op": "add", "path": "", "new": b})),
+        (a, b) => out.push(json!({"op": "replace", "path": path, "old": a, "new": b})),
+    }
+}
+
+fn push_token(path: &mut String, token: &str) -> usize {
+    let len = path.len();
+    path.push('/');
+    path.push_str(&token.replace('~', "~0").replace('/', "~1"));
+    len
+}
+
+/// Searchable metadata text of a raw item besides the title: every string
+/// under keys that look like a description, organism, assay, factor or
+/// keyword field, one per line.
+pub(crate) fn osdr_search_body(raw: &Value) -> String {
+    fn strings(v: &Value, out: &mut Vec<String>) {
+        match v {
+            Value::String(s) if !s.trim().is_empty() => out.push(s.trim().to_string()),
+            Value::Array(a) => a.iter().for_each(|x| strings(x, out)),
+            Value::Object(o) => o.values().for_each(|x| strings(x, out)),
+            _ => {}
+        }
+    }
+    fn walk(v: &Value, out: &mut Vec<String>) {
+        match v {
+            Value::Object(o) => {
+                for (k, x) in o {
+                    let k = k.to_lowercase();
+                    if SEARCH_KEYS.iter().any(|s| k.contains(s)) {
+                        strings(x, out);
+                    } else {
+                        walk(x, out);
+                    }
+                }
+            }
+            Value::Array(a) => a.iter().for_each(|x| walk(x, out)),
+            _ => {}
+        }
+    }
+    const SEARCH_KEYS: [&str; 5] = ["description", "organism", "assay", "factor", "keyword"];
+    let mut out = Vec::new();
+    walk(raw, &mut out);
+    let mut seen = std::collections::HashSet::new();
+    out.retain(|s| seen.insert(s.clone()));
+    out.join("\n")
+}
+
+/// Substring search for backends without full-text indexes: every word of
+/// `q.text` must occur (case-insensitively) in the title or search body.
+/// Returns the requested page and the total number of matches.
+pub(crate) fn fallback_search(
+    rows: impl IntoIterator<Item = (OsdrItem, String)>,
+    q: &OsdrSearchQuery,
+) -> (Vec<OsdrSearchHit>, i64) {
+    let terms: Vec<String> = q
+        .text
+        .split_whitespace()
+        .map(|t| t.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    if terms.is_empty() {
+        return (Vec::new(), 0);
+    }
+    let mut hits: Vec<OsdrSearchHit> = rows
+        .into_iter()
+        .filter_map(|(item, body)| {
+            let title = item.title.clone().unwrap_or_default();
+            let (title_lc, body_lc) = (title.to_lowercase(), body.to_lowercase());
+            let mut rank = 0.0f32;
+            for t in &terms {
+                let in_title = title_lc.contains(t.as_str());
+                let in_body = body_lc.matches(t.as_str()).count();
+                if !in_title && in_body == 0 {
+                    return None;
+                }
+                rank += if in_title { 1.0 } else { 0.0 } + 0.1 * in_body.min(5) as f32;
+            }
+            Some(OsdrSearchHit {
+                title_highlight: item.title.as_deref().map(|t| highlight(t, &terms)),
+                snippet: snippet(&body, &terms),
+                item,
+                rank,
+            })
+        })
+        .collect();
+    let total = hits.len() as i64;
+    hits.sort_by(|a, b| b.rank.total_cmp(&a.rank).then(b.item.id.cmp(&a.item.id)));
+    if let Some(c) = q.after {
+        hits.retain(|h| h.rank.total_cmp(&c.rank).then(h.item.id.cmp(&c.id)).is_lt());
+    }
+    hits.truncate(q.limit.max(0) as usize);
+    (hits, total)
+}
+
+/// Wraps case-insensitive occurrences of `terms` in `<b>…</b>`. Text whose
+/// lowercase form changes byte length is returned unmarked.
+fn highlight(text: &str, terms: &[String]) -> String {
+    let lc = text.to_lowercase();
+    if lc.len() != text.len() {
+        return text.to_string();
+    }
+    let mut marks = vec![false; text.len()];
+    for t in terms {
+        for (start, m) in lc.match_indices(t.as_str()) {
+            marks[start..start + m.len()].iter_mut().for_each(|x| *x = true);
+        }
+    }
+    let mut out = String::with_capacity(text.len() + 16);
+    let mut open = false;
+    for (i, ch) in text.char_indices() {
+        if marks[i] != open {
+            out.push_str(if marks[i] { "<b>" } else { "</b>" });
+            open = marks[i];
+        }
+        out.push(ch);
+    }
+    if open {
+        out.push_str("</b>");
+    }
+    out
+}
+
+/// About 120 characters of `body` around the first matched term.
+fn snippet(body: &str, terms: &[String]) -> Option<String> {
+    let lc = body.to_lowercase();
+    let first = terms.iter().filter_map(|t| lc.find(t.as_str())).min()?;
+    let first = if lc.len() == body.len() { first } else { 0 };
+    let chars: Vec<(usize, char)> = body.char_indices().collect();
+    let at = chars.iter().position(|(i, _)| *i >= first).unwrap_or(0);
+    let from = at.saturating_sub(60);
+    let to = (at + 60).min(chars.len());
+    let end = chars.get(to).map_or(body.len(), |(i, _)| *i);
+    let mut out = highlight(&body[chars[from].0..end], terms).replace('\n', " ");
+    if from > 0 {
+        out.insert(0, '…');
+    }
+    if to < chars.len() {
+        out.push('…');
+    }
+    Some(out)
+}
+
+/// Diff stored with a `removed` version.
+pub(crate) fn removed_diff(at: DateTime<Utc>) -> Value {
+    json!({"fields": {"removed_at": {"old": null, "new": at}}, "raw": []})
+}
+
+/// `LIKE` pattern matching values that start with `prefix` literally.
+pub(crate) fn like_prefix(prefix: &str) -> String {
+    let mut out = String::with_capacity(prefix.len() + 1);
+    for c in prefix.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('%');
+    out
+}
+
+fn pick_f64(v: &Value, keys: &[&str]) -> Option<f64> {
+    for k in keys {
+        if let Some(x) = v.get(*k) {
+            if let Some(f) = x.as_f64() {
+                return Some(f);
+            }
+            if let Some(s) = x.as_str() {
+                if let Ok(f) = s.parse::<f64>() {
+                    return Some(f);
+                }
+            }
+            if let Some(i) = x.as_i64() {
+                return Some(i as f64);
+            }
+        }
+    }
+    None
+}
+
@@ -0,0 +1,1634 @@
+use super::{
+    add_months, batch_ids, closest_epoch, iss_point, like_prefix, month_start, osdr_search_body,
+    parse_partition_name, partition_name, payload_hash, plan_osdr_batch, removed_diff, CacheRepo,
+    EventRepo, IssRepo, JobLocks, OsdrRepo, RetentionRepo, TleRepo,
+};
+use crate::domain::{
+    GeofenceEvent, GeofenceEventQuery, GeofenceTransition, IssPoint, IssStorage, OsdrAssay,
+    OsdrChange, OsdrDetail, OsdrEnrichCandidate, OsdrEnrichFailure, OsdrFile, OsdrFilter, OsdrItem,
+    OsdrListQuery, OsdrSearchHit, OsdrSearchQuery, OsdrSort, OsdrSyncState, OsdrUpsert, OsdrVersion,
+    OsdrVersionKind, RetentionPolicy, RetentionRun, SearchLang, SpaceCacheItem, TableStats, TleSet,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Postgres, QueryBuilder, Row};
+use std::collections::HashMap;
+
+#[derive(Clone)]
+pub struct PgIssRepo {
+    pool: PgPool,
+}
+
+impl PgIssRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    async fn has_timescale(&self) -> anyhow::Result<bool> {
+        Ok(sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM pg_extension WHERE extname = 'timescaledb')",
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    async fn make_hypertable(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "SELECT create_hypertable('iss_fetch_log', 'fetched_at',
+                chunk_time_interval => INTERVAL '1 month',
+                migrate_data => true,
+                if_not_exists => true)",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Rebuilds a plain `iss_fetch_log` (as created by `db/init.sql`) as a
+    /// table partitioned by month, keeping ids and the id sequence.
+    async fn migrate_to_partitions(&self) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("LOCK TABLE iss_fetch_log IN ACCESS EXCLUSIVE MODE")
+            .execute(&mut *tx)
+            .await?;
+        let (min, max): (Option<DateTime<Utc>>, Option<DateTime<Utc>>) =
+            sqlx::query_as("SELECT min(fetched_at), max(fetched_at) FROM iss_fetch_log")
+                .fetch_one(&mut *tx)
+                .await?;
+        for stmt in [
+            "ALTER TABLE iss_fetch_log RENAME TO iss_fetch_log_unpartitioned",
+            "ALTER TABLE iss_fetch_log_unpartitioned RENAME CONSTRAINT iss_fetch_log_pkey TO iss_fetch_log_unpartitioned_pkey",
+            "ALTER SEQUENCE iss_fetch_log_id_seq OWNED BY NONE",
+            "CREATE TABLE iss_fetch_log(
+                id BIGINT NOT NULL DEFAULT nextval('iss_fetch_log_id_seq'),
+                fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                source_url TEXT NOT NULL,
+                payload JSONB NOT NULL,
+                PRIMARY KEY (id, fetched_at)
+            ) PARTITION BY RANGE (fetched_at)",
+        ] {
+            sqlx::query(stmt).execute(&mut *tx).await?;
+        }
+        let now = Utc::now();
+        let mut month = month_start(min.unwrap_or(now));
+        let last = month_start(max.unwrap_or(now).max(now));
+        while month <= last {
+            sqlx::query(&create_partition_sql(month)).execute(&mut *tx).await?;
+            month = add_months(month, 1);
+        }
+        for stmt in [
+            "INSERT INTO iss_fetch_log(id, fetched_at, source_url, payload)
+             SELECT id, fetched_at, source_url, payload FROM iss_fetch_log_unpartitioned",
+            "DROP TABLE iss_fetch_log_unpartitioned",
+            "ALTER SEQUENCE iss_fetch_log_id_seq OWNED BY iss_fetch_log.id",
+        ] {
+            sqlx::query(stmt).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        tracing::info!("iss_fetch_log migrated to monthly partitions");
+        Ok(())
+    }
+
+    /// Creates the partition for `month` and moves into it the rows that
+    /// went to the default partition meanwhile; attaching fails otherwise.
+    async fn create_partition(&self, month: DateTime<Utc>) -> anyhow::Result<()> {
+        let name = partition_name(month);
+        let end = add_months(month, 1);
+        let mut tx = self.pool.begin().await?;
+        for stmt in [
+            format!("LOCK TABLE {DEFAULT_PARTITION} IN ACCESS EXCLUSIVE MODE"),
+            format!("CREATE TABLE {name} (LIKE iss_fetch_log INCLUDING DEFAULTS)"),
+        ] {
+            sqlx::query(&stmt).execute(&mut *tx).await?;
+        }
+        sqlx::query(&format!(
+            "WITH moved AS (DELETE FROM {DEFAULT_PARTITION}
+                            WHERE fetched_at >= $1 AND fetched_at < $2 RETURNING *)
+             INSERT INTO {name} SELECT * FROM moved"
+        ))
+        .bind(month)
+        .bind(end)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(&format!(
+            "ALTER TABLE iss_fetch_log ATTACH PARTITION {name} FOR VALUES FROM ('{}') TO ('{}')",
+            month.to_rfc3339(),
+            end.to_rfc3339()
+        ))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn partitions(&self) -> anyhow::Result<Vec<String>> {
+        Ok(sqlx::query_scalar(
+            "SELECT c.relname::text FROM pg_inherits i
+             JOIN pg_class c ON c.oid = i.inhrelid
+             WHERE i.inhparent = 'iss_fetch_log'::regclass
+             ORDER BY 1",
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
+
+#[async_trait]
+impl IssRepo for PgIssRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        let timescale = self.has_timescale().await?;
+        match self.storage().await? {
+            None if timescale => {
+                sqlx::query(
+                    "CREATE TABLE iss_fetch_log(
+                        id BIGSERIAL,
+                        fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                        source_url TEXT NOT NULL,
+                        payload JSONB NOT NULL,
+                        PRIMARY KEY (id, fetched_at)
+                    )",
+                )
+                .execute(&self.pool)
+                .await?;
+                self.make_hypertable().await?;
+            }
+            None => {
+                sqlx::query(
+                    "CREATE TABLE iss_fetch_log(
+                        id BIGSERIAL,
+                        fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                        source_url TEXT NOT NULL,
+                        payload JSONB NOT NULL,
+                        PRIMARY KEY (id, fetched_at)
+                    ) PARTITION BY RANGE (fetched_at)",
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+            Some(IssStorage::Plain) if timescale => {
+                sqlx::query(
+                    "ALTER TABLE iss_fetch_log
+                        DROP CONSTRAINT IF EXISTS iss_fetch_log_pkey,
+                        ADD PRIMARY KEY (id, fetched_at)",
+                )
+                .execute(&self.pool)
+                .await?;
+                self.make_hypertable().await?;
+            }
+            Some(IssStorage::Plain) => self.migrate_to_partitions().await?,
+            Some(_) => {}
+        }
+        self.ensure_partitions(Utc::now(), 1).await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS ix_iss_fetch_log_fetched_at ON iss_fetch_log(fetched_at DESC)",
+        )
+        .execute(&self.pool)
+        .await?;
+        // rows written before multi-satellite tracking are all ISS positions
+        sqlx::query(
+            "ALTER TABLE iss_fetch_log ADD COLUMN IF NOT EXISTS norad_id BIGINT NOT NULL DEFAULT 25544",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS ix_iss_fetch_log_norad
+                ON iss_fetch_log(norad_id, fetched_at DESC)",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn storage(&self) -> anyhow::Result<Option<IssStorage>> {
+        let relkind: Option<String> = sqlx::query_scalar(
+            "SELECT relkind::text FROM pg_class WHERE oid = to_regclass('iss_fetch_log')",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(relkind) = relkind else {
+            return Ok(None);
+        };
+        if relkind == "p" {
+            return Ok(Some(IssStorage::Partitioned));
+        }
+        let hypertable = self.has_timescale().await?
+            && sqlx::query_scalar::<_, bool>(
+                "SELECT EXISTS(SELECT 1 FROM timescaledb_information.hypertables
+                               WHERE hypertable_name = 'iss_fetch_log')",
+            )
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(Some(if hypertable {
+            IssStorage::Hypertable
+        } else {
+            IssStorage::Plain
+        }))
+    }
+
+    async fn ensure_partitions(
+        &self,
+        now: DateTime<Utc>,
+        ahead: u32,
+    ) -> anyhow::Result<Vec<String>> {
+        if self.storage().await? != Some(IssStorage::Partitioned) {
+            return Ok(Vec::new());
+        }
+        // catches rows for months without a partition instead of failing them
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {DEFAULT_PARTITION} PARTITION OF iss_fetch_log DEFAULT"
+        ))
+        .execute(&self.pool)
+        .await?;
+        let existing = self.partitions().await?;
+        let mut created = Vec::new();
+        let mut month = month_start(now);
+        for _ in 0..=ahead {
+            let name = partition_name(month);
+            if !existing.contains(&name) {
+                self.create_partition(month).await?;
+                created.push(name);
+            }
+            month = add_months(month, 1);
+        }
+        Ok(created)
+    }
+
+    async fn drop_partitions_before(
+        &self,
+        before: DateTime<Utc>,
+    ) -> anyhow::Result<(Vec<String>, i64)> {
+        let storage = self.storage().await?;
+        if !matches!(storage, Some(IssStorage::Partitioned | IssStorage::Hypertable)) {
+            return Ok((Vec::new(), 0));
+        }
+        let mut tx = self.pool.begin().await?;
+        // the latest position of every satellite stays, as with row expiry:
+        // the ones in dropped partitions or chunks are put back afterwards
+        sqlx::query(
+            "CREATE TEMP TABLE kept_latest ON COMMIT DROP AS
+             SELECT * FROM iss_fetch_log
+             WHERE fetched_at < $1 AND id IN (SELECT max(id) FROM iss_fetch_log GROUP BY norad_id)",
+        )
+        .bind(before)
+        .execute(&mut *tx)
+        .await?;
+        let mut dropped = Vec::new();
+        let mut rows;
+        if storage == Some(IssStorage::Partitioned) {
+            rows = sqlx::query(&format!(
+                "DELETE FROM {DEFAULT_PARTITION}
+                 WHERE fetched_at < $1 AND id NOT IN (SELECT id FROM kept_latest)"
+            ))
+            .bind(before)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() as i64;
+            for name in self.partitions().await? {
+                let Some(start) = parse_partition_name(&name) else {
+                    continue;
+                };
+                if add_months(start, 1) > before {
+                    continue;
+                }
+                let n: i64 = sqlx::query_scalar(&format!(
+                    "SELECT count(*) FROM {name} WHERE id NOT IN (SELECT id FROM kept_latest)"
+                ))
+                .fetch_one(&mut *tx)
+                .await?;
+                sqlx::query(&format!("DROP TABLE {name}")).execute(&mut *tx).await?;
+                rows += n;
+                dropped.push(name);
+            }
+        } else {
+            rows = sqlx::query_scalar(
+                "SELECT count(*) FROM iss_fetch_log
+                 WHERE id NOT IN (SELECT id FROM kept_latest)
+                   AND fetched_at < (SELECT coalesce(max(range_end), '-infinity')
+                                     FROM timescaledb_information.chunks
+                                     WHERE hypertable_name = 'iss_fetch_log' AND range_end <= $1)",
+            )
+            .bind(before)
+            .fetch_one(&mut *tx)
+            .await?;
+            dropped =
+                sqlx::query_scalar("SELECT drop_chunks('iss_fetch_log', older_than => $1)::text")
+                    .bind(before)
+                    .fetch_all(&mut *tx)
+                    .await?;
+        }
+        // back into the default partition, or a new chunk
+        sqlx::query(
+            "INSERT INTO iss_fetch_log SELECT * FROM kept_latest k
+             WHERE NOT EXISTS (SELECT 1 FROM iss_fetch_log l WHERE l.id = k.id)",
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok((dropped, rows))
+    }
+
+    async fn insert_log(
+        &self,
+        norad_id: i64,
+        source_url: &str,
+        payload: &Value,
+    ) -> anyhow::Result<DateTime<Utc>> {
+        Ok(sqlx::query_scalar(
+            "INSERT INTO iss_fetch_log (norad_id, source_url, payload) VALUES ($1,$2,$3)
+             RETURNING fetched_at",
+        )
+        .bind(norad_id)
+        .bind(source_url)
+        .bind(payload)
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    async fn last(
+        &self,
+        norad_id: i64,
+    ) -> anyhow::Result<Option<(i64, DateTime<Utc>, String, Value)>> {
+        let row_opt = sqlx::query(
+            "SELECT id, fetched_at, source_url, payload
+             FROM iss_fetch_log
+             WHERE norad_id = $1
+             ORDER BY fetched_at DESC, id DESC LIMIT 1",
+        )
+        .bind(norad_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row_opt.map(|row| {
+            (
+                row.get("id"),
+                row.get("fetched_at"),
+                row.get("source_url"),
+                row.get("payload"),
+            )
+        }))
+    }
+
+    async fn trend(&self, norad_id: i64, limit: i64) -> anyhow::Result<Vec<IssPoint>> {
+        let rows = sqlx::query(
+            "SELECT fetched_at, payload
+             FROM iss_fetch_log
+             WHERE norad_id = $1
+             ORDER BY fetched_at DESC, id DESC
+             LIMIT $2",
+        )
+        .bind(norad_id)
+        .bind(limit.max(2))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .rev()
+            .map(|r| iss_point(r.get("fetched_at"), &r.get::<Value, _>("payload")))
+            .collect())
+    }
+
+    async fn range(
+        &self,
+        norad_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<IssPoint>> {
+        let rows = sqlx::query(
+            "SELECT fetched_at, payload
+             FROM iss_fetch_log
+             WHERE norad_id = $1 AND fetched_at >= $2 AND fetched_at <= $3
+             ORDER BY fetched_at, id",
+        )
+        .bind(norad_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| iss_point(r.get("fetched_at"), &r.get::<Value, _>("payload")))
+            .collect())
+    }
+}
+
+#[derive(Clone)]
+pub struct PgOsdrRepo {
+    pool: PgPool,
+}
+
+impl PgOsdrRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl OsdrRepo for PgOsdrRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS osdr_items(
+                id BIGSERIAL PRIMARY KEY,
+                dataset_id TEXT,
+                title TEXT,
+                status TEXT,
+                updated_at TIMESTAMPTZ,
+                inserted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                raw JSONB NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS ux_osdr_dataset_id
+             ON osdr_items(dataset_id) WHERE dataset_id IS NOT NULL",
+        )
+        .execute(&self.pool)
+        .await?;
+        for stmt in [
+            "ALTER TABLE osdr_items ADD COLUMN IF NOT EXISTS removed_at TIMESTAMPTZ",
+            "CREATE INDEX IF NOT EXISTS ix_osdr_inserted ON osdr_items(inserted_at, id)",
+            "CREATE INDEX IF NOT EXISTS ix_osdr_updated
+             ON osdr_items((coalesce(updated_at, '-infinity'::timestamptz)), id)",
+            "CREATE INDEX IF NOT EXISTS ix_osdr_title ON osdr_items((coalesce(title, '')), id)",
+            "CREATE INDEX IF NOT EXISTS ix_osdr_dataset_prefix
+             ON osdr_items(dataset_id text_pattern_ops)",
+            "CREATE TABLE IF NOT EXISTS osdr_details(
+                dataset_id TEXT PRIMARY KEY,
+                source_updated_at TIMESTAMPTZ,
+                enriched_at TIMESTAMPTZ NOT NULL,
+                mission_name TEXT,
+                mission JSONB,
+                raw JSONB NOT NULL
+            )",
+            "CREATE TABLE IF NOT EXISTS osdr_enrich_failures(
+                dataset_id TEXT PRIMARY KEY,
+                attempts INT NOT NULL,
+                failed_at TIMESTAMPTZ NOT NULL,
+                retry_at TIMESTAMPTZ NOT NULL,
+                error TEXT NOT NULL
+            )",
+            "CREATE TABLE IF NOT EXISTS osdr_files(
+                dataset_id TEXT NOT NULL REFERENCES osdr_details(dataset_id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                url TEXT,
+                size_bytes BIGINT,
+                category TEXT,
+                PRIMARY KEY (dataset_id, name)
+            )",
+            "CREATE TABLE IF NOT EXISTS osdr_assays(
+                dataset_id TEXT NOT NULL REFERENCES osdr_details(dataset_id) ON DELETE CASCADE,
+                position INT NOT NULL,
+                measurement TEXT,
+                technology TEXT,
+                platform TEXT,
+                PRIMARY KEY (dataset_id, position)
+            )",
+            "CREATE TABLE IF NOT EXISTS osdr_organisms(
+                dataset_id TEXT NOT NULL REFERENCES osdr_details(dataset_id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                PRIMARY KEY (dataset_id, name)
+            )",
+            "CREATE TABLE IF NOT EXISTS osdr_factors(
+                dataset_id TEXT NOT NULL REFERENCES osdr_details(dataset_id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                PRIMARY KEY (dataset_id, name)
+            )",
+            "CREATE INDEX IF NOT EXISTS ix_osdr_organisms_name ON osdr_organisms(name)",
+            "CREATE TABLE IF NOT EXISTS osdr_versions(
+                id BIGSERIAL PRIMARY KEY,
+                dataset_id TEXT NOT NULL,
+                version INT NOT NULL,
+                changed_at TIMESTAMPTZ NOT NULL,
+                kind TEXT NOT NULL,
+                title TEXT,
+                diff JSONB NOT NULL,
+                UNIQUE (dataset_id, version)
+            )",
+            "CREATE INDEX IF NOT EXISTS ix_osdr_versions_changed_at ON osdr_versions(changed_at DESC)",
+            "ALTER TABLE osdr_items ADD COLUMN IF NOT EXISTS search_body TEXT",
+            "ALTER TABLE osdr_items ADD COLUMN IF NOT EXISTS search_en tsvector GENERATED ALWAYS AS (
+                setweight(to_tsvector('english', coalesce(title, '')), 'A')
+                || setweight(to_tsvector('english', coalesce(search_body, '')), 'B')
+            ) STORED",
+            "ALTER TABLE osdr_items ADD COLUMN IF NOT EXISTS search_ru tsvector GENERATED ALWAYS AS (
+                setweight(to_tsvector('russian', coalesce(title, '')), 'A')
+                || setweight(to_tsvector('russian', coalesce(search_body, '')), 'B')
+            ) STORED",
+            "CREATE INDEX IF NOT EXISTS ix_osdr_search_en ON osdr_items USING GIN (search_en)",
+            "CREATE INDEX IF NOT EXISTS ix_osdr_search_ru ON osdr_items USING GIN (search_ru)",
+            "CREATE TABLE IF NOT EXISTS sync_state(
+                source TEXT PRIMARY KEY,
+                watermark TIMESTAMPTZ,
+                last_full_at TIMESTAMPTZ,
+                last_sync_at TIMESTAMPTZ
+            )",
+        ] {
+            sqlx::query(stmt).execute(&self.pool).await?;
+        }
+        // Rows written before search existed, in one statement
+        let (ids, bodies): (Vec<i64>, Vec<String>) =
+            sqlx::query("SELECT id, raw FROM osdr_items WHERE search_body IS NULL")
+                .fetch_all(&self.pool)
+                .await?
+                .iter()
+                .map(|r| (r.get::<i64, _>("id"), osdr_search_body(&r.get::<Value, _>("raw"))))
+                .unzip();
+        if !ids.is_empty() {
+            sqlx::query(
+                "UPDATE osdr_items o SET search_body = b.body
+                 FROM UNNEST($1::bigint[], $2::text[]) AS b(id, body)
+                 WHERE o.id = b.id",
+            )
+            .bind(&ids)
+            .bind(&bodies)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn upsert(&self, item: OsdrUpsert) -> anyhow::Result<OsdrChange> {
+        if item.dataset_id.is_none() {
+            sqlx::query(
+                "INSERT INTO osdr_items(dataset_id, title, status, updated_at, raw, search_body)
+                 VALUES($1,$2,$3,$4,$5,$6)",
+            )
+            .bind::<Option<String>>(None)
+            .bind(item.title)
+            .bind(item.status)
+            .bind(item.updated_at)
+            .bind(&item.raw)
+            .bind(osdr_search_body(&item.raw))
+            .execute(&self.pool)
+            .await?;
+            return Ok(OsdrChange::Created);
+        }
+        let changes = self.upsert_batch(std::slice::from_ref(&item)).await?;
+        Ok(changes[0])
+    }
+
+    async fn upsert_batch(&self, items: &[OsdrUpsert]) -> anyhow::Result<Vec<OsdrChange>> {
+        let mut tx = self.pool.begin().await?;
+        // `FOR UPDATE` below locks only the rows that exist: two batches
+        // creating the same dataset would both record version 1. The ids
+        // are locked themselves, in order, until the transaction ends.
+        sqlx::query(
+            "SELECT pg_advisory_xact_lock($1, hashtext(id))
+             FROM (SELECT DISTINCT id FROM UNNEST($2::text[]) AS id ORDER BY id) ids",
+        )
+        .bind(OSDR_LOCK_CLASS)
+        .bind(batch_ids(items))
+        .execute(&mut *tx)
+        .await?;
+        let old: HashMap<String, OsdrItem> = sqlx::query(
+            "SELECT id, dataset_id, title, status, updated_at, inserted_at, removed_at, raw
+             FROM osdr_items WHERE dataset_id = ANY($1) FOR UPDATE",
+        )
+        .bind(batch_ids(items))
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(osdr_item)
+        .filter_map(|o| Some((o.dataset_id.clone()?, o)))
+        .collect();
+        let (changes, writes) = plan_osdr_batch(&old, items)?;
+        if writes.is_empty() {
+            return Ok(changes);
+        }
+        let ids: Vec<&str> = writes.iter().map(|w| w.dataset_id).collect();
+        let titles: Vec<Option<&str>> = writes.iter().map(|w| w.item.title.as_deref()).collect();
+        let statuses: Vec<Option<&str>> = writes.iter().map(|w| w.item.status.as_deref()).collect();
+        let updated: Vec<Option<DateTime<Utc>>> = writes.iter().map(|w| w.item.updated_at).collect();
+        let raws: Vec<Value> = writes.iter().map(|w| w.item.raw.clone()).collect();
+        let bodies: Vec<String> = writes.iter().map(|w| osdr_search_body(&w.item.raw)).collect();
+        sqlx::query(
+            "INSERT INTO osdr_items(dataset_id, title, status, updated_at, raw, search_body)
+             SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::timestamptz[], $5::jsonb[], $6::text[])
+             ON CONFLICT (dataset_id) WHERE dataset_id IS NOT NULL DO UPDATE
+             SET title=EXCLUDED.title,
+                 status=EXCLUDED.status,
+                 updated_at=EXCLUDED.updated_at,
+                 raw=EXCLUDED.raw,
+                 search_body=EXCLUDED.search_body,
+                 removed_at=NULL",
+        )
+        .bind(&ids)
+        .bind(&titles)
+        .bind(&statuses)
+        .bind(&updated)
+        .bind(&raws)
+        .bind(&bodies)
+        .execute(&mut *tx)
+        .await?;
+        let kinds: Vec<&str> = writes.iter().map(|w| w.kind.as_str()).collect();
+        let diffs: Vec<Value> = writes.into_iter().map(|w| w.diff).collect();
+        sqlx::query(
+            "INSERT INTO osdr_versions(dataset_id, version, changed_at, kind, title, diff)
+             SELECT w.dataset_id,
+                    coalesce((SELECT max(version) FROM osdr_versions v WHERE v.dataset_id = w.dataset_id), 0) + 1,
+                    $5, w.kind, w.title, w.diff
+             FROM UNNEST($1::text[], $2::text[], $3::text[], $4::jsonb[]) AS w(dataset_id, kind, title, diff)",
+        )
+        .bind(&ids)
+        .bind(&kinds)
+        .bind(&titles)
+        .bind(&diffs)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(changes)
+    }
+
+    async fn list(&self, q: &OsdrListQuery) -> anyhow::Result<Vec<OsdrItem>> {
+        let expr = osdr_sort_expr(q.sort);
+        let mut qb = QueryBuilder::<Postgres>::new(
+            "SELECT id, dataset_id, title, status, updated_at, inserted_at, removed_at, raw
+             FROM osdr_items WHERE TRUE",
+        );
+        push_osdr_filter(&mut qb, &q.filter);
+        if let Some(c) = &q.after {
+            let op = if q.desc { "<" } else { ">" };
+            qb.push(format!(" AND ({expr}, id) {op} ("));
+            match q.sort {
+                OsdrSort::InsertedAt => {
+                    let at = c
+                        .key_time()?
+                        .ok_or_else(|| anyhow::anyhow!("cursor: missing inserted_at"))?;
+                    qb.push_bind(at);
+                }
+                OsdrSort::UpdatedAt => {
+                    qb.push("coalesce(")
+                        .push_bind(c.key_time()?)
+                        .push("::timestamptz, '-infinity'::timestamptz)");
+                }
+                OsdrSort::Title => {
+                    qb.push_bind(c.key.clone().unwrap_or_default());
+                }
+            }
+            qb.push(", ").push_bind(c.id).push(")");
+        }
+        let dir = if q.desc { "DESC" } else { "ASC" };
+        qb.push(format!(" ORDER BY {expr} {dir}, id {dir} LIMIT "))
+            .push_bind(q.limit);
+        let rows = qb.build().fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(osdr_item).collect())
+    }
+
+    async fn count(&self, filter: &OsdrFilter) -> anyhow::Result<i64> {
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT count(*) FROM osdr_items WHERE TRUE");
+        push_osdr_filter(&mut qb, filter);
+        Ok(qb.build_query_scalar().fetch_one(&self.pool).await?)
+    }
+
+    async fn get(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrItem>> {
+        let row = sqlx::query(
+            "SELECT id, dataset_id, title, status, updated_at, inserted_at, removed_at, raw
+             FROM osdr_items WHERE dataset_id = $1",
+        )
+        .bind(dataset_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.as_ref().map(osdr_item))
+    }
+
+    async fn enrich_candidates(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<OsdrEnrichCandidate>> {
+        let rows = sqlx::query(
+            "SELECT i.dataset_id, i.updated_at, coalesce(f.attempts, 0) AS attempts
+             FROM osdr_items i
+             LEFT JOIN osdr_details d ON d.dataset_id = i.dataset_id
+             LEFT JOIN osdr_enrich_failures f ON f.dataset_id = i.dataset_id
+             WHERE i.dataset_id IS NOT NULL AND i.removed_at IS NULL
+               AND (d.dataset_id IS NULL OR d.source_updated_at IS DISTINCT FROM i.updated_at)
+               AND (f.retry_at IS NULL OR f.retry_at <= $2)
+             ORDER BY i.updated_at DESC NULLS LAST, i.id DESC
+             LIMIT $1",
+        )
+        .bind(limit)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| OsdrEnrichCandidate {
+                dataset_id: r.get("dataset_id"),
+                updated_at: r.get("updated_at"),
+                attempts: r.get("attempts"),
+            })
+            .collect())
+    }
+
+    async fn record_enrich_failure(&self, f: &OsdrEnrichFailure) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO osdr_enrich_failures(dataset_id, attempts, failed_at, retry_at, error)
+             VALUES($1,$2,$3,$4,$5)
+             ON CONFLICT (dataset_id) DO UPDATE
+             SET attempts=EXCLUDED.attempts,
+                 failed_at=EXCLUDED.failed_at,
+                 retry_at=EXCLUDED.retry_at,
+                 error=EXCLUDED.error",
+        )
+        .bind(&f.dataset_id)
+        .bind(f.attempts)
+        .bind(f.failed_at)
+        .bind(f.retry_at)
+        .bind(&f.error)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn save_detail(&self, d: &OsdrDetail) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO osdr_details(dataset_id, source_updated_at, enriched_at, mission_name, mission, raw)
+             VALUES($1,$2,$3,$4,$5,$6)
+             ON CONFLICT (dataset_id) DO UPDATE
+             SET source_updated_at=EXCLUDED.source_updated_at,
+                 enriched_at=EXCLUDED.enriched_at,
+                 mission_name=EXCLUDED.mission_name,
+                 mission=EXCLUDED.mission,
+                 raw=EXCLUDED.raw",
+        )
+        .bind(&d.dataset_id)
+        .bind(d.source_updated_at)
+        .bind(d.enriched_at)
+        .bind(&d.mission_name)
+        .bind(&d.mission)
+        .bind(&d.raw)
+        .execute(&mut *tx)
+        .await?;
+        for table in [
+            "osdr_files",
+            "osdr_assays",
+            "osdr_organisms",
+            "osdr_factors",
+            "osdr_enrich_failures",
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE dataset_id = $1"))
+                .bind(&d.dataset_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query(
+            "INSERT INTO osdr_files(dataset_id, name, url, size_bytes, category)
+             SELECT $1, * FROM UNNEST($2::text[], $3::text[], $4::bigint[], $5::text[])",
+        )
+        .bind(&d.dataset_id)
+        .bind(d.files.iter().map(|f| f.name.clone()).collect::<Vec<_>>())
+        .bind(d.files.iter().map(|f| f.url.clone()).collect::<Vec<_>>())
+        .bind(d.files.iter().map(|f| f.size_bytes).collect::<Vec<_>>())
+        .bind(d.files.iter().map(|f| f.category.clone()).collect::<Vec<_>>())
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO osdr_assays(dataset_id, position, measurement, technology, platform)
+             SELECT $1, (n - 1)::int, m, t, p
+             FROM UNNEST($2::text[], $3::text[], $4::text[]) WITH ORDINALITY AS a(m, t, p, n)",
+        )
+        .bind(&d.dataset_id)
+        .bind(d.assays.iter().map(|a| a.measurement.clone()).collect::<Vec<_>>())
+        .bind(d.assays.iter().map(|a| a.technology.clone()).collect::<Vec<_>>())
+        .bind(d.assays.iter().map(|a| a.platform.clone()).collect::<Vec<_>>())
+        .execute(&mut *tx)
+        .await?;
+        for (table, names) in [("osdr_organisms", &d.organisms), ("osdr_factors", &d.factors)] {
+            sqlx::query(&format!(
+                "INSERT INTO {table}(dataset_id, name) SELECT $1, * FROM UNNEST($2::text[])"
+            ))
+            .bind(&d.dataset_id)
+            .bind(names)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn enrich_failure(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrEnrichFailure>> {
+        let row = sqlx::query(
+            "SELECT attempts, failed_at, retry_at, error FROM osdr_enrich_failures
+             WHERE dataset_id = $1",
+        )
+        .bind(dataset_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| OsdrEnrichFailure {
+            dataset_id: dataset_id.to_string(),
+            attempts: r.get("attempts"),
+            failed_at: r.get("failed_at"),
+            retry_at: r.get("retry_at"),
+            error: r.get("error"),
+        }))
+    }
+
+    async fn detail(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrDetail>> {
+        let Some(row) = sqlx::query(
+            "SELECT source_updated_at, enriched_at, mission_name, mission, raw
+             FROM osdr_details WHERE dataset_id = $1",
+        )
+        .bind(dataset_id)
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let files = sqlx::query(
+            "SELECT name, url, size_bytes, category FROM osdr_files
+             WHERE dataset_id = $1 ORDER BY name",
+        )
+        .bind(dataset_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|r| OsdrFile {
+            name: r.get("name"),
+            url: r.get("url"),
+            size_bytes: r.get("size_bytes"),
+            category: r.get("category"),
+        })
+        .collect();
+        let assays = sqlx::query(
+            "SELECT measurement, technology, platform FROM osdr_assays
+             WHERE dataset_id = $1 ORDER BY position",
+        )
+        .bind(dataset_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|r| OsdrAssay {
+            measurement: r.get("measurement"),
+            technology: r.get("technology"),
+            platform: r.get("platform"),
+        })
+        .collect();
+        let organisms = sqlx::query_scalar(
+            "SELECT name FROM osdr_organisms WHERE dataset_id = $1 ORDER BY name",
+        )
+        .bind(dataset_id)
+        .fetch_all(&self.pool)
+        .await?;
+        let factors = sqlx::query_scalar(
+            "SELECT name FROM osdr_factors WHERE dataset_id = $1 ORDER BY name",
+        )
+        .bind(dataset_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(Some(OsdrDetail {
+            dataset_id: dataset_id.to_string(),
+            source_updated_at: row.get("source_updated_at"),
+            enriched_at: row.get("enriched_at"),
+            mission_name: row.get("mission_name"),
+            mission: row.get("mission"),
+            organisms,
+            factors,
+            assays,
+            files,
+            raw: row.get("raw"),
+        }))
+    }
+
+    async fn search(&self, q: &OsdrSearchQuery) -> anyhow::Result<Vec<OsdrSearchHit>> {
+        let (config, column) = search_config(q.lang);
+        let mut qb = QueryBuilder::<Postgres>::new(format!(
+            "SELECT s.*,
+                    ts_headline('{config}', coalesce(s.title, ''), s.q, 'HighlightAll=true') AS title_highlight,
+                    CASE WHEN coalesce(s.search_body, '') <> '' THEN
+                        replace(ts_headline('{config}', s.search_body, s.q,
+                                            'MaxFragments=2, MaxWords=20, MinWords=8, FragmentDelimiter=\" … \"'),
+                                E'\\n', ' ')
+                    END AS snippet
+             FROM (
+                 SELECT id, dataset_id, title, status, updated_at, inserted_at, removed_at, raw, search_body, q,
+                        ts_rank_cd({column}, q) AS rank
+                 FROM osdr_items, websearch_to_tsquery('{config}', "
+        ));
+        qb.push_bind(q.text.clone())
+            .push(format!(") q WHERE {column} @@ q"));
+        push_osdr_filter(&mut qb, &q.filter);
+        qb.push(") s");
+        if let Some(c) = q.after {
+            qb.push(" WHERE (s.rank, s.id) < (")
+                .push_bind(c.rank)
+                .push("::real, ")
+                .push_bind(c.id)
+                .push(")");
+        }
+        qb.push(" ORDER BY s.rank DESC, s.id DESC LIMIT ")
+            .push_bind(q.limit);
+        let rows = qb.build().fetch_all(&self.pool).await?;
+        Ok(rows
+            .iter()
+            .map(|r| OsdrSearchHit {
+                item: osdr_item(r),
+                rank: r.get("rank"),
+                title_highlight: r.get("title_highlight"),
+                snippet: r.get("snippet"),
+            })
+            .collect())
+    }
+
+    async fn search_count(&self, q: &OsdrSearchQuery) -> anyhow::Result<i64> {
+        let (config, column) = search_config(q.lang);
+        let mut qb = QueryBuilder::<Postgres>::new(format!(
+            "SELECT count(*) FROM osdr_items, websearch_to_tsquery('{config}', "
+        ));
+        qb.push_bind(q.text.clone())
+            .push(format!(") q WHERE {column} @@ q"));
+        push_osdr_filter(&mut qb, &q.filter);
+        Ok(qb.build_query_scalar().fetch_one(&self.pool).await?)
+    }
+
+    async fn mark_removed(
+        &self,
+        seen: &[String],
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query(
+            "UPDATE osdr_items SET removed_at = $2
+             WHERE dataset_id IS NOT NULL AND removed_at IS NULL AND NOT (dataset_id = ANY($1))
+             RETURNING dataset_id, title",
+        )
+        .bind(seen)
+        .bind(at)
+        .fetch_all(&mut *tx)
+        .await?;
+        let ids: Vec<String> = rows.iter().map(|r| r.get("dataset_id")).collect();
+        let titles: Vec<Option<String>> = rows.iter().map(|r| r.get("title")).collect();
+        sqlx::query(
+            "INSERT INTO osdr_versions(dataset_id, version, changed_at, kind, title, diff)
+             SELECT r.dataset_id,
+                    coalesce((SELECT max(version) FROM osdr_versions v WHERE v.dataset_id = r.dataset_id), 0) + 1,
+                    $3, 'removed', r.title, $4
+             FROM UNNEST($1::text[], $2::text[]) AS r(dataset_id, title)",
+        )
+        .bind(&ids)
+        .bind(&titles)
+        .bind(at)
+        .bind(removed_diff(at))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(ids)
+    }
+
+    async fn history(&self, dataset_id: &str, limit: i64) -> anyhow::Result<Vec<OsdrVersion>> {
+        let rows = sqlx::query(
+            "SELECT dataset_id, version, changed_at, kind, title, diff
+             FROM osdr_versions WHERE dataset_id = $1
+             ORDER BY version DESC LIMIT $2",
+        )
+        .bind(dataset_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().filter_map(osdr_version).collect())
+    }
+
+    async fn recent_changes(
+        &self,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<OsdrVersion>> {
+        let rows = sqlx::query(
+            "SELECT * FROM (
+                 SELECT DISTINCT ON (dataset_id) dataset_id, version, changed_at, kind, title, diff
+                 FROM osdr_versions
+                 WHERE $1::timestamptz IS NULL OR changed_at >= $1
+                 ORDER BY dataset_id, version DESC
+             ) latest
+             ORDER BY changed_at DESC, dataset_id LIMIT $2",
+        )
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().filter_map(osdr_version).collect())
+    }
+
+    async fn sync_state(&self) -> anyhow::Result<OsdrSyncState> {
+        let row = sqlx::query(
+            "SELECT watermark, last_full_at, last_sync_at FROM sync_state WHERE source = 'osdr'",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row
+            .map(|r| OsdrSyncState {
+                watermark: r.get("watermark"),
+                last_full_at: r.get("last_full_at"),
+                last_sync_at: r.get("last_sync_at"),
+            })
+            .unwrap_or_default())
+    }
+
+    async fn save_sync_state(&self, state: &OsdrSyncState) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO sync_state(source, watermark, last_full_at, last_sync_at)
+             VALUES('osdr', $1, $2, $3)
+             ON CONFLICT (source) DO UPDATE
+             SET watermark=GREATEST(sync_state.watermark, EXCLUDED.watermark),
+                 last_full_at=GREATEST(sync_state.last_full_at, EXCLUDED.last_full_at),
+                 last_sync_at=GREATEST(sync_state.last_sync_at, EXCLUDED.last_sync_at)",
+        )
+        .bind(state.watermark)
+        .bind(state.last_full_at)
+        .bind(state.last_sync_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+fn osdr_version(r: &PgRow) -> Option<OsdrVersion> {
+    Some(OsdrVersion {
+        dataset_id: r.get("dataset_id"),
+        version: r.get("version"),
+        changed_at: r.get("changed_at"),
+        kind: OsdrVersionKind::parse(r.get("kind"))?,
+        title: r.get("title"),
+        diff: r.get("diff"),
+    })
+}
+
+fn osdr_item(r: &PgRow) -> OsdrItem {
+    OsdrItem {
+        id: r.get("id"),
+        dataset_id: r.get("dataset_id"),
+        title: r.get("title"),
+        status: r.get("status"),
+        updated_at: r.get("updated_at"),
+        inserted_at: r.get("inserted_at"),
+        removed_at: r.get("removed_at"),
+        raw: r.get("raw"),
+    }
+}
+
+fn search_config(lang: SearchLang) -> (&'static str, &'static str) {
+    match lang {
+        SearchLang::English => ("english", "search_en"),
+        SearchLang::Russian => ("russian", "search_ru"),
+    }
+}
+
+fn osdr_sort_expr(sort: OsdrSort) -> &'static str {
+    match sort {
+        OsdrSort::InsertedAt => "inserted_at",
+        OsdrSort::UpdatedAt => "coalesce(updated_at, '-infinity'::timestamptz)",
+        OsdrSort::Title => "coalesce(title, '')",
+    }
+}
+
+fn push_osdr_filter(qb: &mut QueryBuilder<'_, Postgres>, f: &OsdrFilter) {
+    if let Some(status) = &f.status {
+        qb.push(" AND status = ").push_bind(status.clone());
+    }
+    if let Some(from) = f.updated_from {
+        qb.push(" AND updated_at >= ").push_bind(from);
+    }
+    if let Some(to) = f.updated_to {
+        qb.push(" AND updated_at < ").push_bind(to);
+    }
+    if !f.include_removed {
+        qb.push(" AND removed_at IS NULL");
+    }
+    if let Some(prefix) = &f.dataset_id_prefix {
+        qb.push(" AND dataset_id LIKE ")
+            .push_bind(like_prefix(prefix))
+            .push(" ESCAPE '\\'");
+    }
+}
+
+#[derive(Clone)]
+pub struct PgCacheRepo {
+    pool: PgPool,
+}
+
+impl PgCacheRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl CacheRepo for PgCacheRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS space_cache(
+                id BIGSERIAL PRIMARY KEY,
+                source TEXT NOT NULL,
+                fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                payload JSONB NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS ix_space_cache_source ON space_cache(source, fetched_at DESC)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "ALTER TABLE space_cache
+                ADD COLUMN IF NOT EXISTS payload_hash TEXT,
+                ADD COLUMN IF NOT EXISTS last_seen_at TIMESTAMPTZ,
+                ADD COLUMN IF NOT EXISTS check_count INT NOT NULL DEFAULT 1",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("UPDATE space_cache SET last_seen_at = fetched_at WHERE last_seen_at IS NULL")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn write(&self, source: &str, payload: Value) -> anyhow::Result<bool> {
+        let hash = payload_hash(&payload);
+        let inserted = sqlx::query(
+            "WITH latest AS (
+                SELECT id, payload_hash, payload FROM space_cache
+                WHERE source=$1 ORDER BY id DESC LIMIT 1
+             ), bumped AS (
+                UPDATE space_cache c
+                SET last_seen_at = now(), check_count = c.check_count + 1, payload_hash = $3
+                FROM latest
+                WHERE c.id = latest.id
+                  AND (latest.payload_hash = $3
+                       -- rows written before hashing: jsonb equality ignores key order
+                       OR latest.payload_hash IS NULL AND latest.payload = $2)
+                RETURNING c.id
+             )
+             INSERT INTO space_cache(source, payload, payload_hash, last_seen_at)
+             SELECT $1, $2, $3, now()
+             WHERE NOT EXISTS (SELECT 1 FROM bumped)
+             RETURNING id",
+        )
+        .bind(source)
+        .bind(payload)
+        .bind(hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(inserted.is_some())
+    }
+
+    async fn latest(&self, source: &str) -> anyhow::Result<Option<SpaceCacheItem>> {
+        let row = sqlx::query(
+            "SELECT fetched_at, last_seen_at, check_count, payload_hash, payload
+             FROM space_cache
+             WHERE source=$1
+             ORDER BY id DESC
+             LIMIT 1",
+        )
+        .bind(source)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| cache_item(source, &r)))
+    }
+
+    async fn history(&self, source: &str, limit: i64) -> anyhow::Result<Vec<SpaceCacheItem>> {
+        let rows = sqlx::query(
+            "SELECT fetched_at, last_seen_at, check_count, payload_hash, payload
+             FROM space_cache
+             WHERE source=$1
+             ORDER BY id DESC
+             LIMIT $2",
+        )
+        .bind(source)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(|r| cache_item(source, r)).collect())
+    }
+}
+
+#[derive(Clone)]
+pub struct PgRetentionRepo {
+    pool: PgPool,
+}
+
+impl PgRetentionRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RetentionRepo for PgRetentionRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS retention_runs(
+                id BIGSERIAL PRIMARY KEY,
+                ran_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                target TEXT NOT NULL,
+                downsampled BIGINT NOT NULL,
+                expired BIGINT NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS ix_retention_runs_target ON retention_runs(target, ran_at DESC)",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn apply(
+        &self,
+        policy: &RetentionPolicy,
+        skip_sources: &[String],
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<(i64, i64)> {
+        let table = match policy.table.as_str() {
+            "iss_fetch_log" => "iss_fetch_log",
+            "space_cache" => "space_cache",
+            other => anyhow::bail!("retention: unknown table `{other}`"),
+        };
+        let partition = if table == "space_cache" { "source, " } else { "norad_id, " };
+
+        let mut downsampled = 0;
+        if let (Some(raw), Some(bucket)) = (policy.raw, policy.bucket) {
+            let cut = now - chrono::Duration::from_std(raw)?;
+            let bucket = bucket.as_secs() as i64;
+            let mut qb = QueryBuilder::<Postgres>::new(format!("DELETE FROM {table} WHERE fetched_at < "));
+            qb.push_bind(cut);
+            push_scope(&mut qb, table, policy, skip_sources);
+            qb.push(format!(
+                " AND id NOT IN (SELECT DISTINCT ON ({partition}b) id FROM
+                   (SELECT *, floor(extract(epoch FROM fetched_at) / "
+            ));
+            qb.push_bind(bucket);
+            qb.push(format!(") AS b FROM {table} WHERE fetched_at < "));
+            qb.push_bind(cut);
+            qb.push(format!(") s ORDER BY {partition}b, fetched_at)"));
+            downsampled = qb.build().execute(&self.pool).await?.rows_affected() as i64;
+        }
+
+        let expire_after = policy.max.or(if policy.bucket.is_none() { policy.raw } else { None });
+        let mut expired = 0;
+        if let Some(max) = expire_after {
+            let cut = now - chrono::Duration::from_std(max)?;
+            let mut qb = QueryBuilder::<Postgres>::new(format!("DELETE FROM {table} WHERE fetched_at < "));
+            qb.push_bind(cut);
+            push_scope(&mut qb, table, policy, skip_sources);
+            expired = qb.build().execute(&self.pool).await?.rows_affected() as i64;
+        }
+
+        Ok((downsampled, expired))
+    }
+
+    async fn record(
+        &self,
+        target: &str,
+        downsampled: i64,
+        expired: i64,
+    ) -> anyhow::Result<RetentionRun> {
+        let ran_at: DateTime<Utc> = sqlx::query_scalar(
+            "INSERT INTO retention_runs(target, downsampled, expired) VALUES ($1,$2,$3) RETURNING ran_at",
+        )
+        .bind(target)
+        .bind(downsampled)
+        .bind(expired)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(RetentionRun {
+            target: target.to_string(),
+            ran_at,
+            downsampled,
+            expired,
+        })
+    }
+
+    async fn table_stats(&self) -> anyhow::Result<Vec<TableStats>> {
+        let mut out = Vec::new();
+        for table in RetentionPolicy::TABLES {
+            let row = sqlx::query(&format!(
+                "SELECT count(*) AS rows,
+                    (SELECT coalesce(sum(pg_total_relation_size(c.oid)), 0)::bigint
+                     FROM pg_class c
+                     WHERE c.oid = '{table}'::regclass
+                        OR c.oid IN (SELECT inhrelid FROM pg_inherits
+                                     WHERE inhparent = '{table}'::regclass)) AS bytes
+                 FROM {table}"
+            ))
+            .fetch_one(&self.pool)
+            .await?;
+            let reclaimed: i64 = sqlx::query_scalar(
+                "SELECT coalesce(sum(downsampled + expired), 0)::bigint
+                 FROM retention_runs
+                 WHERE target = $1 OR target LIKE $1 || '/%'",
+            )
+            .bind(table)
+            .fetch_one(&self.pool)
+            .await?;
+            out.push(TableStats {
+                table: table.to_string(),
+                rows: row.get("rows"),
+                total_bytes: row.get("bytes"),
+                reclaimed_rows: reclaimed,
+            });
+        }
+        Ok(out)
+    }
+
+    async fn last_runs(&self) -> anyhow::Result<Vec<RetentionRun>> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT ON (target) target, ran_at, downsampled, expired
+             FROM retention_runs
+             ORDER BY target, ran_at DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| RetentionRun {
+                target: r.get("target"),
+                ran_at: r.get("ran_at"),
+                downsampled: r.get("downsampled"),
+                expired: r.get("expired"),
+            })
+            .collect())
+    }
+}
+
+#[derive(Clone)]
+pub struct PgTleRepo {
+    pool: PgPool,
+}
+
+impl PgTleRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl TleRepo for PgTleRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS tle_sets(
+                id BIGSERIAL PRIMARY KEY,
+                norad_id BIGINT NOT NULL,
+                name TEXT,
+                epoch TIMESTAMPTZ NOT NULL,
+                line1 TEXT NOT NULL,
+                line2 TEXT NOT NULL,
+                fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                UNIQUE(norad_id, epoch)
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert(&self, sets: &[TleSet]) -> anyhow::Result<usize> {
+        if sets.is_empty() {
+            return Ok(0);
+        }
+        let inserted = sqlx::query(
+            "INSERT INTO tle_sets(norad_id, name, epoch, line1, line2, fetched_at)
+             SELECT * FROM UNNEST($1::bigint[], $2::text[], $3::timestamptz[],
+                                  $4::text[], $5::text[], $6::timestamptz[])
+             ON CONFLICT (norad_id, epoch) DO NOTHING",
+        )
+        .bind(sets.iter().map(|s| s.norad_id).collect::<Vec<_>>())
+        .bind(sets.iter().map(|s| s.name.clone()).collect::<Vec<_>>())
+        .bind(sets.iter().map(|s| s.epoch).collect::<Vec<_>>())
+        .bind(sets.iter().map(|s| s.line1.clone()).collect::<Vec<_>>())
+        .bind(sets.iter().map(|s| s.line2.clone()).collect::<Vec<_>>())
+        .bind(sets.iter().map(|s| s.fetched_at).collect::<Vec<_>>())
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(inserted as usize)
+    }
+
+    async fn nearest(&self, norad_id: i64, at: DateTime<Utc>) -> anyhow::Result<Option<TleSet>> {
+        // the closest set on either side of `at`, each found through the unique index
+        let rows = sqlx::query(
+            "(SELECT norad_id, name, epoch, line1, line2, fetched_at FROM tle_sets
+              WHERE norad_id = $1 AND epoch <= $2 ORDER BY epoch DESC LIMIT 1)
+             UNION ALL
+             (SELECT norad_id, name, epoch, line1, line2, fetched_at FROM tle_sets
+              WHERE norad_id = $1 AND epoch > $2 ORDER BY epoch LIMIT 1)",
+        )
+        .bind(norad_id)
+        .bind(at)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(closest_epoch(rows.iter().map(tle_set), at))
+    }
+
+    async fn history(&self, norad_id: i64, limit: i64) -> anyhow::Result<Vec<TleSet>> {
+        let rows = sqlx::query(
+            "SELECT norad_id, name, epoch, line1, line2, fetched_at FROM tle_sets
+             WHERE norad_id = $1
+             ORDER BY epoch DESC
+             LIMIT $2",
+        )
+        .bind(norad_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(tle_set).collect())
+    }
+}
+
+fn tle_set(r: &PgRow) -> TleSet {
+    TleSet {
+        norad_id: r.get("norad_id"),
+        name: r.get("name"),
+        epoch: r.get("epoch"),
+        line1: r.get("line1"),
+        line2: r.get("line2"),
+        fetched_at: r.get("fetched_at"),
+    }
+}
+
+#[derive(Clone)]
+pub struct PgEventRepo {
+    pool: PgPool,
+}
+
+impl PgEventRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventRepo for PgEventRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS iss_events(
+                id BIGSERIAL PRIMARY KEY,
+                norad_id BIGINT NOT NULL,
+                fence TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                at TIMESTAMPTZ NOT NULL,
+                latitude DOUBLE PRECISION NOT NULL,
+                longitude DOUBLE PRECISION NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS ix_iss_events_fence
+                ON iss_events(norad_id, fence, id DESC)",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert(&self, events: &[GeofenceEvent]) -> anyhow::Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        sqlx::query(
+            "INSERT INTO iss_events(norad_id, fence, kind, at, latitude, longitude)
+             SELECT * FROM UNNEST($1::bigint[], $2::text[], $3::text[], $4::timestamptz[],
+                                  $5::float8[], $6::float8[])",
+        )
+        .bind(events.iter().map(|e| e.norad_id).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.fence.clone()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.kind.as_str()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.at).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.latitude).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.longitude).collect::<Vec<_>>())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn inside(&self, norad_id: i64) -> anyhow::Result<Vec<String>> {
+        Ok(sqlx::query_scalar(
+            "SELECT fence FROM (
+                SELECT DISTINCT ON (fence) fence, kind FROM iss_events
+                WHERE norad_id = $1
+                ORDER BY fence, id DESC
+             ) latest WHERE kind = 'enter'",
+        )
+        .bind(norad_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn list(&self, q: &GeofenceEventQuery) -> anyhow::Result<Vec<GeofenceEvent>> {
+        let mut qb = QueryBuilder::<Postgres>::new(
+            "SELECT id, norad_id, fence, kind, at, latitude, longitude FROM iss_events WHERE true",
+        );
+        if let Some(id) = q.norad_id {
+            qb.push(" AND norad_id = ").push_bind(id);
+        }
+        if let Some(fence) = &q.fence {
+            qb.push(" AND fence = ").push_bind(fence.clone());
+        }
+        if let Some(since) = q.since {
+            qb.push(" AND at >= ").push_bind(since);
+        }
+        qb.push(" ORDER BY id DESC LIMIT ").push_bind(q.limit);
+        let rows = qb.build().fetch_all(&self.pool).await?;
+        Ok(rows.iter().filter_map(geofence_event).collect())
+    }
+}
+
+fn geofence_event(r: &PgRow) -> Option<GeofenceEvent> {
+    Some(GeofenceEvent {
+        id: r.get("id"),
+        norad_id: r.get("norad_id"),
+        fence: r.get("fence"),
+        kind: GeofenceTransition::parse(r.get("kind"))?,
+        at: r.get("at"),
+        latitude: r.get("latitude"),
+        longitude: r.get("longitude"),
+    })
+}
+
+#[derive(Clone)]
+pub struct PgJobLocks {
+    pool: PgPool,
+}
+
+impl PgJobLocks {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl JobLocks for PgJobLocks {
+    async fn try_lock(&self, key: i64) -> bool {
+        sqlx::query_scalar::<_, bool>("SELECT pg_try_advisory_lock($1)")
+            .bind(key)
+            .fetch_one(&self.pool)
+            .await
+            .unwrap_or(false)
+    }
+
+    async fn unlock(&self, key: i64) -> bool {
+        sqlx::query_scalar::<_, bool>("SELECT pg_advisory_unlock($1)")
+            .bind(key)
+            .fetch_one(&self.pool)
+            .await
+            .unwrap_or(false)
+    }
+}
+
+fn cache_item(source: &str, r: &sqlx::postgres::PgRow) -> SpaceCacheItem {
+    SpaceCacheItem {
+        source: source.to_string(),
+        fetched_at: r.get("fetched_at"),
+        last_seen_at: r.get("last_seen_at"),
+        check_count: r.get("check_count"),
+        payload_hash: r.get("payload_hash"),
+        payload: r.get("payload"),
+    }
+}
+
+fn push_scope<'a>(
+    qb: &mut QueryBuilder<'a, Postgres>,
+    table: &str,
+    policy: &RetentionPolicy,
+    skip_sources: &[String],
+) {
+    if table == "space_cache" {
+        match &policy.source {
+            Some(src) => {
+                qb.push(" AND source = ").push_bind(src.clone());
+            }
+            None => {
+                qb.push(" AND NOT (source = ANY(")
+                    .push_bind(skip_sources.to_vec())
+                    .push("))");
+            }
+        }
+        qb.push(" AND id NOT IN (SELECT max(id) FROM space_cache GROUP BY source)");
+    } else {
+        qb.push(" AND id NOT IN (SELECT max(id) FROM iss_fetch_log GROUP BY norad_id)");
+    }
+}
+
+/// First key of the per-dataset advisory locks taken by OSDR upserts; the
+/// two-key lock space does not overlap the one of [`JobLocks`].
+const OSDR_LOCK_CLASS: i32 = 0x05D2;
+
+/// Holds rows of months that have no partition of their own.
+const DEFAULT_PARTITION: &str = "iss_fetch_log_default";
+
+fn create_partition_sql(month: DateTime<Utc>) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} PARTITION OF iss_fetch_log FOR VALUES FROM ('{}') TO ('{}')",
+        partition_name(month),
+        month.to_rfc3339(),
+        add_months(month, 1).to_rfc3339()
+    )
+}
+
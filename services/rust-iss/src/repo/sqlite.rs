@@ -0,0 +1,1351 @@
+use super::{
+    batch_ids, closest_epoch, fallback_search, iss_point, osdr_search_body, plan_osdr_batch,
+    payload_hash, removed_diff, CacheRepo, EventRepo, IssRepo, JobLocks, OsdrRepo, RetentionRepo,
+    TleRepo,
+};
+use crate::domain::{
+    GeofenceEvent, GeofenceEventQuery, GeofenceTransition, IssPoint, IssStorage, OsdrAssay,
+    OsdrChange, OsdrDetail, OsdrEnrichCandidate, OsdrEnrichFailure, OsdrFile, OsdrFilter, OsdrItem,
+    OsdrListQuery, OsdrSearchHit, OsdrSearchQuery, OsdrSort, OsdrSyncState, OsdrUpsert, OsdrVersion,
+    OsdrVersionKind, RetentionPolicy, RetentionRun, SpaceCacheItem, TableStats, TleSet,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
+use std::collections::{HashMap, HashSet};
+
+/// Rows per multi-row statement, keeping bound parameters under SQLite's
+/// default limit of 999.
+const BATCH_ROWS: usize = 100;
+
+// Timestamps are bound from Rust (sqlx stores them as RFC 3339 text) rather
+// than defaulted in SQL, so every row uses the same sortable format.
+
+#[derive(Clone)]
+pub struct SqliteIssRepo {
+    pool: SqlitePool,
+}
+
+impl SqliteIssRepo {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl IssRepo for SqliteIssRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS iss_fetch_log(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                fetched_at TEXT NOT NULL,
+                source_url TEXT NOT NULL,
+                payload TEXT NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        let columns: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM pragma_table_info('iss_fetch_log')")
+                .fetch_all(&self.pool)
+                .await?;
+        if !columns.iter().any(|c| c == "norad_id") {
+            sqlx::query(
+                "ALTER TABLE iss_fetch_log ADD COLUMN norad_id INTEGER NOT NULL DEFAULT 25544",
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS ix_iss_fetch_log_fetched_at ON iss_fetch_log(fetched_at)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS ix_iss_fetch_log_norad ON iss_fetch_log(norad_id, fetched_at)",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_log(
+        &self,
+        norad_id: i64,
+        source_url: &str,
+        payload: &Value,
+    ) -> anyhow::Result<DateTime<Utc>> {
+        let fetched_at = Utc::now();
+        sqlx::query(
+            "INSERT INTO iss_fetch_log(norad_id, fetched_at, source_url, payload) VALUES (?,?,?,?)",
+        )
+        .bind(norad_id)
+        .bind(fetched_at)
+        .bind(source_url)
+        .bind(payload)
+        .execute(&self.pool)
+        .await?;
+        Ok(fetched_at)
+    }
+
+    async fn last(
+        &self,
+        norad_id: i64,
+    ) -> anyhow::Result<Option<(i64, DateTime<Utc>, String, Value)>> {
+        let row = sqlx::query(
+            "SELECT id, fetched_at, source_url, payload
+             FROM iss_fetch_log
+             WHERE norad_id = ?
+             ORDER BY fetched_at DESC, id DESC LIMIT 1",
+        )
+        .bind(norad_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| {
+            (
+                r.get("id"),
+                r.get("fetched_at"),
+                r.get("source_url"),
+                r.get("payload"),
+            )
+        }))
+    }
+
+    async fn trend(&self, norad_id: i64, limit: i64) -> anyhow::Result<Vec<IssPoint>> {
+        let rows = sqlx::query(
+            "SELECT fetched_at, payload
+             FROM iss_fetch_log
+             WHERE norad_id = ?
+             ORDER BY fetched_at DESC, id DESC
+             LIMIT ?",
+        )
+        .bind(norad_id)
+        .bind(limit.max(2))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .rev()
+            .map(|r| iss_point(r.get("fetched_at"), &r.get::<Value, _>("payload")))
+            .collect())
+    }
+
+    async fn range(
+        &self,
+        norad_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<IssPoint>> {
+        let rows = sqlx::query(
+            "SELECT fetched_at, payload
+             FROM iss_fetch_log
+             WHERE norad_id = ? AND fetched_at >= ? AND fetched_at <= ?
+             ORDER BY fetched_at, id",
+        )
+        .bind(norad_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| iss_point(r.get("fetched_at"), &r.get::<Value, _>("payload")))
+            .collect())
+    }
+
+    async fn storage(&self) -> anyhow::Result<Option<IssStorage>> {
+        Ok(Some(IssStorage::Sqlite))
+    }
+}
+
+#[derive(Clone)]
+pub struct SqliteOsdrRepo {
+    pool: SqlitePool,
+}
+
+impl SqliteOsdrRepo {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    // SQLite has no usable Unicode case folding, so search filters in Rust.
+    async fn search_rows(&self, filter: &OsdrFilter) -> anyhow::Result<Vec<(OsdrItem, String)>> {
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            "SELECT id, dataset_id, title, status, updated_at, inserted_at, removed_at, raw,
+                    coalesce(search_body, '') AS search_body
+             FROM osdr_items WHERE 1=1",
+        );
+        push_osdr_filter(&mut qb, filter);
+        let rows = qb.build().fetch_all(&self.pool).await?;
+        Ok(rows
+            .iter()
+            .map(|r| (osdr_item(r), r.get("search_body")))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl OsdrRepo for SqliteOsdrRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS osdr_items(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                dataset_id TEXT,
+                title TEXT,
+                status TEXT,
+                updated_at TEXT,
+                inserted_at TEXT NOT NULL,
+                raw TEXT NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS ux_osdr_dataset_id
+             ON osdr_items(dataset_id) WHERE dataset_id IS NOT NULL",
+        )
+        .execute(&self.pool)
+        .await?;
+        for stmt in [
+            "CREATE TABLE IF NOT EXISTS osdr_details(
+                dataset_id TEXT PRIMARY KEY,
+                source_updated_at TEXT,
+                enriched_at TEXT NOT NULL,
+                mission_name TEXT,
+                mission TEXT,
+                raw TEXT NOT NULL
+            )",
+            "CREATE TABLE IF NOT EXISTS osdr_enrich_failures(
+                dataset_id TEXT PRIMARY KEY,
+                attempts INTEGER NOT NULL,
+                failed_at TEXT NOT NULL,
+                retry_at TEXT NOT NULL,
+                error TEXT NOT NULL
+            )",
+            "CREATE TABLE IF NOT EXISTS osdr_files(
+                dataset_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                url TEXT,
+                size_bytes INTEGER,
+                category TEXT,
+                PRIMARY KEY (dataset_id, name)
+            )",
+            "CREATE TABLE IF NOT EXISTS osdr_assays(
+                dataset_id TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                measurement TEXT,
+                technology TEXT,
+                platform TEXT,
+                PRIMARY KEY (dataset_id, position)
+            )",
+            "CREATE TABLE IF NOT EXISTS osdr_organisms(
+                dataset_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                PRIMARY KEY (dataset_id, name)
+            )",
+            "CREATE TABLE IF NOT EXISTS osdr_factors(
+                dataset_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                PRIMARY KEY (dataset_id, name)
+            )",
+            "CREATE TABLE IF NOT EXISTS osdr_versions(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                dataset_id TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                changed_at TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                title TEXT,
+                diff TEXT NOT NULL,
+                UNIQUE (dataset_id, version)
+            )",
+            "CREATE INDEX IF NOT EXISTS ix_osdr_versions_changed_at ON osdr_versions(changed_at)",
+            "CREATE TABLE IF NOT EXISTS sync_state(
+                source TEXT PRIMARY KEY,
+                watermark TEXT,
+                last_full_at TEXT,
+                last_sync_at TEXT
+            )",
+        ] {
+            sqlx::query(stmt).execute(&self.pool).await?;
+        }
+        add_column(&self.pool, "osdr_items", "search_body", "TEXT").await?;
+        add_column(&self.pool, "osdr_items", "removed_at", "TEXT").await?;
+        let pending: Vec<(i64, String)> =
+            sqlx::query("SELECT id, raw FROM osdr_items WHERE search_body IS NULL")
+                .fetch_all(&self.pool)
+                .await?
+                .iter()
+                .map(|r| (r.get("id"), osdr_search_body(&r.get::<Value, _>("raw"))))
+                .collect();
+        if !pending.is_empty() {
+            // one statement over `[[id, body], …]`
+            sqlx::query(
+                "UPDATE osdr_items SET search_body = json_extract(b.value, '$[1]')
+                 FROM json_each(?) AS b
+                 WHERE osdr_items.id = json_extract(b.value, '$[0]')",
+            )
+            .bind(json!(pending))
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn upsert(&self, item: OsdrUpsert) -> anyhow::Result<OsdrChange> {
+        if item.dataset_id.is_none() {
+            sqlx::query(
+                "INSERT INTO osdr_items(dataset_id, title, status, updated_at, inserted_at, raw, search_body)
+                 VALUES(NULL,?,?,?,?,?,?)",
+            )
+            .bind(item.title)
+            .bind(item.status)
+            .bind(item.updated_at)
+            .bind(Utc::now())
+            .bind(&item.raw)
+            .bind(osdr_search_body(&item.raw))
+            .execute(&self.pool)
+            .await?;
+            return Ok(OsdrChange::Created);
+        }
+        let changes = self.upsert_batch(std::slice::from_ref(&item)).await?;
+        Ok(changes[0])
+    }
+
+    async fn upsert_batch(&self, items: &[OsdrUpsert]) -> anyhow::Result<Vec<OsdrChange>> {
+        let mut tx = self.pool.begin().await?;
+        let mut old = HashMap::new();
+        for ids in batch_ids(items).chunks(BATCH_ROWS) {
+            let mut qb = QueryBuilder::<Sqlite>::new(
+                "SELECT id, dataset_id, title, status, updated_at, inserted_at, removed_at, raw
+                 FROM osdr_items WHERE dataset_id IN (",
+            );
+            let mut sep = qb.separated(", ");
+            for id in ids {
+                sep.push_bind(id);
+            }
+            qb.push(")");
+            for r in qb.build().fetch_all(&mut *tx).await? {
+                let item = osdr_item(&r);
+                if let Some(ds) = item.dataset_id.clone() {
+                    old.insert(ds, item);
+                }
+            }
+        }
+        let (changes, writes) = plan_osdr_batch(&old, items)?;
+        let now = Utc::now();
+        for chunk in writes.chunks(BATCH_ROWS) {
+            let mut qb = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO osdr_items(dataset_id, title, status, updated_at, inserted_at, raw, search_body) ",
+            );
+            qb.push_values(chunk, |mut b, w| {
+                b.push_bind(w.dataset_id)
+                    .push_bind(&w.item.title)
+                    .push_bind(&w.item.status)
+                    .push_bind(w.item.updated_at)
+                    .push_bind(now)
+                    .push_bind(&w.item.raw)
+                    .push_bind(osdr_search_body(&w.item.raw));
+            });
+            qb.push(
+                " ON CONFLICT (dataset_id) WHERE dataset_id IS NOT NULL DO UPDATE
+                 SET title=excluded.title,
+                     status=excluded.status,
+                     updated_at=excluded.updated_at,
+                     raw=excluded.raw,
+                     search_body=excluded.search_body,
+                     removed_at=NULL",
+            );
+            qb.build().execute(&mut *tx).await?;
+
+            let mut qb = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO osdr_versions(dataset_id, version, changed_at, kind, title, diff) ",
+            );
+            qb.push_values(chunk, |mut b, w| {
+                b.push_bind(w.dataset_id)
+                    .push("coalesce((SELECT max(version) FROM osdr_versions WHERE dataset_id = ")
+                    .push_bind_unseparated(w.dataset_id)
+                    .push_unseparated("), 0) + 1")
+                    .push_bind(now)
+                    .push_bind(w.kind.as_str())
+                    .push_bind(&w.item.title)
+                    .push_bind(&w.diff);
+            });
+            qb.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(changes)
+    }
+
+    async fn list(&self, q: &OsdrListQuery) -> anyhow::Result<Vec<OsdrItem>> {
+        let expr = osdr_sort_expr(q.sort);
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            "SELECT id, dataset_id, title, status, updated_at, inserted_at, removed_at, raw
+             FROM osdr_items WHERE 1=1",
+        );
+        push_osdr_filter(&mut qb, &q.filter);
+        if let Some(c) = &q.after {
+            let op = if q.desc { "<" } else { ">" };
+            qb.push(format!(" AND ({expr}, id) {op} ("));
+            match q.sort {
+                OsdrSort::InsertedAt => {
+                    let at = c
+                        .key_time()?
+                        .ok_or_else(|| anyhow::anyhow!("cursor: missing inserted_at"))?;
+                    qb.push_bind(at);
+                }
+                OsdrSort::UpdatedAt => {
+                    qb.push("coalesce(").push_bind(c.key_time()?).push(", '')");
+                }
+                OsdrSort::Title => {
+                    qb.push_bind(c.key.clone().unwrap_or_default());
+                }
+            }
+            qb.push(", ").push_bind(c.id).push(")");
+        }
+        let dir = if q.desc { "DESC" } else { "ASC" };
+        qb.push(format!(" ORDER BY {expr} {dir}, id {dir} LIMIT "))
+            .push_bind(q.limit);
+        let rows = qb.build().fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(osdr_item).collect())
+    }
+
+    async fn count(&self, filter: &OsdrFilter) -> anyhow::Result<i64> {
+        let mut qb = QueryBuilder::<Sqlite>::new("SELECT count(*) FROM osdr_items WHERE 1=1");
+        push_osdr_filter(&mut qb, filter);
+        Ok(qb.build_query_scalar().fetch_one(&self.pool).await?)
+    }
+
+    async fn get(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrItem>> {
+        let row = sqlx::query(
+            "SELECT id, dataset_id, title, status, updated_at, inserted_at, removed_at, raw
+             FROM osdr_items WHERE dataset_id = ?",
+        )
+        .bind(dataset_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.as_ref().map(osdr_item))
+    }
+
+    async fn enrich_candidates(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<OsdrEnrichCandidate>> {
+        let rows = sqlx::query(
+            "SELECT i.dataset_id, i.updated_at, coalesce(f.attempts, 0) AS attempts
+             FROM osdr_items i
+             LEFT JOIN osdr_details d ON d.dataset_id = i.dataset_id
+             LEFT JOIN osdr_enrich_failures f ON f.dataset_id = i.dataset_id
+             WHERE i.dataset_id IS NOT NULL AND i.removed_at IS NULL
+               AND (d.dataset_id IS NULL OR d.source_updated_at IS NOT i.updated_at)
+               AND (f.retry_at IS NULL OR f.retry_at <= ?)
+             ORDER BY i.updated_at IS NULL, i.updated_at DESC, i.id DESC
+             LIMIT ?",
+        )
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| OsdrEnrichCandidate {
+                dataset_id: r.get("dataset_id"),
+                updated_at: r.get("updated_at"),
+                attempts: r.get("attempts"),
+            })
+            .collect())
+    }
+
+    async fn record_enrich_failure(&self, f: &OsdrEnrichFailure) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO osdr_enrich_failures(dataset_id, attempts, failed_at, retry_at, error)
+             VALUES(?,?,?,?,?)
+             ON CONFLICT (dataset_id) DO UPDATE
+             SET attempts=excluded.attempts,
+                 failed_at=excluded.failed_at,
+                 retry_at=excluded.retry_at,
+                 error=excluded.error",
+        )
+        .bind(&f.dataset_id)
+        .bind(f.attempts)
+        .bind(f.failed_at)
+        .bind(f.retry_at)
+        .bind(&f.error)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn save_detail(&self, d: &OsdrDetail) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO osdr_details(dataset_id, source_updated_at, enriched_at, mission_name, mission, raw)
+             VALUES(?,?,?,?,?,?)
+             ON CONFLICT (dataset_id) DO UPDATE
+             SET source_updated_at=excluded.source_updated_at,
+                 enriched_at=excluded.enriched_at,
+                 mission_name=excluded.mission_name,
+                 mission=excluded.mission,
+                 raw=excluded.raw",
+        )
+        .bind(&d.dataset_id)
+        .bind(d.source_updated_at)
+        .bind(d.enriched_at)
+        .bind(&d.mission_name)
+        .bind(&d.mission)
+        .bind(&d.raw)
+        .execute(&mut *tx)
+        .await?;
+        for table in [
+            "osdr_files",
+            "osdr_assays",
+            "osdr_organisms",
+            "osdr_factors",
+            "osdr_enrich_failures",
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE dataset_id = ?"))
+                .bind(&d.dataset_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        if !d.files.is_empty() {
+            QueryBuilder::<Sqlite>::new(
+                "INSERT INTO osdr_files(dataset_id, name, url, size_bytes, category) ",
+            )
+            .push_values(&d.files, |mut b, f| {
+                b.push_bind(&d.dataset_id)
+                    .push_bind(&f.name)
+                    .push_bind(&f.url)
+                    .push_bind(f.size_bytes)
+                    .push_bind(&f.category);
+            })
+            .build()
+            .execute(&mut *tx)
+            .await?;
+        }
+        if !d.assays.is_empty() {
+            QueryBuilder::<Sqlite>::new(
+                "INSERT INTO osdr_assays(dataset_id, position, measurement, technology, platform) ",
+            )
+            .push_values(d.assays.iter().enumerate(), |mut b, (i, a)| {
+                b.push_bind(&d.dataset_id)
+                    .push_bind(i as i64)
+                    .push_bind(&a.measurement)
+                    .push_bind(&a.technology)
+                    .push_bind(&a.platform);
+            })
+            .build()
+            .execute(&mut *tx)
+            .await?;
+        }
+        for (table, names) in [("osdr_organisms", &d.organisms), ("osdr_factors", &d.factors)] {
+            if names.is_empty() {
+                continue;
+            }
+            QueryBuilder::<Sqlite>::new(format!("INSERT INTO {table}(dataset_id, name) "))
+                .push_values(names, |mut b, name| {
+                    b.push_bind(&d.dataset_id).push_bind(name);
+                })
+                .build()
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn enrich_failure(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrEnrichFailure>> {
+        let row = sqlx::query(
+            "SELECT attempts, failed_at, retry_at, error FROM osdr_enrich_failures
+             WHERE dataset_id = ?",
+        )
+        .bind(dataset_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| OsdrEnrichFailure {
+            dataset_id: dataset_id.to_string(),
+            attempts: r.get("attempts"),
+            failed_at: r.get("failed_at"),
+            retry_at: r.get("retry_at"),
+            error: r.get("error"),
+        }))
+    }
+
+    async fn detail(&self, dataset_id: &str) -> anyhow::Result<Option<OsdrDetail>> {
+        let Some(row) = sqlx::query(
+            "SELECT source_updated_at, enriched_at, mission_name, mission, raw
+             FROM osdr_details WHERE dataset_id = ?",
+        )
+        .bind(dataset_id)
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let files = sqlx::query(
+            "SELECT name, url, size_bytes, category FROM osdr_files
+             WHERE dataset_id = ? ORDER BY name",
+        )
+        .bind(dataset_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|r| OsdrFile {
+            name: r.get("name"),
+            url: r.get("url"),
+            size_bytes: r.get("size_bytes"),
+            category: r.get("category"),
+        })
+        .collect();
+        let assays = sqlx::query(
+            "SELECT measurement, technology, platform FROM osdr_assays
+             WHERE dataset_id = ? ORDER BY position",
+        )
+        .bind(dataset_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|r| OsdrAssay {
+            measurement: r.get("measurement"),
+            technology: r.get("technology"),
+            platform: r.get("platform"),
+        })
+        .collect();
+        let organisms =
+            sqlx::query_scalar("SELECT name FROM osdr_organisms WHERE dataset_id = ? ORDER BY name")
+                .bind(dataset_id)
+                .fetch_all(&self.pool)
+                .await?;
+        let factors =
+            sqlx::query_scalar("SELECT name FROM osdr_factors WHERE dataset_id = ? ORDER BY name")
+                .bind(dataset_id)
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(Some(OsdrDetail {
+            dataset_id: dataset_id.to_string(),
+            source_updated_at: row.get("source_updated_at"),
+            enriched_at: row.get("enriched_at"),
+            mission_name: row.get("mission_name"),
+            mission: row.get("mission"),
+            organisms,
+            factors,
+            assays,
+            files,
+            raw: row.get("raw"),
+        }))
+    }
+
+    async fn search(&self, q: &OsdrSearchQuery) -> anyhow::Result<Vec<OsdrSearchHit>> {
+        Ok(fallback_search(self.search_rows(&q.filter).await?, q).0)
+    }
+
+    async fn search_count(&self, q: &OsdrSearchQuery) -> anyhow::Result<i64> {
+        Ok(fallback_search(self.search_rows(&q.filter).await?, q).1)
+    }
+
+    async fn mark_removed(
+        &self,
+        seen: &[String],
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<String>> {
+        let seen: HashSet<&str> = seen.iter().map(String::as_str).collect();
+        let mut tx = self.pool.begin().await?;
+        let live = sqlx::query(
+            "SELECT dataset_id, title FROM osdr_items
+             WHERE dataset_id IS NOT NULL AND removed_at IS NULL",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut ids = Vec::new();
+        for r in live {
+            let ds: String = r.get("dataset_id");
+            if seen.contains(ds.as_str()) {
+                continue;
+            }
+            sqlx::query("UPDATE osdr_items SET removed_at = ? WHERE dataset_id = ?")
+                .bind(at)
+                .bind(&ds)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                "INSERT INTO osdr_versions(dataset_id, version, changed_at, kind, title, diff)
+                 SELECT ?1, coalesce(max(version), 0) + 1, ?2, 'removed', ?3, ?4
+                 FROM osdr_versions WHERE dataset_id = ?1",
+            )
+            .bind(&ds)
+            .bind(at)
+            .bind(r.get::<Option<String>, _>("title"))
+            .bind(removed_diff(at))
+            .execute(&mut *tx)
+            .await?;
+            ids.push(ds);
+        }
+        tx.commit().await?;
+        Ok(ids)
+    }
+
+    async fn history(&self, dataset_id: &str, limit: i64) -> anyhow::Result<Vec<OsdrVersion>> {
+        let rows = sqlx::query(
+            "SELECT dataset_id, version, changed_at, kind, title, diff
+             FROM osdr_versions WHERE dataset_id = ?
+             ORDER BY version DESC LIMIT ?",
+        )
+        .bind(dataset_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().filter_map(osdr_version).collect())
+    }
+
+    async fn recent_changes(
+        &self,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<OsdrVersion>> {
+        let rows = sqlx::query(
+            "SELECT v.dataset_id, v.version, v.changed_at, v.kind, v.title, v.diff
+             FROM osdr_versions v
+             WHERE v.version = (SELECT max(version) FROM osdr_versions WHERE dataset_id = v.dataset_id)
+               AND (?1 IS NULL OR v.changed_at >= ?1)
+             ORDER BY v.changed_at DESC, v.dataset_id LIMIT ?2",
+        )
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().filter_map(osdr_version).collect())
+    }
+
+    async fn sync_state(&self) -> anyhow::Result<OsdrSyncState> {
+        let row = sqlx::query(
+            "SELECT watermark, last_full_at, last_sync_at FROM sync_state WHERE source = 'osdr'",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row
+            .map(|r| OsdrSyncState {
+                watermark: r.get("watermark"),
+                last_full_at: r.get("last_full_at"),
+                last_sync_at: r.get("last_sync_at"),
+            })
+            .unwrap_or_default())
+    }
+
+    async fn save_sync_state(&self, state: &OsdrSyncState) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO sync_state(source, watermark, last_full_at, last_sync_at)
+             VALUES('osdr', ?, ?, ?)
+             ON CONFLICT (source) DO UPDATE
+             SET watermark=max(coalesce(watermark, excluded.watermark),
+                               coalesce(excluded.watermark, watermark)),
+                 last_full_at=max(coalesce(last_full_at, excluded.last_full_at),
+                                  coalesce(excluded.last_full_at, last_full_at)),
+                 last_sync_at=max(coalesce(last_sync_at, excluded.last_sync_at),
+                                  coalesce(excluded.last_sync_at, last_sync_at))",
+        )
+        .bind(state.watermark)
+        .bind(state.last_full_at)
+        .bind(state.last_sync_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+/// `ALTER TABLE … ADD COLUMN` unless the column already exists; SQLite has
+/// no `IF NOT EXISTS` for columns.
+async fn add_column(pool: &SqlitePool, table: &str, column: &str, decl: &str) -> anyhow::Result<()> {
+    let exists: bool =
+        sqlx::query_scalar("SELECT count(*) > 0 FROM pragma_table_info(?) WHERE name = ?")
+            .bind(table)
+            .bind(column)
+            .fetch_one(pool)
+            .await?;
+    if !exists {
+        sqlx::query(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+fn osdr_version(r: &SqliteRow) -> Option<OsdrVersion> {
+    Some(OsdrVersion {
+        dataset_id: r.get("dataset_id"),
+        version: r.get("version"),
+        changed_at: r.get("changed_at"),
+        kind: OsdrVersionKind::parse(r.get("kind"))?,
+        title: r.get("title"),
+        diff: r.get("diff"),
+    })
+}
+
+fn osdr_item(r: &SqliteRow) -> OsdrItem {
+    OsdrItem {
+        id: r.get("id"),
+        dataset_id: r.get("dataset_id"),
+        title: r.get("title"),
+        status: r.get("status"),
+        updated_at: r.get("updated_at"),
+        inserted_at: r.get("inserted_at"),
+        removed_at: r.get("removed_at"),
+        raw: r.get("raw"),
+    }
+}
+
+fn osdr_sort_expr(sort: OsdrSort) -> &'static str {
+    match sort {
+        OsdrSort::InsertedAt => "inserted_at",
+        OsdrSort::UpdatedAt => "coalesce(updated_at, '')",
+        OsdrSort::Title => "coalesce(title, '')",
+    }
+}
+
+// SQLite's LIKE is case-insensitive, so the prefix match compares substrings.
+fn push_osdr_filter(qb: &mut QueryBuilder<'_, Sqlite>, f: &OsdrFilter) {
+    if let Some(status) = &f.status {
+        qb.push(" AND status = ").push_bind(status.clone());
+    }
+    if let Some(from) = f.updated_from {
+        qb.push(" AND updated_at >= ").push_bind(from);
+    }
+    if let Some(to) = f.updated_to {
+        qb.push(" AND updated_at < ").push_bind(to);
+    }
+    if !f.include_removed {
+        qb.push(" AND removed_at IS NULL");
+    }
+    if let Some(prefix) = &f.dataset_id_prefix {
+        qb.push(" AND substr(dataset_id, 1, length(")
+            .push_bind(prefix.clone())
+            .push(")) = ")
+            .push_bind(prefix.clone());
+    }
+}
+
+#[derive(Clone)]
+pub struct SqliteCacheRepo {
+    pool: SqlitePool,
+}
+
+impl SqliteCacheRepo {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl CacheRepo for SqliteCacheRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS space_cache(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                source TEXT NOT NULL,
+                fetched_at TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                payload_hash TEXT,
+                last_seen_at TEXT,
+                check_count INTEGER NOT NULL DEFAULT 1
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS ix_space_cache_source ON space_cache(source, fetched_at DESC)",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn write(&self, source: &str, payload: Value) -> anyhow::Result<bool> {
+        let hash = payload_hash(&payload);
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+        let bumped = sqlx::query(
+            "UPDATE space_cache
+             SET last_seen_at = ?, check_count = check_count + 1
+             WHERE id = (SELECT id FROM space_cache WHERE source = ? ORDER BY id DESC LIMIT 1)
+               AND payload_hash = ?",
+        )
+        .bind(now)
+        .bind(source)
+        .bind(&hash)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if bumped == 0 {
+            sqlx::query(
+                "INSERT INTO space_cache(source, fetched_at, payload, payload_hash, last_seen_at)
+                 VALUES (?,?,?,?,?)",
+            )
+            .bind(source)
+            .bind(now)
+            .bind(payload)
+            .bind(&hash)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(bumped == 0)
+    }
+
+    async fn latest(&self, source: &str) -> anyhow::Result<Option<SpaceCacheItem>> {
+        let row = sqlx::query(
+            "SELECT fetched_at, last_seen_at, check_count, payload_hash, payload
+             FROM space_cache
+             WHERE source = ?
+             ORDER BY id DESC
+             LIMIT 1",
+        )
+        .bind(source)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| cache_item(source, &r)))
+    }
+
+    async fn history(&self, source: &str, limit: i64) -> anyhow::Result<Vec<SpaceCacheItem>> {
+        let rows = sqlx::query(
+            "SELECT fetched_at, last_seen_at, check_count, payload_hash, payload
+             FROM space_cache
+             WHERE source = ?
+             ORDER BY id DESC
+             LIMIT ?",
+        )
+        .bind(source)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(|r| cache_item(source, r)).collect())
+    }
+}
+
+fn cache_item(source: &str, r: &SqliteRow) -> SpaceCacheItem {
+    SpaceCacheItem {
+        source: source.to_string(),
+        fetched_at: r.get("fetched_at"),
+        last_seen_at: r.get("last_seen_at"),
+        check_count: r.get("check_count"),
+        payload_hash: r.get("payload_hash"),
+        payload: r.get("payload"),
+    }
+}
+
+#[derive(Clone)]
+pub struct SqliteRetentionRepo {
+    pool: SqlitePool,
+}
+
+impl SqliteRetentionRepo {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RetentionRepo for SqliteRetentionRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS retention_runs(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ran_at TEXT NOT NULL,
+                target TEXT NOT NULL,
+                downsampled INTEGER NOT NULL,
+                expired INTEGER NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn apply(
+        &self,
+        policy: &RetentionPolicy,
+        skip_sources: &[String],
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<(i64, i64)> {
+        let table = match policy.table.as_str() {
+            "iss_fetch_log" => "iss_fetch_log",
+            "space_cache" => "space_cache",
+            other => anyhow::bail!("retention: unknown table `{other}`"),
+        };
+        let partition = if table == "space_cache" { "source, " } else { "norad_id, " };
+
+        let mut downsampled = 0;
+        if let (Some(raw), Some(bucket)) = (policy.raw, policy.bucket) {
+            let cut = now - chrono::Duration::from_std(raw)?;
+            let mut qb = QueryBuilder::<Sqlite>::new(format!("DELETE FROM {table} WHERE fetched_at < "));
+            qb.push_bind(cut);
+            push_scope(&mut qb, table, policy, skip_sources);
+            qb.push(format!(
+                " AND id NOT IN (SELECT id FROM
+                   (SELECT id, row_number() OVER (
+                        PARTITION BY {partition}CAST(strftime('%s', fetched_at) AS INTEGER) / "
+            ));
+            qb.push_bind(bucket.as_secs() as i64);
+            qb.push(format!(
+                " ORDER BY fetched_at, id) AS rn FROM {table} WHERE fetched_at < "
+            ));
+            qb.push_bind(cut);
+            qb.push(") WHERE rn = 1)");
+            downsampled = qb.build().execute(&self.pool).await?.rows_affected() as i64;
+        }
+
+        let expire_after = policy.max.or(if policy.bucket.is_none() { policy.raw } else { None });
+        let mut expired = 0;
+        if let Some(max) = expire_after {
+            let cut = now - chrono::Duration::from_std(max)?;
+            let mut qb = QueryBuilder::<Sqlite>::new(format!("DELETE FROM {table} WHERE fetched_at < "));
+            qb.push_bind(cut);
+            push_scope(&mut qb, table, policy, skip_sources);
+            expired = qb.build().execute(&self.pool).await?.rows_affected() as i64;
+        }
+        Ok((downsampled, expired))
+    }
+
+    async fn record(
+        &self,
+        target: &str,
+        downsampled: i64,
+        expired: i64,
+    ) -> anyhow::Result<RetentionRun> {
+        let ran_at = Utc::now();
+        sqlx::query("INSERT INTO retention_runs(ran_at, target, downsampled, expired) VALUES (?,?,?,?)")
+            .bind(ran_at)
+            .bind(target)
+            .bind(downsampled)
+            .bind(expired)
+            .execute(&self.pool)
+            .await?;
+        Ok(RetentionRun {
+            target: target.to_string(),
+            ran_at,
+            downsampled,
+            expired,
+        })
+    }
+
+    async fn table_stats(&self) -> anyhow::Result<Vec<TableStats>> {
+        let mut out = Vec::new();
+        for table in RetentionPolicy::TABLES {
+            // SQLite has no per-table size function without dbstat, so the
+            // payload text length stands in for the table size.
+            let row = sqlx::query(&format!(
+                "SELECT count(*) AS rows, coalesce(sum(length(payload)), 0) AS bytes FROM {table}"
+            ))
+            .fetch_one(&self.pool)
+            .await?;
+            let reclaimed: i64 = sqlx::query_scalar(
+                "SELECT coalesce(sum(downsampled + expired), 0)
+                 FROM retention_runs
+                 WHERE target = ?1 OR target LIKE ?1 || '/%'",
+            )
+            .bind(table)
+            .fetch_one(&self.pool)
+            .await?;
+            out.push(TableStats {
+                table: table.to_string(),
+                rows: row.get("rows"),
+                total_bytes: row.get("bytes"),
+                reclaimed_rows: reclaimed,
+            });
+        }
+        Ok(out)
+    }
+
+    async fn last_runs(&self) -> anyhow::Result<Vec<RetentionRun>> {
+        let rows = sqlx::query(
+            "SELECT target, ran_at, downsampled, expired
+             FROM retention_runs r
+             WHERE id = (SELECT max(id) FROM retention_runs WHERE target = r.target)
+             ORDER BY target",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| RetentionRun {
+                target: r.get("target"),
+                ran_at: r.get("ran_at"),
+                downsampled: r.get("downsampled"),
+                expired: r.get("expired"),
+            })
+            .collect())
+    }
+}
+
+fn push_scope(
+    qb: &mut QueryBuilder<'_, Sqlite>,
+    table: &str,
+    policy: &RetentionPolicy,
+    skip_sources: &[String],
+) {
+    if table == "space_cache" {
+        match &policy.source {
+            Some(src) => {
+                qb.push(" AND source = ").push_bind(src.clone());
+            }
+            None if !skip_sources.is_empty() => {
+                qb.push(" AND source NOT IN (");
+                let mut list = qb.separated(", ");
+                for src in skip_sources {
+                    list.push_bind(src.clone());
+                }
+                qb.push(")");
+            }
+            None => {}
+        }
+        qb.push(" AND id NOT IN (SELECT max(id) FROM space_cache GROUP BY source)");
+    } else {
+        qb.push(" AND id NOT IN (SELECT max(id) FROM iss_fetch_log GROUP BY norad_id)");
+    }
+}
+
+/// `pg_try_advisory_lock` has no SQLite counterpart, so locks are rows in
+/// `job_locks`. A lock older than `STALE_AFTER_SECS` is assumed to belong
+/// to a crashed process and may be taken over.
+#[derive(Clone)]
+pub struct SqliteTleRepo {
+    pool: SqlitePool,
+}
+
+impl SqliteTleRepo {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl TleRepo for SqliteTleRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS tle_sets(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                norad_id INTEGER NOT NULL,
+                name TEXT,
+                epoch TEXT NOT NULL,
+                line1 TEXT NOT NULL,
+                line2 TEXT NOT NULL,
+                fetched_at TEXT NOT NULL,
+                UNIQUE(norad_id, epoch)
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert(&self, sets: &[TleSet]) -> anyhow::Result<usize> {
+        let mut tx = self.pool.begin().await?;
+        let mut inserted = 0;
+        for s in sets {
+            inserted += sqlx::query(
+                "INSERT OR IGNORE INTO tle_sets(norad_id, name, epoch, line1, line2, fetched_at)
+                 VALUES (?,?,?,?,?,?)",
+            )
+            .bind(s.norad_id)
+            .bind(&s.name)
+            .bind(s.epoch)
+            .bind(&s.line1)
+            .bind(&s.line2)
+            .bind(s.fetched_at)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(inserted as usize)
+    }
+
+    async fn nearest(&self, norad_id: i64, at: DateTime<Utc>) -> anyhow::Result<Option<TleSet>> {
+        let rows = sqlx::query(
+            "SELECT * FROM (SELECT norad_id, name, epoch, line1, line2, fetched_at FROM tle_sets
+                            WHERE norad_id = ?1 AND epoch <= ?2 ORDER BY epoch DESC LIMIT 1)
+             UNION ALL
+             SELECT * FROM (SELECT norad_id, name, epoch, line1, line2, fetched_at FROM tle_sets
+                            WHERE norad_id = ?1 AND epoch > ?2 ORDER BY epoch LIMIT 1)",
+        )
+        .bind(norad_id)
+        .bind(at)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(closest_epoch(rows.iter().map(tle_set), at))
+    }
+
+    async fn history(&self, norad_id: i64, limit: i64) -> anyhow::Result<Vec<TleSet>> {
+        let rows = sqlx::query(
+            "SELECT norad_id, name, epoch, line1, line2, fetched_at FROM tle_sets
+             WHERE norad_id = ?
+             ORDER BY epoch DESC
+             LIMIT ?",
+        )
+        .bind(norad_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(tle_set).collect())
+    }
+}
+
+fn tle_set(r: &SqliteRow) -> TleSet {
+    TleSet {
+        norad_id: r.get("norad_id"),
+        name: r.get("name"),
+        epoch: r.get("epoch"),
+        line1: r.get("line1"),
+        line2: r.get("line2"),
+        fetched_at: r.get("fetched_at"),
+    }
+}
+
+#[derive(Clone)]
+pub struct SqliteEventRepo {
+    pool: SqlitePool,
+}
+
+impl SqliteEventRepo {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventRepo for SqliteEventRepo {
+    async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS iss_events(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                norad_id INTEGER NOT NULL,
+                fence TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                at TEXT NOT NULL,
+                latitude REAL NOT NULL,
+                longitude REAL NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS ix_iss_events_fence ON iss_events(norad_id, fence, id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert(&self, events: &[GeofenceEvent]) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for e in events {
+            sqlx::query(
+                "INSERT INTO iss_events(norad_id, fence, kind, at, latitude, longitude)
+                 VALUES (?,?,?,?,?,?)",
+            )
+            .bind(e.norad_id)
+            .bind(&e.fence)
+            .bind(e.kind.as_str())
+            .bind(e.at)
+            .bind(e.latitude)
+            .bind(e.longitude)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn inside(&self, norad_id: i64) -> anyhow::Result<Vec<String>> {
+        Ok(sqlx::query_scalar(
+            "SELECT fence FROM iss_events e
+             WHERE norad_id = ?1 AND kind = 'enter'
+               AND id = (SELECT max(id) FROM iss_events
+                         WHERE norad_id = ?1 AND fence = e.fence)",
+        )
+        .bind(norad_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn list(&self, q: &GeofenceEventQuery) -> anyhow::Result<Vec<GeofenceEvent>> {
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            "SELECT id, norad_id, fence, kind, at, latitude, longitude FROM iss_events WHERE 1",
+        );
+        if let Some(id) = q.norad_id {
+            qb.push(" AND norad_id = ").push_bind(id);
+        }
+        if let Some(fence) = &q.fence {
+            qb.push(" AND fence = ").push_bind(fence.clone());
+        }
+        if let Some(since) = q.since {
+            qb.push(" AND at >= ").push_bind(since);
+        }
+        qb.push(" ORDER BY id DESC LIMIT ").push_bind(q.limit);
+        let rows = qb.build().fetch_all(&self.pool).await?;
+        Ok(rows.iter().filter_map(geofence_event).collect())
+    }
+}
+
+fn geofence_event(r: &SqliteRow) -> Option<GeofenceEvent> {
+    Some(GeofenceEvent {
+        id: r.get("id"),
+        norad_id: r.get("norad_id"),
+        fence: r.get("fence"),
+        kind: GeofenceTransition::parse(r.get("kind"))?,
+        at: r.get("at"),
+        latitude: r.get("latitude"),
+        longitude: r.get("longitude"),
+    })
+}
+
+#[derive(Clone)]
+pub struct SqliteJobLocks {
+    pool: SqlitePool,
+}
+
+impl SqliteJobLocks {
+    const STALE_AFTER_SECS: i64 = 3_600;
+
+    pub async fn new(pool: SqlitePool) -> anyhow::Result<Self> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS job_locks(
+                key INTEGER PRIMARY KEY,
+                acquired_at TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl JobLocks for SqliteJobLocks {
+    async fn try_lock(&self, key: i64) -> bool {
+        let now = Utc::now();
+        let stale = now - chrono::Duration::seconds(Self::STALE_AFTER_SECS);
+        sqlx::query(
+            "INSERT INTO job_locks(key, acquired_at) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET acquired_at = excluded.acquired_at
+             WHERE job_locks.acquired_at < ?3",
+        )
+        .bind(key)
+        .bind(now)
+        .bind(stale)
+        .execute(&self.pool)
+        .await
+        .map(|r| r.rows_affected() == 1)
+        .unwrap_or(false)
+    }
+
+    async fn unlock(&self, key: i64) -> bool {
+        sqlx::query("DELETE FROM job_locks WHERE key = ?")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map(|r| r.rows_affected() == 1)
+            .unwrap_or(false)
+    }
+}
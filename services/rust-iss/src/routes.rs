@@ -1,29 +1,64 @@
 use axum::{
+    body::Body,
     extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
     routing::get,
-    Router,
+    Json, Router,
 };
 use serde::Deserialize;
-use sqlx::Row;
 
 use crate::{
-    domain::{Health, IssTrend, SpaceCacheItem},
-    error::{ApiEnvelope, ApiResult},
+    domain::{
+        Geofence, GeofenceEvent, GeofenceEventQuery, Health, IssPrediction, IssStats, IssTrend,
+        PassPrediction, PositionEstimate, OsdrEnrichRun, OsdrExportFormat, OsdrFilter,
+        OsdrListQuery, OsdrPage, OsdrSearchHit, OsdrSearchQuery, OsdrSort, OsdrSyncReport,
+        OsdrVersion, RetentionReport, RetentionRun, SatelliteStatus, SearchLang, SpaceCacheItem,
+        TleRefreshReport, TleSet, TrackFormat, TrackedSatellite, TrendSeries,
+    },
+    error::{ApiEnvelope, ApiError, ApiResult},
+    orbit::Observer,
+    services::decode_cursor,
+    solar::{night_ring, subsolar_point},
+    timeparse::TimeParser,
+    track::render,
     AppState,
 };
-use chrono::{Days, Utc};
+use chrono::{DateTime, Days, Utc};
 
 pub fn build_router(state: AppState) -> Router {
     Router::new()
         .route("/health", get(health))
         .route("/last", get(last_iss))
         .route("/fetch", get(trigger_iss))
+        .route("/satellites", get(satellites))
         .route("/iss/trend", get(iss_trend))
+        .route("/iss/position", get(iss_position))
+        .route("/iss/at", get(iss_at).post(iss_at_batch))
+        .route("/iss/predict", get(iss_predict))
+        .route("/iss/passes", get(iss_passes))
+        .route("/iss/events", get(iss_events))
+        .route("/iss/geofences", get(iss_geofences))
+        .route("/iss/sun", get(iss_sun))
+        .route("/iss/terminator", get(iss_terminator))
+        .route("/iss/track", get(iss_track))
+        .route("/iss/stats", get(iss_stats))
+        .route("/iss/tle", get(iss_tle))
+        .route("/iss/tle/refresh", get(iss_tle_refresh))
         .route("/osdr/sync", get(osdr_sync))
         .route("/osdr/list", get(osdr_list))
+        .route("/osdr/enrich", get(osdr_enrich))
+        .route("/osdr/changes", get(osdr_changes))
+        .route("/osdr/search", get(osdr_search))
+        .route("/osdr/export", get(osdr_export))
+        .route("/osdr/:dataset_id", get(osdr_detail))
+        .route("/osdr/:dataset_id/history", get(osdr_history))
         .route("/space/:src/latest", get(space_latest))
+        .route("/space/:src/history", get(space_history))
         .route("/space/refresh", get(space_refresh))
         .route("/space/summary", get(space_summary))
+        .route("/retention/status", get(retention_status))
+        .route("/retention/run", get(retention_run))
         // Convenience routes for PHP frontend
         .route("/space/apod", get(space_apod))
         .route("/space/neo", get(space_neo))
@@ -39,56 +74,594 @@ async fn health() -> ApiResult<Health> {
     }))
 }
 
-async fn last_iss(State(st): State<AppState>) -> ApiResult<serde_json::Value> {
-    let last = st.iss.last().await?;
+#[derive(Deserialize)]
+struct SatQuery {
+    sat: Option<String>,
+}
+
+/// The tracked satellite named by `?sat=`, a NORAD id or a case-insensitive
+/// name; the first one in `TRACKED_SATELLITES` when absent.
+fn tracked<'a>(st: &'a AppState, sat: Option<&str>) -> Result<&'a TrackedSatellite, ApiError> {
+    let sats = &st.cfg.satellites;
+    let Some(key) = sat.map(str::trim).filter(|s| !s.is_empty()) else {
+        return sats
+            .first()
+            .ok_or_else(|| ApiError::Invalid("no tracked satellites".to_string()));
+    };
+    let id = key.parse::<i64>().ok();
+    sats.iter()
+        .find(|s| Some(s.norad_id) == id || s.name.eq_ignore_ascii_case(key))
+        .ok_or_else(|| ApiError::Invalid(format!("satellite `{key}` is not tracked")))
+}
+
+async fn satellites(State(st): State<AppState>) -> ApiResult<Vec<SatelliteStatus>> {
+    Ok(ApiEnvelope::ok(st.iss.satellites(&st.cfg.satellites).await?))
+}
+
+async fn last_iss(
+    State(st): State<AppState>,
+    Query(q): Query<SatQuery>,
+) -> ApiResult<serde_json::Value> {
+    let sat = tracked(&st, q.sat.as_deref())?;
+    let last = st.iss.last(sat.norad_id).await?;
     let payload = last.map(|(id, at, src, json)| {
         serde_json::json!({"id": id, "fetched_at": at, "source_url": src, "payload": json })
     });
     Ok(ApiEnvelope::ok(payload.unwrap_or_else(|| serde_json::json!({"message":"no data"}))))
 }
 
-async fn trigger_iss(State(st): State<AppState>) -> ApiResult<serde_json::Value> {
-    st.iss.fetch_and_store().await?;
-    last_iss(State(st)).await
+async fn trigger_iss(
+    State(st): State<AppState>,
+    Query(q): Query<SatQuery>,
+) -> ApiResult<serde_json::Value> {
+    let sat = tracked(&st, q.sat.as_deref())?.clone();
+    st.iss.fetch_and_store(&sat).await?;
+    last_iss(State(st), Query(q)).await
 }
 
 #[derive(Deserialize)]
 struct TrendQuery {
     limit: Option<i64>,
+    sat: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    points: Option<usize>,
+    by: Option<String>,
 }
 
+/// Longest `/iss/trend` window; every position in it is loaded to be thinned.
+const TREND_MAX_DAYS: i64 = 31;
+
+/// The latest `limit` positions, or with `from`/`to` a time range
+/// downsampled to `points`.
 async fn iss_trend(
     State(st): State<AppState>,
     Query(q): Query<TrendQuery>,
 ) -> ApiResult<IssTrend> {
-    let limit = q
-        .limit
-        .unwrap_or_else(|| st.cfg.trend_limit_default)
-        .clamp(2, 1000);
-    let trend = st.iss.trend(limit).await?;
+    let sat = tracked(&st, q.sat.as_deref())?;
+    if q.from.is_none() && q.to.is_none() {
+        let limit = q
+            .limit
+            .unwrap_or(st.cfg.trend_limit_default)
+            .clamp(2, 1000);
+        return Ok(ApiEnvelope::ok(st.iss.trend(sat.norad_id, limit).await?));
+    }
+    let to = parse_bound(&st.cfg.time_parser, "to", q.to.as_deref())?.unwrap_or_else(Utc::now);
+    let from = parse_bound(&st.cfg.time_parser, "from", q.from.as_deref())?
+        .unwrap_or(to - chrono::Duration::days(1));
+    if to - from > chrono::Duration::days(TREND_MAX_DAYS) {
+        return Err(ApiError::Invalid(format!("at most {TREND_MAX_DAYS} days")));
+    }
+    let by = q.by.as_deref().unwrap_or("latitude");
+    let series = TrendSeries::parse(by).ok_or_else(|| ApiError::Invalid(format!("by: {by}")))?;
+    let points = q.points.unwrap_or(500).clamp(3, 5000);
+    let trend = st.iss.trend_range(sat.norad_id, from, to, points, series).await?;
     Ok(ApiEnvelope::ok(trend))
 }
 
-async fn osdr_sync(State(st): State<AppState>) -> ApiResult<serde_json::Value> {
-    let written = st.osdr.sync().await?;
-    Ok(ApiEnvelope::ok(serde_json::json!({ "written": written })))
+#[derive(Deserialize)]
+struct TrackQuery {
+    sat: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    format: Option<String>,
+    points: Option<usize>,
+}
+
+/// Longest `/iss/track` window.
+const TRACK_MAX_DAYS: i64 = 31;
+
+/// The stored track as a file for QGIS or Google Earth, split at the
+/// antimeridian and at gaps in the log.
+async fn iss_track(
+    State(st): State<AppState>,
+    Query(q): Query<TrackQuery>,
+) -> Result<Response, ApiError> {
+    let name = q.format.as_deref().unwrap_or("geojson");
+    let format =
+        TrackFormat::parse(name).ok_or_else(|| ApiError::Invalid(format!("format: {name}")))?;
+    let sat = tracked(&st, q.sat.as_deref())?;
+    let to = parse_bound(&st.cfg.time_parser, "to", q.to.as_deref())?.unwrap_or_else(Utc::now);
+    let from = parse_bound(&st.cfg.time_parser, "from", q.from.as_deref())?
+        .unwrap_or(to - chrono::Duration::days(1));
+    if to - from > chrono::Duration::days(TRACK_MAX_DAYS) {
+        return Err(ApiError::Invalid(format!("at most {TRACK_MAX_DAYS} days")));
+    }
+    let points = q.points.map(|n| n.clamp(3, 100_000));
+    let runs = st.iss.track(sat.norad_id, from, to, points).await?;
+    let body = render(format, &sat.name, sat.norad_id, &runs);
+    let disposition =
+        format!("attachment; filename=\"track-{}.{}\"", sat.norad_id, format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+struct StatsQuery {
+    sat: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// Longest `/iss/stats` window.
+const STATS_MAX_DAYS: i64 = 90;
+
+/// Period, daily distance, altitude decay and reboosts from the log; by
+/// default over the last week.
+async fn iss_stats(
+    State(st): State<AppState>,
+    Query(q): Query<StatsQuery>,
+) -> ApiResult<IssStats> {
+    let sat = tracked(&st, q.sat.as_deref())?;
+    let to = parse_bound(&st.cfg.time_parser, "to", q.to.as_deref())?.unwrap_or_else(Utc::now);
+    let from = parse_bound(&st.cfg.time_parser, "from", q.from.as_deref())?
+        .unwrap_or(to - chrono::Duration::days(7));
+    if to - from > chrono::Duration::days(STATS_MAX_DAYS) {
+        return Err(ApiError::Invalid(format!("at most {STATS_MAX_DAYS} days")));
+    }
+    Ok(ApiEnvelope::ok(st.iss.stats(sat.norad_id, from, to).await?))
+}
+
+#[derive(Deserialize)]
+struct PositionQuery {
+    at: Option<String>,
+    sat: Option<String>,
+}
+
+/// Position propagated from the stored element sets, available between polls
+/// and while wheretheiss.at is down.
+async fn iss_position(
+    State(st): State<AppState>,
+    Query(q): Query<PositionQuery>,
+) -> ApiResult<serde_json::Value> {
+    let at = parse_bound(&st.cfg.time_parser, "at", q.at.as_deref())?.unwrap_or_else(Utc::now);
+    let sat = tracked(&st, q.sat.as_deref())?;
+    let pos = st.orbit.position(sat.norad_id, at).await?;
+    Ok(ApiEnvelope::ok(match pos {
+        Some(p) => serde_json::to_value(p).map_err(anyhow::Error::from)?,
+        None => serde_json::json!({"message": "no orbital elements"}),
+    }))
+}
+
+#[derive(Deserialize)]
+struct AtQuery {
+    ts: Option<String>,
+    sat: Option<String>,
+}
+
+/// Timestamps accepted by one `/iss/at` batch.
+const AT_BATCH_MAX: usize = 1000;
+
+/// Position at an exact instant, for correlating telemetry and events.
+async fn iss_at(
+    State(st): State<AppState>,
+    Query(q): Query<AtQuery>,
+) -> ApiResult<serde_json::Value> {
+    let Some(ts) = q.ts.as_deref() else {
+        return Err(ApiError::Invalid("ts is required".to_string()));
+    };
+    let at = st
+        .cfg
+        .time_parser
+        .parse_str(ts)
+        .map_err(|e| ApiError::Invalid(format!("ts: {e}")))?;
+    let sat = tracked(&st, q.sat.as_deref())?;
+    Ok(ApiEnvelope::ok(match st.iss.position_at(sat.norad_id, at).await? {
+        Some(p) => serde_json::to_value(p).map_err(anyhow::Error::from)?,
+        None => serde_json::json!({"message": "no position data"}),
+    }))
+}
+
+#[derive(Deserialize)]
+struct AtBatch {
+    ts: Vec<serde_json::Value>,
+    sat: Option<String>,
+}
+
+/// `/iss/at` for many timestamps: one entry per timestamp, in order, null
+/// where there is no position data.
+async fn iss_at_batch(
+    State(st): State<AppState>,
+    Json(body): Json<AtBatch>,
+) -> ApiResult<Vec<Option<PositionEstimate>>> {
+    if body.ts.len() > AT_BATCH_MAX {
+        return Err(ApiError::Invalid(format!("at most {AT_BATCH_MAX} timestamps")));
+    }
+    let sat = tracked(&st, body.sat.as_deref())?;
+    let ats = body
+        .ts
+        .iter()
+        .enumerate()
+        .map(|(i, ts)| {
+            st.cfg
+                .time_parser
+                .parse_value(ts)
+                .map_err(|e| ApiError::Invalid(format!("ts[{i}]: {e}")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ApiEnvelope::ok(st.iss.positions_at(sat.norad_id, &ats).await?))
+}
+
+#[derive(Deserialize)]
+struct PredictQuery {
+    minutes: Option<i64>,
+    step: Option<i64>,
+    sat: Option<String>,
+}
+
+/// The ground track from now on, for the map to draw the next orbits.
+async fn iss_predict(
+    State(st): State<AppState>,
+    Query(q): Query<PredictQuery>,
+) -> ApiResult<IssPrediction> {
+    let minutes = q.minutes.unwrap_or(90).clamp(1, 1440);
+    let step = q.step.unwrap_or(60).clamp(10, 3600);
+    let sat = tracked(&st, q.sat.as_deref())?;
+    Ok(ApiEnvelope::ok(st.iss.predict(sat.norad_id, Utc::now(), minutes, step).await?))
+}
+
+#[derive(Deserialize)]
+struct PassesQuery {
+    lat: Option<f64>,
+    lon: Option<f64>,
+    /// metres
+    alt: Option<f64>,
+    days: Option<i64>,
+    sat: Option<String>,
+    visible: Option<bool>,
+}
+
+/// Upcoming passes over an observer; `?visible=true` keeps only the ones
+/// that can be seen by eye.
+async fn iss_passes(
+    State(st): State<AppState>,
+    Query(q): Query<PassesQuery>,
+) -> ApiResult<PassPrediction> {
+    let (Some(lat), Some(lon)) = (q.lat, q.lon) else {
+        return Err(ApiError::Invalid("lat and lon are required".to_string()));
+    };
+    let alt = q.alt.unwrap_or(0.0);
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Err(ApiError::Invalid(format!("bad observer location {lat},{lon}")));
+    }
+    if !(-500.0..=9000.0).contains(&alt) {
+        return Err(ApiError::Invalid(format!("bad observer altitude {alt} m")));
+    }
+    let observer = Observer { latitude: lat, longitude: lon, altitude: alt / 1000.0 };
+    let days = q.days.unwrap_or(3).clamp(1, 10);
+    let sat = tracked(&st, q.sat.as_deref())?;
+    let from = Utc::now();
+    let mut out = st
+        .orbit
+        .passes(sat.norad_id, observer, from, from + chrono::Duration::days(days))
+        .await?;
+    if q.visible.unwrap_or(false) {
+        out.passes.retain(|p| p.visible);
+    }
+    Ok(ApiEnvelope::ok(out))
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    sat: Option<String>,
+    fence: Option<String>,
+    since: Option<String>,
+    limit: Option<i64>,
+}
+
+/// Geofence entries and exits, newest first; all satellites unless `?sat=`.
+async fn iss_events(
+    State(st): State<AppState>,
+    Query(q): Query<EventsQuery>,
+) -> ApiResult<Vec<GeofenceEvent>> {
+    let norad_id = match q.sat.as_deref() {
+        Some(sat) => Some(tracked(&st, Some(sat))?.norad_id),
+        None => None,
+    };
+    let query = GeofenceEventQuery {
+        norad_id,
+        fence: q.fence.filter(|f| !f.is_empty()),
+        since: parse_bound(&st.cfg.time_parser, "since", q.since.as_deref())?,
+        limit: q.limit.unwrap_or(100).clamp(1, 1000),
+    };
+    Ok(ApiEnvelope::ok(st.iss.events(&query).await?))
+}
+
+async fn iss_geofences(State(st): State<AppState>) -> ApiResult<Vec<Geofence>> {
+    Ok(ApiEnvelope::ok(st.iss.geofences().to_vec()))
+}
+
+/// Where the Sun stands overhead and whether the satellite is in the
+/// Earth's shadow; `illumination` is null before any element set is stored.
+async fn iss_sun(
+    State(st): State<AppState>,
+    Query(q): Query<PositionQuery>,
+) -> ApiResult<serde_json::Value> {
+    let at = parse_bound(&st.cfg.time_parser, "at", q.at.as_deref())?.unwrap_or_else(Utc::now);
+    let sat = tracked(&st, q.sat.as_deref())?;
+    let pos = st.orbit.position(sat.norad_id, at).await?;
+    let (latitude, longitude) = subsolar_point(at);
+    Ok(ApiEnvelope::ok(serde_json::json!({
+        "at": at,
+        "subsolar": {"latitude": latitude, "longitude": longitude},
+        "norad_id": sat.norad_id,
+        "illumination": pos.map(|p| p.illumination),
+    })))
+}
+
+#[derive(Deserialize)]
+struct TerminatorQuery {
+    at: Option<String>,
+}
+
+/// The night side as a GeoJSON Feature for the map overlay; plain GeoJSON
+/// rather than the envelope so map libraries can load the URL directly.
+async fn iss_terminator(
+    State(st): State<AppState>,
+    Query(q): Query<TerminatorQuery>,
+) -> Result<Response, ApiError> {
+    let at = parse_bound(&st.cfg.time_parser, "at", q.at.as_deref())?.unwrap_or_else(Utc::now);
+    let (latitude, longitude) = subsolar_point(at);
+    let feature = serde_json::json!({
+        "type": "Feature",
+        "geometry": {"type": "Polygon", "coordinates": [night_ring(at, 1.0)]},
+        "properties": {
+            "at": at,
+            "subsolar": {"latitude": latitude, "longitude": longitude},
+        },
+    });
+    Ok(([(header::CONTENT_TYPE, "application/geo+json")], feature.to_string()).into_response())
+}
+
+async fn iss_tle(
+    State(st): State<AppState>,
+    Query(q): Query<TrendQuery>,
+) -> ApiResult<Vec<TleSet>> {
+    let limit = q.limit.unwrap_or(1).clamp(1, 200);
+    let sat = tracked(&st, q.sat.as_deref())?;
+    Ok(ApiEnvelope::ok(st.orbit.history(sat.norad_id, limit).await?))
+}
+
+async fn iss_tle_refresh(State(st): State<AppState>) -> ApiResult<TleRefreshReport> {
+    Ok(ApiEnvelope::ok(st.orbit.refresh().await?))
+}
+
+#[derive(Debug, Deserialize)]
+struct SyncQuery {
+    full: Option<bool>,
+}
+
+async fn osdr_sync(
+    State(st): State<AppState>,
+    Query(q): Query<SyncQuery>,
+) -> ApiResult<OsdrSyncReport> {
+    Ok(ApiEnvelope::ok(st.osdr.sync(q.full.unwrap_or(false)).await?))
 }
 
 #[derive(Deserialize)]
 struct OsdrQuery {
     limit: Option<i64>,
+    cursor: Option<String>,
+    status: Option<String>,
+    updated_from: Option<String>,
+    updated_to: Option<String>,
+    prefix: Option<String>,
+    include_removed: Option<bool>,
+    sort: Option<OsdrSort>,
+    order: Option<String>,
 }
 
 async fn osdr_list(
     State(st): State<AppState>,
     Query(q): Query<OsdrQuery>,
-) -> ApiResult<serde_json::Value> {
+) -> ApiResult<OsdrPage> {
     let limit = q
         .limit
         .unwrap_or(st.cfg.osdr_list_limit)
         .clamp(1, 500);
-    let items = st.osdr.list(limit).await?;
-    Ok(ApiEnvelope::ok(serde_json::json!({ "items": items })))
+    let desc = match q.order.as_deref() {
+        None | Some("desc") => true,
+        Some("asc") => false,
+        Some(other) => return Err(ApiError::Invalid(format!("order: {other}"))),
+    };
+    let query = OsdrListQuery {
+        filter: osdr_filter(
+            &st.cfg.time_parser,
+            q.status,
+            q.updated_from,
+            q.updated_to,
+            q.prefix,
+            q.include_removed,
+        )?,
+        sort: q.sort.unwrap_or_default(),
+        desc,
+        limit,
+        after: q.cursor.as_deref().map(decode_cursor).transpose()?,
+    };
+    Ok(ApiEnvelope::ok(st.osdr.list(query).await?))
+}
+
+fn osdr_filter(
+    parser: &TimeParser,
+    status: Option<String>,
+    updated_from: Option<String>,
+    updated_to: Option<String>,
+    prefix: Option<String>,
+    include_removed: Option<bool>,
+) -> Result<OsdrFilter, ApiError> {
+    Ok(OsdrFilter {
+        status: status.filter(|s| !s.is_empty()),
+        updated_from: parse_bound(parser, "updated_from", updated_from.as_deref())?,
+        updated_to: parse_bound(parser, "updated_to", updated_to.as_deref())?,
+        dataset_id_prefix: prefix.filter(|s| !s.is_empty()),
+        include_removed: include_removed.unwrap_or(false),
+    })
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: Option<String>,
+    lang: Option<String>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    status: Option<String>,
+    updated_from: Option<String>,
+    updated_to: Option<String>,
+    prefix: Option<String>,
+    include_removed: Option<bool>,
+}
+
+async fn osdr_search(
+    State(st): State<AppState>,
+    Query(q): Query<SearchQuery>,
+) -> ApiResult<OsdrPage<OsdrSearchHit>> {
+    let text = q.q.unwrap_or_default();
+    let lang = match q.lang.as_deref() {
+        None | Some("auto") => SearchLang::detect(&text),
+        Some("en") => SearchLang::English,
+        Some("ru") => SearchLang::Russian,
+        Some(other) => return Err(ApiError::Invalid(format!("lang: {other}"))),
+    };
+    let query = OsdrSearchQuery {
+        text,
+        lang,
+        filter: osdr_filter(
+            &st.cfg.time_parser,
+            q.status,
+            q.updated_from,
+            q.updated_to,
+            q.prefix,
+            q.include_removed,
+        )?,
+        limit: q
+            .limit
+            .unwrap_or(st.cfg.osdr_list_limit)
+            .clamp(1, 500),
+        after: q.cursor.as_deref().map(decode_cursor).transpose()?,
+    };
+    Ok(ApiEnvelope::ok(st.osdr.search(query).await?))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    format: Option<String>,
+    status: Option<String>,
+    updated_from: Option<String>,
+    updated_to: Option<String>,
+    prefix: Option<String>,
+    include_removed: Option<bool>,
+}
+
+async fn osdr_export(
+    State(st): State<AppState>,
+    Query(q): Query<ExportQuery>,
+) -> Result<Response, ApiError> {
+    let name = q.format.as_deref().unwrap_or("csv");
+    let format = OsdrExportFormat::parse(name)
+        .ok_or_else(|| ApiError::Invalid(format!("format: {name}")))?;
+    let filter = osdr_filter(
+        &st.cfg.time_parser,
+        q.status,
+        q.updated_from,
+        q.updated_to,
+        q.prefix,
+        q.include_removed,
+    )?;
+    let body = Body::from_stream(st.osdr.export(filter, format).await?);
+    let disposition = format!("attachment; filename=\"osdr.{}\"", format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+async fn osdr_enrich(State(st): State<AppState>) -> ApiResult<OsdrEnrichRun> {
+    Ok(ApiEnvelope::ok(st.osdr.enrich(st.cfg.osdr_enrich_batch).await?))
+}
+
+async fn osdr_detail(
+    Path(dataset_id): Path<String>,
+    State(st): State<AppState>,
+) -> ApiResult<serde_json::Value> {
+    let Some(item) = st.osdr.get(&dataset_id).await? else {
+        return Ok(ApiEnvelope::ok(
+            serde_json::json!({"dataset_id": dataset_id, "message": "not found"}),
+        ));
+    };
+    let detail = st.osdr.detail_or_enrich(&dataset_id, item.updated_at).await?;
+    let stale = detail
+        .as_ref()
+        .is_some_and(|d| d.source_updated_at != item.updated_at);
+    Ok(ApiEnvelope::ok(serde_json::json!({
+        "item": item,
+        "detail": detail,
+        "detail_stale": stale
+    })))
+}
+
+async fn osdr_history(
+    Path(dataset_id): Path<String>,
+    Query(q): Query<HistoryQuery>,
+    State(st): State<AppState>,
+) -> ApiResult<Vec<OsdrVersion>> {
+    let limit = q.limit.unwrap_or(20).clamp(1, 200);
+    Ok(ApiEnvelope::ok(st.osdr.history(&dataset_id, limit).await?))
+}
+
+#[derive(Deserialize)]
+struct ChangesQuery {
+    since: Option<String>,
+    limit: Option<i64>,
+}
+
+async fn osdr_changes(
+    Query(q): Query<ChangesQuery>,
+    State(st): State<AppState>,
+) -> ApiResult<Vec<OsdrVersion>> {
+    let since = parse_bound(&st.cfg.time_parser, "since", q.since.as_deref())?;
+    let limit = q.limit.unwrap_or(20).clamp(1, 200);
+    Ok(ApiEnvelope::ok(st.osdr.recent_changes(since, limit).await?))
+}
+
+/// Reads the timestamp parameter `name` with the configured parser, so that
+/// epochs and `TIME_FORMATS` are accepted as in upstream data.
+fn parse_bound(
+    parser: &TimeParser,
+    name: &str,
+    s: Option<&str>,
+) -> Result<Option<DateTime<Utc>>, ApiError> {
+    s.map(|s| parser.parse_str(s).map_err(|e| ApiError::Invalid(format!("{name}: {e}"))))
+        .transpose()
 }
 
 async fn space_latest(
@@ -97,11 +670,33 @@ async fn space_latest(
 ) -> ApiResult<serde_json::Value> {
     let item = st.space.latest(&src).await?;
     let payload = item
-        .map(|i| serde_json::json!({ "source": i.source, "fetched_at": i.fetched_at, "payload": i.payload }))
+        .map(|i| {
+            serde_json::json!({
+                "source": i.source,
+                "fetched_at": i.fetched_at,
+                "last_seen_at": i.last_seen_at,
+                "check_count": i.check_count,
+                "payload": i.payload
+            })
+        })
         .unwrap_or_else(|| serde_json::json!({"source": src, "message": "no data"}));
     Ok(ApiEnvelope::ok(payload))
 }
 
+#[derive(Deserialize)]
+struct HistoryQuery {
+    limit: Option<i64>,
+}
+
+async fn space_history(
+    Path(src): Path<String>,
+    Query(q): Query<HistoryQuery>,
+    State(st): State<AppState>,
+) -> ApiResult<Vec<SpaceCacheItem>> {
+    let limit = q.limit.unwrap_or(20).clamp(1, 200);
+    Ok(ApiEnvelope::ok(st.space.history(&src, limit).await?))
+}
+
 #[derive(Deserialize)]
 struct RefreshQuery {
     src: Option<String>,
@@ -155,14 +750,13 @@ async fn space_summary(State(st): State<AppState>) -> ApiResult<serde_json::Valu
     let cme = st.space.latest("cme").await?;
     let spacex = st.space.latest("spacex").await?;
 
-    let iss_last = st.iss.last().await?;
+    let iss_last = match st.cfg.satellites.first() {
+        Some(sat) => st.iss.last(sat.norad_id).await?,
+        None => None,
+    };
     let iss_last = iss_last.map(|(_, at, _, payload)| serde_json::json!({"at": at, "payload": payload}));
 
-    let osdr_count: i64 = sqlx::query("SELECT count(*) AS c FROM osdr_items")
-        .fetch_one(&st.pool)
-        .await
-        .map(|r| r.get::<i64, _>("c"))
-        .unwrap_or(0);
+    let osdr_count = st.osdr.count(&OsdrFilter::default()).await.unwrap_or(0);
 
     Ok(ApiEnvelope::ok(serde_json::json!({
         "apod": apod.map(item_to_json),
@@ -175,8 +769,16 @@ async fn space_summary(State(st): State<AppState>) -> ApiResult<serde_json::Valu
     })))
 }
 
+async fn retention_status(State(st): State<AppState>) -> ApiResult<RetentionReport> {
+    Ok(ApiEnvelope::ok(st.retention.report().await?))
+}
+
+async fn retention_run(State(st): State<AppState>) -> ApiResult<Vec<RetentionRun>> {
+    Ok(ApiEnvelope::ok(st.retention.run().await?))
+}
+
 fn item_to_json(item: SpaceCacheItem) -> serde_json::Value {
-    serde_json::json!({"at": item.fetched_at, "payload": item.payload})
+    serde_json::json!({"at": item.fetched_at, "last_seen_at": item.last_seen_at, "payload": item.payload})
 }
 
 fn last_days(n: i64) -> (String, String) {
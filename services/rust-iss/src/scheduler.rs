@@ -1,21 +1,34 @@
 use std::time::Duration;
 
-use tokio::time::interval;
+use tokio::time::{interval_at, Instant};
 use tracing::info;
 
 use crate::AppState;
 
 pub fn spawn_jobs(state: AppState) {
-    spawn_job(
-        "iss",
-        state.cfg.every_iss,
-        10_001,
-        state.clone(),
-        |st| async move {
-            st.iss.fetch_and_store().await?;
-            Ok(())
-        },
-    );
+    // one position job per tracked satellite, each with its own lock
+    for (i, sat) in state.cfg.satellites.iter().enumerate() {
+        let name = if i == 0 { "iss".to_string() } else { format!("iss-{}", sat.norad_id) };
+        spawn_job(
+            name,
+            sat.every_seconds,
+            satellite_lock_id(sat.norad_id),
+            state.clone(),
+            move |st| async move {
+                let sat = st.cfg.satellites[i].clone();
+                for e in st.iss.fetch_and_store(&sat).await? {
+                    info!(
+                        job = "iss",
+                        norad_id = e.norad_id,
+                        fence = %e.fence,
+                        kind = e.kind.as_str(),
+                        "geofence crossed"
+                    );
+                }
+                Ok(())
+            },
+        );
+    }
 
     spawn_job(
         "osdr",
@@ -23,11 +36,38 @@ pub fn spawn_jobs(state: AppState) {
         10_002,
         state.clone(),
         |st| async move {
-            let _ = st.osdr.sync().await;
+            let report = st.osdr.sync(false).await?;
+            if report.failed > 0 {
+                tracing::warn!(
+                    job = "osdr",
+                    failed = report.failed,
+                    written = report.inserted + report.updated + report.restored,
+                    "osdr sync finished with failures"
+                );
+            }
             Ok(())
         },
     );
 
+    // full reconciliation on a clock of its own, first when the last one
+    // is due; a restart does not force one
+    let st = state.clone();
+    tokio::spawn(async move {
+        let delay = st.osdr.full_sync_due_in().await.unwrap_or_default();
+        let every = st.cfg.every_osdr_full;
+        spawn_job_after("osdr-full", delay, every, 10_010, st, |st| async move {
+            let report = st.osdr.sync(true).await?;
+            info!(
+                job = "osdr-full",
+                failed = report.failed,
+                written = report.inserted + report.updated + report.restored,
+                removed = report.removed.len(),
+                "osdr catalog reconciled"
+            );
+            Ok(())
+        });
+    });
+
     spawn_job(
         "apod",
         state.cfg.every_apod,
@@ -78,53 +118,119 @@ pub fn spawn_jobs(state: AppState) {
         "spacex",
         state.cfg.every_spacex,
         10_006,
-        state,
+        state.clone(),
         |st| async move {
             let _ = st.space.spacex().await;
             Ok(())
         },
     );
+
+    spawn_job(
+        "retention",
+        state.cfg.every_retention,
+        10_007,
+        state.clone(),
+        |st| async move {
+            for run in st.retention.run().await? {
+                info!(
+                    job = "retention",
+                    target = %run.target,
+                    downsampled = run.downsampled,
+                    expired = run.expired,
+                    "retention applied"
+                );
+            }
+            Ok(())
+        },
+    );
+
+    spawn_job(
+        "tle",
+        state.cfg.every_tle,
+        10_009,
+        state.clone(),
+        |st| async move {
+            let report = st.orbit.refresh().await?;
+            info!(
+                job = "tle",
+                parsed = report.parsed,
+                inserted = report.inserted,
+                rejected = report.errors.len(),
+                "element sets refreshed"
+            );
+            Ok(())
+        },
+    );
+
+    spawn_job(
+        "osdr-enrich",
+        state.cfg.every_osdr_enrich,
+        10_008,
+        state,
+        |st| async move {
+            let run = st.osdr.enrich(st.cfg.osdr_enrich_batch).await?;
+            if run.candidates > 0 {
+                info!(
+                    job = "osdr-enrich",
+                    enriched = run.enriched,
+                    failed = run.failed,
+                    "osdr details enriched"
+                );
+            }
+            Ok(())
+        },
+    );
 }
 
-fn spawn_job<F, Fut>(name: &'static str, seconds: u64, lock_id: i64, state: AppState, f: F)
-where
+/// Satellite jobs lock above the fixed ids of the other jobs.
+fn satellite_lock_id(norad_id: i64) -> i64 {
+    1_000_000_000 + norad_id
+}
+
+fn spawn_job<F, Fut>(
+    name: impl Into<String>,
+    seconds: u64,
+    lock_id: i64,
+    state: AppState,
+    f: F,
+) where
     F: Fn(AppState) -> Fut + Send + 'static + Copy,
     Fut: std::future::Future<Output = Result<(), crate::error::ApiError>> + Send + 'static,
 {
+    spawn_job_after(name, Duration::ZERO, seconds, lock_id, state, f);
+}
+
+/// [`spawn_job`] with the first run `delay` from now.
+fn spawn_job_after<F, Fut>(
+    name: impl Into<String>,
+    delay: Duration,
+    seconds: u64,
+    lock_id: i64,
+    state: AppState,
+    f: F,
+) where
+    F: Fn(AppState) -> Fut + Send + 'static + Copy,
+    Fut: std::future::Future<Output = Result<(), crate::error::ApiError>> + Send + 'static,
+{
+    let name = name.into();
     tokio::spawn(async move {
-        let mut ticker = interval(Duration::from_secs(seconds));
+        let mut ticker = interval_at(Instant::now() + delay, Duration::from_secs(seconds));
         loop {
             ticker.tick().await;
-            if !try_lock(&state.pool, lock_id).await {
+            if !state.locks.try_lock(lock_id).await {
                 continue;
             }
             let res = f(state.clone()).await;
             if let Err(e) = res {
-                tracing::error!(job = name, error = ?e, "job failed");
+                tracing::error!(job = %name, error = ?e, "job failed");
             } else {
-                info!(job = name, "job done");
+                info!(job = %name, "job done");
             }
-            let _ = unlock(&state.pool, lock_id).await;
+            let _ = state.locks.unlock(lock_id).await;
         }
     });
 }
 
-async fn try_lock(pool: &sqlx::PgPool, key: i64) -> bool {
-    sqlx::query_scalar::<_, bool>("SELECT pg_try_advisory_lock($1)")
-        .bind(key)
-        .fetch_one(pool)
-        .await
-        .unwrap_or(false)
-}
-
-async fn unlock(pool: &sqlx::PgPool, key: i64) -> bool {
-    sqlx::query_scalar::<_, bool>("SELECT pg_advisory_unlock($1)")
-        .bind(key)
-        .fetch_one(pool)
-        .await
-        .unwrap_or(false)
-}
-
 fn last_days(n: i64) -> (String, String) {
     let to = chrono::Utc::now().date_naive();
     let from = to - chrono::Days::new(n as u64);
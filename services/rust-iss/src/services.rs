@@ -1,106 +1,1385 @@
-use crate::clients::UpstreamClients;
+use crate::clients::{path_segment, UpstreamClients};
 use crate::config::AppConfig;
-use crate::domain::{IssTrend, OsdrUpsert, SpaceCacheItem};
+use crate::domain::{
+    EstimateMethod, Geofence, GeofenceEvent, GeofenceEventQuery, Illumination, IssPoint,
+    IssPrediction, IssStats, IssTrend, OrbitPosition, OsdrAssay, OsdrCursor, OsdrDetail,
+    OsdrEnrichFailure, OsdrEnrichRun, OsdrExportFormat, OsdrFieldMap, OsdrFile, OsdrFilter,
+    OsdrItem, OsdrListQuery, OsdrPage, OsdrSearchCursor, OsdrSearchHit, OsdrSearchQuery, OsdrSort,
+    OsdrSyncMode, OsdrSyncReport, OsdrUpsert, OsdrVersion, MagnitudeClass, PassPoint,
+    PassPrediction, PositionEstimate, PredictionModel, RetentionPolicy, RetentionReport,
+    RetentionRun, SatelliteStatus, SatellitePass, SpaceCacheItem, TleRefreshReport, TleSet,
+    TrackMode, TrackPoint, TrackedSatellite, TrendSeries, TrendWindow,
+};
 use crate::error::ApiError;
-use crate::repo::{CacheRepo, IssRepo, OsdrRepo};
-use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use crate::orbit::{
+    dot, geodetic, magnitude, parse_tle_text, teme_from_geodetic, CircularOrbit, Observer,
+    OrbitError, Sgp4, Tle, ISS_NORAD_ID,
+};
+use crate::solar::{illumination, sun_position};
+use crate::geofence;
+use crate::stats;
+use crate::track::{split_track, TrackVertex};
+use crate::repo::{iss_point, CacheRepo, EventRepo, IssRepo, OsdrRepo, RetentionRepo, TleRepo};
+use crate::timeparse::{TimeError, TimeParser};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
+use futures_util::{stream, Stream};
 
 #[derive(Clone)]
 pub struct IssService {
-    repo: IssRepo,
+    repo: Arc<dyn IssRepo>,
+    events: Arc<dyn EventRepo>,
     clients: UpstreamClients,
-    cfg: AppConfig,
+    orbit: Arc<OrbitService>,
+    fences: Vec<Geofence>,
 }
 
 impl IssService {
-    pub fn new(repo: IssRepo, clients: UpstreamClients, cfg: AppConfig) -> Self {
-        Self { repo, clients, cfg }
+    pub fn new(
+        repo: Arc<dyn IssRepo>,
+        events: Arc<dyn EventRepo>,
+        clients: UpstreamClients,
+        orbit: Arc<OrbitService>,
+        fences: Vec<Geofence>,
+    ) -> Self {
+        Self { repo, events, clients, orbit, fences }
     }
 
-    pub async fn fetch_and_store(&self) -> Result<(), ApiError> {
-        let payload = self.clients.fetch_iss().await?;
-        self.repo
-            .insert_log(&self.cfg.where_iss_url, &payload)
-            .await?;
-        Ok(())
+    /// Records the current position of `sat`: fetched from its poll URL, or
+    /// propagated from the stored element sets in `propagate` mode. Returns
+    /// the geofence entries and exits the new position makes, timed like the
+    /// logged row.
+    pub async fn fetch_and_store(
+        &self,
+        sat: &TrackedSatellite,
+    ) -> Result<Vec<GeofenceEvent>, ApiError> {
+        let (source_url, payload) = match (&sat.mode, &sat.poll_url) {
+            (TrackMode::Poll, Some(url)) => (url.clone(), self.clients.fetch_position(url).await?),
+            _ => {
+                let pos = self
+                    .orbit
+                    .position(sat.norad_id, Utc::now())
+                    .await?
+                    .ok_or_else(|| {
+                        ApiError::Invalid(format!("norad {}: no orbital elements", sat.norad_id))
+                    })?;
+                ("sgp4".to_string(), propagated_payload(sat, &pos))
+            }
+        };
+        let fetched_at = self.repo.insert_log(sat.norad_id, &source_url, &payload).await?;
+        self.check_geofences(sat.norad_id, &iss_point(fetched_at, &payload)).await
     }
 
-    pub async fn last(&self) -> Result<Option<(i64, DateTime<Utc>, String, Value)>, ApiError> {
-        Ok(self.repo.last().await?)
+    /// Compares `point` with the side of every fence the satellite was last
+    /// seen on and stores the crossings.
+    async fn check_geofences(
+        &self,
+        norad_id: i64,
+        point: &IssPoint,
+    ) -> Result<Vec<GeofenceEvent>, ApiError> {
+        let (Some(lat), Some(lon)) = (point.latitude, point.longitude) else {
+            return Ok(Vec::new());
+        };
+        if self.fences.is_empty() {
+            return Ok(Vec::new());
+        }
+        let inside = self.events.inside(norad_id).await?;
+        let events: Vec<GeofenceEvent> = geofence::transitions(&self.fences, &inside, lat, lon)
+            .into_iter()
+            .map(|(fence, kind)| GeofenceEvent {
+                id: 0,
+                norad_id,
+                fence: fence.name.clone(),
+                kind,
+                at: point.at,
+                latitude: lat,
+                longitude: lon,
+            })
+            .collect();
+        self.events.insert(&events).await?;
+        Ok(events)
     }
 
-    pub async fn trend(&self, limit: i64) -> Result<IssTrend, ApiError> {
-        let points = self.repo.trend(limit).await?;
-        if points.len() < 2 {
-            return Ok(IssTrend {
-                movement: false,
-                delta_km: 0.0,
-                dt_sec: 0.0,
-                points,
-            });
+    pub async fn events(&self, q: &GeofenceEventQuery) -> Result<Vec<GeofenceEvent>, ApiError> {
+        Ok(self.events.list(q).await?)
+    }
+
+    pub fn geofences(&self) -> &[Geofence] {
+        &self.fences
+    }
+
+    pub async fn last(
+        &self,
+        norad_id: i64,
+    ) -> Result<Option<(i64, DateTime<Utc>, String, Value)>, ApiError> {
+        Ok(self.repo.last(norad_id).await?)
+    }
+
+    /// Every tracked satellite with the time of its latest recorded position
+    /// and the epoch of the element set it would be propagated from.
+    pub async fn satellites(
+        &self,
+        tracked: &[TrackedSatellite],
+    ) -> Result<Vec<SatelliteStatus>, ApiError> {
+        let now = Utc::now();
+        let mut out = Vec::with_capacity(tracked.len());
+        for sat in tracked {
+            let last_at = self.repo.last(sat.norad_id).await?.map(|(_, at, _, _)| at);
+            let tle_epoch = self.orbit.nearest(sat.norad_id, now).await?.map(|s| s.epoch);
+            out.push(SatelliteStatus { satellite: sat.clone(), last_at, tle_epoch });
         }
-        let mut delta_km = 0.0;
-        let mut movement = false;
-        for win in points.windows(2) {
-            if let [a, b] = win {
-                if let (Some(lat1), Some(lon1), Some(lat2), Some(lon2)) =
-                    (a.latitude, a.longitude, b.latitude, b.longitude)
-                {
-                    delta_km += haversine_km(lat1, lon1, lat2, lon2);
-                    if delta_km > 0.1 {
-                        movement = true;
-                    }
+        Ok(out)
+    }
+
+    /// Positions of `norad_id` every `step` seconds over the next `minutes`,
+    /// from `from` on. Propagates the element set nearest to `from`; without
+    /// one, follows a circular orbit fitted to the latest logged positions.
+    pub async fn predict(
+        &self,
+        norad_id: i64,
+        from: DateTime<Utc>,
+        minutes: i64,
+        step: i64,
+    ) -> Result<IssPrediction, ApiError> {
+        let times: Vec<DateTime<Utc>> = (0..=minutes * 60)
+            .step_by(step.max(1) as usize)
+            .map(|s| from + chrono::Duration::seconds(s))
+            .collect();
+        let (model, tle_epoch, positions) = match self.orbit.nearest(norad_id, from).await? {
+            Some(set) => {
+                let sgp4 = propagator(&set)?;
+                let positions = times
+                    .iter()
+                    .map(|t| sgp4.at(*t).map(|s| s.position))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| ApiError::Invalid(format!("norad {norad_id}: {e}")))?;
+                (PredictionModel::Sgp4, Some(set.epoch), positions)
+            }
+            None => {
+                let points = self.repo.trend(norad_id, FIT_POINTS).await?;
+                let orbit = fit_orbit(&points).ok_or_else(|| {
+                    ApiError::Invalid(format!(
+                        "norad {norad_id}: no orbital elements and too few recent positions"
+                    ))
+                })?;
+                let end = times.last().copied().unwrap_or(from);
+                if orbit.revolutions(from).max(orbit.revolutions(end)) > FIT_MAX_REVOLUTIONS {
+                    return Err(ApiError::Invalid(format!(
+                        "norad {norad_id}: no orbital elements and the latest positions are more \
+                         than {FIT_MAX_REVOLUTIONS} revolutions from the requested window"
+                    )));
                 }
+                (PredictionModel::Fit, None, times.iter().map(|t| orbit.at(*t)).collect())
             }
+        };
+        let points = times
+            .into_iter()
+            .zip(positions)
+            .map(|(at, r)| {
+                let (latitude, longitude, altitude) = geodetic(r, at);
+                let illumination = illumination(r, sun_position(at));
+                TrackPoint {
+                    at,
+                    latitude,
+                    longitude,
+                    altitude,
+                    sunlit: illumination != Illumination::Umbra,
+                    illumination,
+                }
+            })
+            .collect();
+        Ok(IssPrediction { norad_id, model, tle_epoch, step_seconds: step, points })
+    }
+
+    pub async fn trend(&self, norad_id: i64, limit: i64) -> Result<IssTrend, ApiError> {
+        let mut points = self.repo.trend(norad_id, limit).await?;
+        points.iter_mut().for_each(fill_visibility);
+        Ok(summarize(points, None))
+    }
+
+    /// Position of `norad_id` at `at`: interpolated between the logged
+    /// positions around it or propagated from the stored elements, whichever
+    /// is expected to be closer. `None` when neither is possible.
+    pub async fn position_at(
+        &self,
+        norad_id: i64,
+        at: DateTime<Utc>,
+    ) -> Result<Option<PositionEstimate>, ApiError> {
+        Ok(self.positions_at(norad_id, &[at]).await?.pop().flatten())
+    }
+
+    /// [`Self::position_at`] for each of `ats`. Timestamps within
+    /// [`INTERPOLATION_MAX_GAP_SECONDS`] of each other share one range of the
+    /// log, and each element set is read and set up once.
+    pub async fn positions_at(
+        &self,
+        norad_id: i64,
+        ats: &[DateTime<Utc>],
+    ) -> Result<Vec<Option<PositionEstimate>>, ApiError> {
+        let mut order: Vec<usize> = (0..ats.len()).collect();
+        order.sort_by_key(|&i| ats[i]);
+        let sorted: Vec<DateTime<Utc>> = order.iter().map(|&i| ats[i]).collect();
+        let sets = self.nearest_sets(norad_id, &sorted).await?;
+        let gap = chrono::Duration::seconds(INTERPOLATION_MAX_GAP_SECONDS);
+        let mut out: Vec<Option<PositionEstimate>> = ats.iter().map(|_| None).collect();
+        // elements that do not propagate leave interpolation
+        let mut elements: Option<(&TleSet, Option<Sgp4>)> = None;
+        let mut start = 0;
+        while start < sorted.len() {
+            let mut end = start + 1;
+            while end < sorted.len() && sorted[end] - sorted[end - 1] <= gap {
+                end += 1;
+            }
+            let (from, to) = (sorted[start] - gap, sorted[end - 1] + gap);
+            let points = self.repo.range(norad_id, from, to).await?;
+            let located: Vec<&IssPoint> =
+                points.iter().filter(|p| p.latitude.is_some() && p.longitude.is_some()).collect();
+            for k in start..end {
+                let at = sorted[k];
+                if elements.as_ref().map(|(set, _)| *set) != sets[k].as_ref() {
+                    elements = sets[k].as_ref().map(|set| (set, propagator(set).ok()));
+                }
+                let interpolated = interpolate_at(norad_id, &located, at);
+                let propagated = elements.as_ref().and_then(|(set, sgp4)| {
+                    propagate_estimate(norad_id, set, sgp4.as_ref()?, at)
+                });
+                out[order[k]] = match (interpolated, propagated) {
+                    (Some(i), Some(p)) if p.uncertainty_km < i.uncertainty_km => Some(p),
+                    (Some(i), _) => Some(i),
+                    (None, p) => p,
+                };
+            }
+            start = end;
+        }
+        Ok(out)
+    }
+
+    /// The element set nearest to each of the sorted `ats`, as
+    /// [`OrbitService::nearest`] picks it for each alone. Nearest sets split
+    /// the timeline into intervals, so a run whose ends share a set shares it
+    /// throughout and is not looked up again.
+    async fn nearest_sets(
+        &self,
+        norad_id: i64,
+        ats: &[DateTime<Utc>],
+    ) -> Result<Vec<Option<TleSet>>, ApiError> {
+        let mut sets: Vec<Option<Option<TleSet>>> = ats.iter().map(|_| None).collect();
+        let mut runs = if ats.is_empty() { Vec::new() } else { vec![(0, ats.len() - 1)] };
+        while let Some((lo, hi)) = runs.pop() {
+            for i in [lo, hi] {
+                if sets[i].is_none() {
+                    sets[i] = Some(self.orbit.nearest(norad_id, ats[i]).await?);
+                }
+            }
+            if sets[lo] == sets[hi] {
+                for i in lo + 1..hi {
+                    sets[i] = sets[lo].clone();
+                }
+            } else if hi - lo > 1 {
+                let mid = lo + (hi - lo) / 2;
+                runs.push((lo, mid));
+                runs.push((mid, hi));
+            }
+        }
+        Ok(sets.into_iter().map(Option::flatten).collect())
+    }
+
+    /// Orbit statistics over the positions logged between `from` and `to`.
+    pub async fn stats(
+        &self,
+        norad_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<IssStats, ApiError> {
+        if from >= to {
+            return Err(ApiError::Invalid(format!("from {from} is not before to {to}")));
+        }
+        let points = self.repo.range(norad_id, from, to).await?;
+        Ok(stats::compute(norad_id, from, to, &points))
+    }
+
+    /// Every position between `from` and `to`, or about `max_points` of them
+    /// chosen by LTTB on latitude, for the track export.
+    pub async fn track(
+        &self,
+        norad_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        max_points: Option<usize>,
+    ) -> Result<Vec<Vec<TrackVertex>>, ApiError> {
+        if from >= to {
+            return Err(ApiError::Invalid(format!("from {from} is not before to {to}")));
         }
-        let dt_sec =
-            (points.last().unwrap().at - points.first().unwrap().at).num_milliseconds() as f64
-                / 1000.0;
-        Ok(IssTrend {
-            movement,
-            delta_km,
-            dt_sec,
+        let runs = split_track(&self.repo.range(norad_id, from, to).await?);
+        let Some(n) = max_points else {
+            return Ok(runs);
+        };
+        // thinned run by run, so that the cuts stay where they are
+        let total: usize = runs.iter().map(Vec::len).sum();
+        Ok(runs
+            .into_iter()
+            .map(|run| {
+                let share = (n * run.len()).div_ceil(total.max(1)).max(3);
+                lttb_by(run, share, |v| Some((v.at.timestamp_millis() as f64, v.latitude)))
+            })
+            .collect())
+    }
+
+    /// Positions between `from` and `to`, thinned out to about `max_points`
+    /// with LTTB on `series`. The totals cover every stored position.
+    pub async fn trend_range(
+        &self,
+        norad_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        max_points: usize,
+        series: TrendSeries,
+    ) -> Result<IssTrend, ApiError> {
+        if from >= to {
+            return Err(ApiError::Invalid(format!("from {from} is not before to {to}")));
+        }
+        let points = self.repo.range(norad_id, from, to).await?;
+        let window = TrendWindow { from, to, total: points.len(), series };
+        let mut trend = summarize(points, Some(window));
+        trend.points = lttb(std::mem::take(&mut trend.points), max_points, series);
+        trend.points.iter_mut().for_each(fill_visibility);
+        Ok(trend)
+    }
+}
+
+/// Distance travelled and time covered by consecutive positions.
+fn summarize(points: Vec<IssPoint>, window: Option<TrendWindow>) -> IssTrend {
+    if points.len() < 2 {
+        return IssTrend {
+            movement: false,
+            delta_km: 0.0,
+            dt_sec: 0.0,
             points,
+            window,
+        };
+    }
+    let mut delta_km = 0.0;
+    let mut movement = false;
+    for win in points.windows(2) {
+        if let [a, b] = win {
+            if let (Some(lat1), Some(lon1), Some(lat2), Some(lon2)) =
+                (a.latitude, a.longitude, b.latitude, b.longitude)
+            {
+                delta_km += haversine_km(lat1, lon1, lat2, lon2);
+                if delta_km > 0.1 {
+                    movement = true;
+                }
+            }
+        }
+    }
+    let dt_sec =
+        (points.last().unwrap().at - points.first().unwrap().at).num_milliseconds() as f64
+            / 1000.0;
+    IssTrend {
+        movement,
+        delta_km,
+        dt_sec,
+        points,
+        window,
+    }
+}
+
+/// Largest-Triangle-Three-Buckets: keeps the first and last points and, from
+/// each of `threshold - 2` equal buckets in between, the point spanning the
+/// largest triangle with the previous pick and the mean of the next bucket.
+/// Points without a `series` value are left out.
+pub(crate) fn lttb(
+    points: Vec<IssPoint>,
+    threshold: usize,
+    series: TrendSeries,
+) -> Vec<IssPoint> {
+    lttb_by(points, threshold, |p| Some((p.at.timestamp_millis() as f64, series.value(p)?)))
+}
+
+/// LTTB over the `(x, y)` that `xy` gives each point; points without one are
+/// left out.
+pub(crate) fn lttb_by<T>(
+    points: Vec<T>,
+    threshold: usize,
+    xy: impl Fn(&T) -> Option<(f64, f64)>,
+) -> Vec<T> {
+    let points: Vec<(f64, f64, T)> = points
+        .into_iter()
+        .filter_map(|p| {
+            let (x, y) = xy(&p)?;
+            Some((x, y, p))
+        })
+        .collect();
+    let n = points.len();
+    if threshold < 3 || n <= threshold {
+        return points.into_iter().map(|(_, _, p)| p).collect();
+    }
+    let bucket = (n - 2) as f64 / (threshold - 2) as f64;
+    let edge = |i: usize| ((i as f64 * bucket) as usize + 1).min(n - 1);
+    let mut keep = Vec::with_capacity(threshold);
+    keep.push(0);
+    let mut a = 0;
+    for i in 0..threshold - 2 {
+        let (start, end) = (edge(i), edge(i + 1));
+        let next = &points[end..edge(i + 2).max(end + 1)];
+        let len = next.len() as f64;
+        let (mx, my) = next.iter().fold((0.0, 0.0), |(x, y), p| (x + p.0 / len, y + p.1 / len));
+        let (ax, ay) = (points[a].0, points[a].1);
+        let area = |k: usize| {
+            let (x, y) = (points[k].0, points[k].1);
+            ((ax - mx) * (y - ay) - (ax - x) * (my - ay)).abs()
+        };
+        a = (start..end).max_by(|&i, &j| area(i).total_cmp(&area(j))).unwrap_or(start);
+        keep.push(a);
+    }
+    keep.push(n - 1);
+    let mut keep = keep.into_iter().peekable();
+    points
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, (_, _, p))| (keep.next_if_eq(&i).is_some()).then_some(p))
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct OrbitService {
+    repo: Arc<dyn TleRepo>,
+    clients: UpstreamClients,
+    cfg: AppConfig,
+}
+
+impl OrbitService {
+    pub fn new(repo: Arc<dyn TleRepo>, clients: UpstreamClients, cfg: AppConfig) -> Self {
+        Self { repo, clients, cfg }
+    }
+
+    /// Reads element sets from `TLE_FILE` when set, otherwise from `TLE_URL`,
+    /// and stores the ones not seen before. A `TLE_URL` with an `{id}`
+    /// placeholder is fetched once per tracked satellite; failed fetches are
+    /// reported like sets that fail to parse, unless every fetch failed.
+    pub async fn refresh(&self) -> Result<TleRefreshReport, ApiError> {
+        let mut errors = Vec::new();
+        let (source, text) = match &self.cfg.tle_file {
+            Some(path) => (
+                path.clone(),
+                tokio::fs::read_to_string(path)
+                    .await
+                    .map_err(|e| ApiError::Io(format!("tle file `{path}`"), e))?,
+            ),
+            None if self.cfg.tle_url.contains("{id}") => {
+                let mut text = String::new();
+                let mut failed = None;
+                for sat in &self.cfg.satellites {
+                    let url = self.cfg.tle_url.replace("{id}", &sat.norad_id.to_string());
+                    match self.clients.fetch_tle(&url).await {
+                        Ok(t) => {
+                            text.push_str(&t);
+                            text.push('\n');
+                        }
+                        Err(e) => {
+                            errors.push(format!("norad {}: {e}", sat.norad_id));
+                            failed = Some(e);
+                        }
+                    }
+                }
+                match failed {
+                    Some(e) if errors.len() == self.cfg.satellites.len() => return Err(e),
+                    _ => (self.cfg.tle_url.clone(), text),
+                }
+            }
+            None => (self.cfg.tle_url.clone(), self.clients.fetch_tle(&self.cfg.tle_url).await?),
+        };
+        let (sets, rejected) = parse_tle_text(&text);
+        errors.extend(rejected.iter().map(ToString::to_string));
+        let fetched_at = Utc::now();
+        let rows: Vec<TleSet> = sets
+            .into_iter()
+            .map(|t| TleSet {
+                norad_id: t.norad_id,
+                name: t.name,
+                epoch: t.epoch,
+                line1: t.line1,
+                line2: t.line2,
+                fetched_at,
+            })
+            .collect();
+        let inserted = self.repo.insert(&rows).await?;
+        Ok(TleRefreshReport {
+            source,
+            parsed: rows.len(),
+            inserted,
+            errors,
+        })
+    }
+
+    pub async fn nearest(
+        &self,
+        norad_id: i64,
+        at: DateTime<Utc>,
+    ) -> Result<Option<TleSet>, ApiError> {
+        Ok(self.repo.nearest(norad_id, at).await?)
+    }
+
+    /// Passes of `norad_id` over `observer` that rise and set between `from`
+    /// and `to`, propagated from the element set nearest to `from`.
+    pub async fn passes(
+        &self,
+        norad_id: i64,
+        observer: Observer,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<PassPrediction, ApiError> {
+        let set = self
+            .repo
+            .nearest(norad_id, from)
+            .await?
+            .ok_or_else(|| ApiError::Invalid(format!("norad {norad_id}: no orbital elements")))?;
+        let sgp4 = propagator(&set)?;
+        let standard = (norad_id == ISS_NORAD_ID).then_some(ISS_STANDARD_MAGNITUDE);
+        let passes = find_passes(&sgp4, &observer, from, to, standard)
+            .map_err(|e| ApiError::Invalid(format!("norad {norad_id}: {e}")))?;
+        Ok(PassPrediction {
+            norad_id,
+            latitude: observer.latitude,
+            longitude: observer.longitude,
+            altitude: observer.altitude * 1000.0,
+            tle_epoch: set.epoch,
+            from,
+            to,
+            passes,
         })
     }
+
+    pub async fn history(&self, norad_id: i64, limit: i64) -> Result<Vec<TleSet>, ApiError> {
+        Ok(self.repo.history(norad_id, limit).await?)
+    }
+
+    /// Position of `norad_id` at `at`, propagated from the stored set whose
+    /// epoch is closest; `None` before any set is stored.
+    pub async fn position(
+        &self,
+        norad_id: i64,
+        at: DateTime<Utc>,
+    ) -> Result<Option<OrbitPosition>, ApiError> {
+        match self.repo.nearest(norad_id, at).await? {
+            Some(set) => Ok(Some(propagate_set(&set, at)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn propagator(set: &TleSet) -> Result<Sgp4, ApiError> {
+    let tle = Tle::parse(set.name.as_deref(), &set.line1, &set.line2)
+        .map_err(|e| ApiError::Invalid(format!("stored element set: {e}")))?;
+    Sgp4::new(&tle).map_err(|e| ApiError::Invalid(format!("norad {}: {e}", set.norad_id)))
+}
+
+pub(crate) fn propagate_set(set: &TleSet, at: DateTime<Utc>) -> Result<OrbitPosition, ApiError> {
+    propagate(&propagator(set)?, set, at)
+}
+
+fn propagate(sgp4: &Sgp4, set: &TleSet, at: DateTime<Utc>) -> Result<OrbitPosition, ApiError> {
+    let state = sgp4
+        .at(at)
+        .map_err(|e| ApiError::Invalid(format!("norad {}: {e}", set.norad_id)))?;
+    let (latitude, longitude, altitude) = geodetic(state.position, at);
+    Ok(OrbitPosition {
+        norad_id: set.norad_id,
+        at,
+        latitude,
+        longitude,
+        altitude,
+        velocity: state.speed_kms() * 3600.0,
+        illumination: illumination(state.position, sun_position(at)),
+        tle_epoch: set.epoch,
+    })
+}
+
+/// ISS magnitude at 1000 km and half phase.
+const ISS_STANDARD_MAGNITUDE: f64 = -1.8;
+/// Sun elevation below which the observer counts as in darkness: the end of
+/// civil twilight.
+const DARK_SUN_ELEVATION: f64 = -6.0;
+/// The horizon scan step; LEO passes last several minutes, so none is skipped.
+const PASS_SCAN_SECONDS: i64 = 30;
+/// How often a pass is sampled for sunlight and darkness.
+const PASS_SAMPLE_SECONDS: i64 = 10;
+
+/// Scans `from..to` for passes; neither the rise nor the set is searched for
+/// past `to`, so a pass still up then is left out. A pass already in progress
+/// at `from` is left out too.
+pub(crate) fn find_passes(
+    sgp4: &Sgp4,
+    observer: &Observer,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    standard: Option<f64>,
+) -> Result<Vec<SatellitePass>, OrbitError> {
+    let elevation = |t| Ok(observer.look(sgp4.at(t)?.position, t).elevation);
+    let step = chrono::Duration::seconds(PASS_SCAN_SECONDS);
+    let mut passes = Vec::new();
+    let mut above = elevation(from)? > 0.0;
+    let mut t = from;
+    while t < to {
+        let next = (t + step).min(to);
+        let up = elevation(next)? > 0.0;
+        if up && !above {
+            let rise = horizon_crossing(&elevation, t, next)?;
+            let mut last_up = next;
+            let mut set = None;
+            while last_up < to {
+                let probe = (last_up + step).min(to);
+                if elevation(probe)? <= 0.0 {
+                    set = Some(horizon_crossing(&elevation, last_up, probe)?);
+                    break;
+                }
+                last_up = probe;
+            }
+            if let Some(set) = set {
+                passes.push(describe_pass(sgp4, observer, rise, set, standard)?);
+            }
+            t = (last_up + step).min(to);
+            above = false;
+        } else {
+            t = next;
+            above = up;
+        }
+    }
+    Ok(passes)
+}
+
+/// Bisects to the second when elevation changes sign between `a` and `b`.
+fn horizon_crossing(
+    elevation: &impl Fn(DateTime<Utc>) -> Result<f64, OrbitError>,
+    mut a: DateTime<Utc>,
+    mut b: DateTime<Utc>,
+) -> Result<DateTime<Utc>, OrbitError> {
+    let a_up = elevation(a)? > 0.0;
+    while (b - a).num_milliseconds() > 1000 {
+        let mid = a + (b - a) / 2;
+        if (elevation(mid)? > 0.0) == a_up {
+            a = mid;
+        } else {
+            b = mid;
+        }
+    }
+    Ok(a + (b - a) / 2)
+}
+
+fn describe_pass(
+    sgp4: &Sgp4,
+    observer: &Observer,
+    rise: DateTime<Utc>,
+    set: DateTime<Utc>,
+    standard: Option<f64>,
+) -> Result<SatellitePass, OrbitError> {
+    let look = |t| Ok::<_, OrbitError>(observer.look(sgp4.at(t)?.position, t));
+    // golden-section search for the highest point
+    let (mut a, mut b) = (rise, set);
+    let golden = 0.381_966;
+    while (b - a).num_milliseconds() > 1000 {
+        let span = (b - a).num_milliseconds() as f64;
+        let c = a + chrono::Duration::milliseconds((span * golden) as i64);
+        let d = b - chrono::Duration::milliseconds((span * golden) as i64);
+        if look(c)?.elevation < look(d)?.elevation {
+            a = c;
+        } else {
+            b = d;
+        }
+    }
+    let top_at = a + (b - a) / 2;
+    let top = look(top_at)?;
+
+    let mut visible = false;
+    let mut brightest: Option<f64> = None;
+    let mut t = rise;
+    while t <= set {
+        let r = sgp4.at(t)?.position;
+        let sun = sun_position(t);
+        if illumination(r, sun) != Illumination::Umbra && observer.look(sun, t).elevation < DARK_SUN_ELEVATION {
+            visible = true;
+            if let Some(std) = standard {
+                let m = magnitude(std, r, sun, observer.position(t));
+                brightest = Some(brightest.map_or(m, |b| b.min(m)));
+            }
+        }
+        t += chrono::Duration::seconds(PASS_SAMPLE_SECONDS);
+    }
+    Ok(SatellitePass {
+        rise: PassPoint { at: rise, azimuth: look(rise)?.azimuth },
+        culmination: PassPoint { at: top_at, azimuth: top.azimuth },
+        set: PassPoint { at: set, azimuth: look(set)?.azimuth },
+        duration_seconds: (set - rise).num_seconds(),
+        max_elevation: top.elevation,
+        visible,
+        magnitude: brightest,
+        magnitude_class: brightest.map(MagnitudeClass::of),
+    })
+}
+
+/// How many logged positions `predict` looks back over to fit an orbit.
+const FIT_POINTS: i64 = 30;
+/// A fitted orbit drifts by about 100 km a revolution; further from the
+/// positions it was fitted to it is not used.
+const FIT_MAX_REVOLUTIONS: f64 = 3.0;
+
+/// Fits a circular orbit through the latest logged position and the newest
+/// earlier one at least a minute and at most 45 minutes (under half an ISS
+/// revolution) before it.
+pub(crate) fn fit_orbit(points: &[IssPoint]) -> Option<CircularOrbit> {
+    let teme = |p: &IssPoint| match (p.latitude, p.longitude, p.altitude) {
+        (Some(lat), Some(lon), Some(alt)) => Some((p.at, teme_from_geodetic(lat, lon, alt, p.at))),
+        _ => None,
+    };
+    let mut located = points.iter().rev().filter_map(teme);
+    let last = located.next()?;
+    located
+        .filter(|(at, _)| (60..=45 * 60).contains(&(last.0 - *at).num_seconds()))
+        .find_map(|a| CircularOrbit::fit(a, last))
+}
+
+/// Logged positions further apart than this are not interpolated between.
+pub(crate) const INTERPOLATION_MAX_GAP_SECONDS: i64 = 1200;
+/// Error of a logged position; wheretheiss.at propagates elements itself.
+const SAMPLE_UNCERTAINTY_KM: f64 = 1.0;
+/// SGP4 error at the element epoch and its growth with the elements' age.
+const SGP4_UNCERTAINTY_KM: f64 = 1.0;
+const SGP4_DRIFT_KM_PER_DAY: f64 = 2.0;
+/// Older elements are not used: past two weeks the error grows much faster
+/// than linearly.
+const SGP4_MAX_AGE_DAYS: f64 = 14.0;
+/// rad/s
+const EARTH_ROTATION: f64 = 7.292_115e-5;
+
+/// `at` propagated from `set`, unless the elements are older than
+/// [`SGP4_MAX_AGE_DAYS`] there.
+fn propagate_estimate(
+    norad_id: i64,
+    set: &TleSet,
+    sgp4: &Sgp4,
+    at: DateTime<Utc>,
+) -> Option<PositionEstimate> {
+    let age_days = (at - set.epoch).num_seconds().abs() as f64 / 86_400.0;
+    if age_days > SGP4_MAX_AGE_DAYS {
+        return None;
+    }
+    let p = propagate(sgp4, set, at).ok()?;
+    Some(PositionEstimate {
+        norad_id,
+        at,
+        latitude: p.latitude,
+        longitude: p.longitude,
+        altitude: Some(p.altitude),
+        method: EstimateMethod::Propagated,
+        uncertainty_km: SGP4_UNCERTAINTY_KM + SGP4_DRIFT_KM_PER_DAY * age_days,
+        samples: None,
+        tle_epoch: Some(p.tle_epoch),
+    })
+}
+
+/// Interpolates between the located positions around `at`, oldest first.
+fn interpolate_at(
+    norad_id: i64,
+    located: &[&IssPoint],
+    at: DateTime<Utc>,
+) -> Option<PositionEstimate> {
+    let before = located[..located.partition_point(|p| p.at <= at)].last()?;
+    let after = located.get(located.partition_point(|p| p.at < at))?;
+    let (latitude, longitude, altitude, uncertainty_km) = interpolate(before, after, at)?;
+    Some(PositionEstimate {
+        norad_id,
+        at,
+        latitude,
+        longitude,
+        altitude,
+        method: EstimateMethod::Interpolated,
+        uncertainty_km,
+        samples: Some([before.at, after.at]),
+        tle_epoch: None,
+    })
+}
+
+/// Latitude, longitude, altitude and expected error in km at `at`, moving
+/// along the great circle from `a` to `b` at constant speed. An orbit is a
+/// great circle only in the inertial frame; the Earth turning underneath
+/// bends the ground track by the Coriolis acceleration `2ωv`, which sets the
+/// error.
+pub(crate) fn interpolate(
+    a: &IssPoint,
+    b: &IssPoint,
+    at: DateTime<Utc>,
+) -> Option<(f64, f64, Option<f64>, f64)> {
+    let (lat_a, lon_a, lat_b, lon_b) = (a.latitude?, a.longitude?, b.latitude?, b.longitude?);
+    let dt = (b.at - a.at).num_milliseconds() as f64 / 1000.0;
+    if dt <= 0.0 {
+        return (a.at == at).then_some((lat_a, lon_a, a.altitude, SAMPLE_UNCERTAINTY_KM));
+    }
+    if dt > INTERPOLATION_MAX_GAP_SECONDS as f64 || at < a.at || at > b.at {
+        return None;
+    }
+    let f = (at - a.at).num_milliseconds() as f64 / 1000.0 / dt;
+    let unit = |lat: f64, lon: f64| {
+        let (slat, clat) = lat.to_radians().sin_cos();
+        let (slon, clon) = lon.to_radians().sin_cos();
+        [clat * clon, clat * slon, slat]
+    };
+    let (ua, ub) = (unit(lat_a, lon_a), unit(lat_b, lon_b));
+    let theta = dot(ua, ub).clamp(-1.0, 1.0).acos();
+    let u = if theta < 1e-9 {
+        ua
+    } else {
+        let (wa, wb) = (((1.0 - f) * theta).sin(), (f * theta).sin());
+        let s = theta.sin();
+        [0, 1, 2].map(|i| (wa * ua[i] + wb * ub[i]) / s)
+    };
+    let latitude = u[2].clamp(-1.0, 1.0).asin().to_degrees();
+    let longitude = u[1].atan2(u[0]).to_degrees();
+    let altitude = match (a.altitude, b.altitude) {
+        (Some(x), Some(y)) => Some(x + (y - x) * f),
+        (x, y) if f < 0.5 => x.or(y),
+        (x, y) => y.or(x),
+    };
+    let speed = theta * 6371.0 / dt;
+    let bend = f * (1.0 - f) * dt * dt * EARTH_ROTATION * speed;
+    Some((latitude, longitude, altitude, SAMPLE_UNCERTAINTY_KM + bend))
+}
+
+/// Computes `visibility` for points whose payload did not carry it.
+fn fill_visibility(p: &mut IssPoint) {
+    if p.visibility.is_some() {
+        return;
+    }
+    if let (Some(lat), Some(lon), Some(alt)) = (p.latitude, p.longitude, p.altitude) {
+        let r = teme_from_geodetic(lat, lon, alt, p.at);
+        p.visibility = Some(illumination(r, sun_position(p.at)).visibility().to_string());
+    }
+}
+
+/// A propagated position shaped like a wheretheiss.at reply, so trends and
+/// summaries read polled and propagated rows alike.
+fn propagated_payload(sat: &TrackedSatellite, pos: &OrbitPosition) -> Value {
+    serde_json::json!({
+        "id": sat.norad_id,
+        "name": sat.name,
+        "latitude": pos.latitude,
+        "longitude": pos.longitude,
+        "altitude": pos.altitude,
+        "velocity": pos.velocity,
+        "visibility": pos.illumination.visibility(),
+        "illumination": pos.illumination,
+        "timestamp": pos.at.timestamp(),
+        "units": "kilometers",
+        "source": "sgp4",
+        "tle_epoch": pos.tle_epoch,
+    })
 }
 
 #[derive(Clone)]
 pub struct OsdrService {
-    repo: OsdrRepo,
+    repo: Arc<dyn OsdrRepo>,
     clients: UpstreamClients,
+    cfg: AppConfig,
 }
 
 impl OsdrService {
-    pub fn new(repo: OsdrRepo, clients: UpstreamClients) -> Self {
-        Self { repo, clients }
+    pub fn new(repo: Arc<dyn OsdrRepo>, clients: UpstreamClients, cfg: AppConfig) -> Self {
+        Self { repo, clients, cfg }
+    }
+
+    /// Syncs the upstream catalog in pages of `OSDR_SYNC_BATCH` items, each
+    /// page in one transaction. Runs a full pass when `full` is set or before
+    /// the first watermark exists; otherwise only items newer than the
+    /// watermark are written. Only full passes tombstone datasets the catalog
+    /// no longer lists.
+    ///
+    /// Items without a `dataset_id`, repeated ids and pages the store rejects
+    /// are reported as failed; the rest of the catalog is still written, but
+    /// the watermark only advances when no page failed.
+    pub async fn sync(&self, full: bool) -> Result<OsdrSyncReport, ApiError> {
+        let mut state = self.repo.sync_state().await?;
+        let now = Utc::now();
+        let mode = if full || state.watermark.is_none() {
+            OsdrSyncMode::Full
+        } else {
+            OsdrSyncMode::Incremental
+        };
+        let since = match mode {
+            OsdrSyncMode::Full => None,
+            OsdrSyncMode::Incremental => state.watermark,
+        };
+        let json = self.clients.fetch_osdr(since).await?;
+        let mut report = OsdrSyncReport {
+            mode,
+            ..Default::default()
+        };
+        let mut seen = HashSet::new();
+        let (mut positions, mut items) = (Vec::new(), Vec::new());
+        let mut idless = Vec::new();
+        let mut rejected = Vec::new();
+        let catalog = normalize_osdr_items(
+            &json,
+            &self.cfg.osdr_field_map,
+            &self.cfg.time_parser,
+            &mut rejected,
+        );
+        for (index, e) in rejected {
+            let dataset_id = catalog[index].dataset_id.clone();
+            report.warn(index, dataset_id, format!("updated_at: {e}"));
+        }
+        for (index, item) in catalog.into_iter().enumerate() {
+            for (field, missing) in [
+                ("dataset_id", item.dataset_id.is_none()),
+                ("title", item.title.is_none()),
+                ("status", item.status.is_none()),
+                ("updated_at", item.updated_at.is_none()),
+            ] {
+                if missing {
+                    *report.unmapped.entry(field.to_string()).or_default() += 1;
+                }
+            }
+            // Upstreams that ignore the since parameter are filtered here
+            if let (Some(wm), Some(at)) = (since, item.updated_at) {
+                if at <= wm {
+                    report.skipped += 1;
+                    continue;
+                }
+            }
+            match &item.dataset_id {
+                // Nothing to batch or version on; stored one by one as before
+                None => idless.push((index, item)),
+                Some(ds) if !seen.insert(ds.clone()) => {
+                    report.fail(index, Some(ds.clone()), "duplicate dataset_id")
+                }
+                Some(_) => {
+                    positions.push(index);
+                    items.push(item);
+                }
+            }
+        }
+        let batch = self.cfg.osdr_sync_batch.max(1);
+        let mut page_failed = false;
+        for (page, pos) in items.chunks(batch).zip(positions.chunks(batch)) {
+            match self.repo.upsert_batch(page).await {
+                Ok(changes) => changes.into_iter().for_each(|c| report.record(c)),
+                Err(e) => {
+                    tracing::warn!(error = ?e, items = page.len(), "osdr sync page failed");
+                    page_failed = true;
+                    let message = e.to_string();
+                    for (item, index) in page.iter().zip(pos) {
+                        report.fail(*index, item.dataset_id.clone(), &message);
+                    }
+                }
+            }
+        }
+        let newest = items
+            .iter()
+            .chain(idless.iter().map(|(_, i)| i))
+            .filter_map(|i| i.updated_at)
+            .max();
+        for (index, item) in idless {
+            match self.repo.upsert(item).await {
+                Ok(change) => report.record(change),
+                Err(e) => {
+                    tracing::warn!(error = ?e, "osdr sync item without dataset_id failed");
+                    page_failed = true;
+                    report.fail(index, None, &e.to_string());
+                }
+            }
+        }
+        // An empty pass is more likely an upstream glitch than a wiped catalog
+        if mode == OsdrSyncMode::Full && !seen.is_empty() {
+            let seen: Vec<String> = seen.into_iter().collect();
+            report.removed = self.repo.mark_removed(&seen, now).await?;
+        }
+
+        if !page_failed {
+            state.watermark = state.watermark.max(newest);
+        }
+        if mode == OsdrSyncMode::Full {
+            state.last_full_at = Some(now);
+        }
+        state.last_sync_at = Some(now);
+        self.repo.save_sync_state(&state).await?;
+        report.watermark = state.watermark;
+        Ok(report)
+    }
+
+    /// Time left until the next full pass is due, `OSDR_FULL_SYNC_EVERY_SECONDS`
+    /// after the last one; zero when it is due already or never ran.
+    pub async fn full_sync_due_in(&self) -> Result<std::time::Duration, ApiError> {
+        let state = self.repo.sync_state().await?;
+        let every = chrono::Duration::seconds(self.cfg.every_osdr_full as i64);
+        Ok(state
+            .last_full_at
+            .and_then(|t| (t + every - Utc::now()).to_std().ok())
+            .unwrap_or_default())
+    }
+
+    pub async fn list(&self, mut q: OsdrListQuery) -> Result<OsdrPage, ApiError> {
+        if let Some(c) = &q.after {
+            if c.sort != q.sort || c.desc != q.desc {
+                return Err(ApiError::Invalid(
+                    "cursor does not match sort/order".to_string(),
+                ));
+            }
+        }
+        let limit = q.limit;
+        // One extra row tells us whether another page exists.
+        q.limit = limit + 1;
+        let mut items = self.repo.list(&q).await?;
+        let next_cursor = if items.len() as i64 > limit {
+            items.truncate(limit as usize);
+            items.last().map(|last| encode_cursor(&cursor_for(last, q.sort, q.desc)))
+        } else {
+            None
+        };
+        let total_estimate = self.repo.count(&q.filter).await?;
+        Ok(OsdrPage {
+            items,
+            limit,
+            next_cursor,
+            total_estimate,
+        })
+    }
+
+    /// The catalog matching `filter` rendered as `format`, oldest first. Rows
+    /// are read [`EXPORT_PAGE`] at a time as the stream is polled, so only one
+    /// page is held in memory.
+    ///
+    /// The first page is read before this returns, so a failing store is
+    /// reported as an error response. A page failing once the response has
+    /// started ends the body with an error trailer (see [`export_error`]).
+    pub async fn export(
+        &self,
+        filter: OsdrFilter,
+        format: OsdrExportFormat,
+    ) -> Result<impl Stream<Item = Result<String, ApiError>> + Send + 'static, ApiError> {
+        let repo = self.repo.clone();
+        let dataset_url = self.cfg.osdr_dataset_url.clone();
+        let page = move |after| OsdrListQuery {
+            filter: filter.clone(),
+            sort: OsdrSort::InsertedAt,
+            desc: false,
+            limit: EXPORT_PAGE,
+            after,
+        };
+        let first = repo.list(&page(None)).await?;
+        let render = move |rows: Vec<OsdrItem>, first: bool| {
+            let mut out = if first { export_header(format) } else { String::new() };
+            for (i, item) in rows.iter().enumerate() {
+                if format == OsdrExportFormat::JsonLd && !(first && i == 0) {
+                    out.push(',');
+                }
+                export_row(&mut out, item, format, &dataset_url);
+            }
+            match rows.last() {
+                Some(last) if rows.len() as i64 == EXPORT_PAGE => {
+                    (out, ExportStep::Page(cursor_for(last, OsdrSort::InsertedAt, false)))
+                }
+                _ => (out, ExportStep::Footer),
+            }
+        };
+        Ok(stream::unfold(ExportStep::First(first), move |step| {
+            let (repo, page, render) = (repo.clone(), page.clone(), render.clone());
+            async move {
+                let (chunk, next) = match step {
+                    ExportStep::First(rows) => render(rows, true),
+                    ExportStep::Page(after) => match repo.list(&page(Some(after))).await {
+                        Ok(rows) => render(rows, false),
+                        Err(e) => {
+                            tracing::warn!(error = ?e, "osdr export page failed");
+                            (export_error(format, &e.to_string()), ExportStep::Done)
+                        }
+                    },
+                    ExportStep::Footer => (export_footer(format), ExportStep::Done),
+                    ExportStep::Done => return None,
+                };
+                Some((Ok(chunk), next))
+            }
+        }))
+    }
+
+    pub async fn search(&self, mut q: OsdrSearchQuery) -> Result<OsdrPage<OsdrSearchHit>, ApiError> {
+        if q.text.trim().is_empty() {
+            return Err(ApiError::Invalid("q must not be empty".to_string()));
+        }
+        let limit = q.limit;
+        q.limit = limit + 1;
+        let mut items = self.repo.search(&q).await?;
+        let next_cursor = if items.len() as i64 > limit {
+            items.truncate(limit as usize);
+            items.last().map(|last| {
+                encode_cursor(&OsdrSearchCursor {
+                    rank: last.rank,
+                    id: last.item.id,
+                })
+            })
+        } else {
+            None
+        };
+        let total_estimate = self.repo.search_count(&q).await?;
+        Ok(OsdrPage {
+            items,
+            limit,
+            next_cursor,
+            total_estimate,
+        })
+    }
+
+    pub async fn count(&self, filter: &OsdrFilter) -> Result<i64, ApiError> {
+        Ok(self.repo.count(filter).await?)
+    }
+
+    pub async fn get(&self, dataset_id: &str) -> Result<Option<OsdrItem>, ApiError> {
+        Ok(self.repo.get(dataset_id).await?)
     }
 
-    pub async fn sync(&self) -> Result<usize, ApiError> {
-        let json = self.clients.fetch_osdr().await?;
-        let items = normalize_osdr_items(&json);
-        let mut written = 0usize;
-        for item in items {
-            self.repo.upsert(item).await?;
-            written += 1;
+    pub async fn history(&self, dataset_id: &str, limit: i64) -> Result<Vec<OsdrVersion>, ApiError> {
+        Ok(self.repo.history(dataset_id, limit).await?)
+    }
+
+    pub async fn recent_changes(
+        &self,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<Vec<OsdrVersion>, ApiError> {
+        Ok(self.repo.recent_changes(since, limit).await?)
+    }
+
+    /// Fetches and stores detail metadata for one dataset.
+    pub async fn enrich_one(
+        &self,
+        dataset_id: &str,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> Result<OsdrDetail, ApiError> {
+        let json = self.clients.fetch_osdr_detail(dataset_id).await?;
+        let mut detail = parse_osdr_detail(dataset_id, &json);
+        detail.source_updated_at = updated_at;
+        self.repo.save_detail(&detail).await?;
+        Ok(detail)
+    }
+
+    /// The stored detail of a dataset, or fetched now when the enrichment job
+    /// has not reached it yet. A dataset whose enrichment failed is not
+    /// fetched again before its retry is due, and failing here backs it off
+    /// as in [`Self::enrich`].
+    pub async fn detail_or_enrich(
+        &self,
+        dataset_id: &str,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<OsdrDetail>, ApiError> {
+        if let Some(detail) = self.repo.detail(dataset_id).await? {
+            return Ok(Some(detail));
+        }
+        let now = Utc::now();
+        let failure = self.repo.enrich_failure(dataset_id).await?;
+        if failure.as_ref().is_some_and(|f| f.retry_at > now) {
+            return Ok(None);
+        }
+        match self.enrich_one(dataset_id, updated_at).await {
+            Ok(detail) => Ok(Some(detail)),
+            Err(e) => {
+                let attempts = failure.map_or(0, |f| f.attempts);
+                self.record_enrich_failure(dataset_id, attempts, now, e).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Enriches up to `batch` datasets that are new or changed since their
+    /// last enrichment. A failed dataset is retried after a backoff that
+    /// doubles with each failure in a row, from one enrichment period up to
+    /// a day.
+    pub async fn enrich(&self, batch: i64) -> Result<OsdrEnrichRun, ApiError> {
+        let now = Utc::now();
+        let candidates = self.repo.enrich_candidates(now, batch).await?;
+        let mut run = OsdrEnrichRun {
+            candidates: candidates.len(),
+            ..Default::default()
+        };
+        for c in candidates {
+            match self.enrich_one(&c.dataset_id, c.updated_at).await {
+                Ok(_) => run.enriched += 1,
+                Err(e) => {
+                    run.failed += 1;
+                    self.record_enrich_failure(&c.dataset_id, c.attempts, now, e).await?;
+                }
+            }
+        }
+        Ok(run)
+    }
+
+    /// Records the failure that followed `attempts` earlier ones in a row.
+    async fn record_enrich_failure(
+        &self,
+        dataset_id: &str,
+        attempts: i32,
+        now: DateTime<Utc>,
+        e: ApiError,
+    ) -> Result<(), ApiError> {
+        tracing::warn!(dataset_id, error = ?e, "osdr enrichment failed");
+        let attempts = attempts + 1;
+        let backoff = (self.cfg.every_osdr_enrich as i64)
+            .saturating_mul(1 << (attempts - 1).min(16))
+            .clamp(1, ENRICH_BACKOFF_MAX_SECONDS);
+        self.repo
+            .record_enrich_failure(&OsdrEnrichFailure {
+                dataset_id: dataset_id.to_string(),
+                attempts,
+                failed_at: now,
+                retry_at: now + chrono::Duration::seconds(backoff),
+                error: e.to_string(),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+/// Longest wait before a failed enrichment is retried.
+const ENRICH_BACKOFF_MAX_SECONDS: i64 = 86_400;
+
+/// Rows per database page while exporting.
+const EXPORT_PAGE: i64 = 500;
+
+enum ExportStep {
+    /// The first page, read before the response started.
+    First(Vec<OsdrItem>),
+    Page(OsdrCursor),
+    Footer,
+    Done,
+}
+
+const EXPORT_CSV_COLUMNS: [&str; 7] =
+    ["dataset_id", "title", "status", "updated_at", "inserted_at", "removed_at", "url"];
+
+pub(crate) fn export_header(format: OsdrExportFormat) -> String {
+    match format {
+        OsdrExportFormat::Csv => format!("{}\r\n", EXPORT_CSV_COLUMNS.join(",")),
+        OsdrExportFormat::Ndjson => String::new(),
+        OsdrExportFormat::JsonLd => concat!(
+            r#"{"@context":"https://schema.org/","@type":"DataCatalog","#,
+            r#""name":"NASA Open Science Data Repository","dataset":["#
+        )
+        .to_string(),
+    }
+}
+
+pub(crate) fn export_footer(format: OsdrExportFormat) -> String {
+    match format {
+        OsdrExportFormat::JsonLd => "]}".to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Ends an export cut short by `message` in place of the footer: a
+/// `#error: …` CSV row, an `{"error": …}` NDJSON line, or an `error` member
+/// closing the JSON-LD catalog, so the body still parses and says why it
+/// is incomplete.
+pub(crate) fn export_error(format: OsdrExportFormat, message: &str) -> String {
+    match format {
+        OsdrExportFormat::Csv => format!("{}\r\n", csv_field(&format!("#error: {message}"))),
+        OsdrExportFormat::Ndjson => format!("{}\n", serde_json::json!({"error": message})),
+        OsdrExportFormat::JsonLd => format!("],\"error\":{}}}", Value::from(message)),
+    }
+}
+
+/// Appends one item; JSON-LD items are not comma-separated here.
+pub(crate) fn export_row(out: &mut String, item: &OsdrItem, format: OsdrExportFormat, dataset_url: &str) {
+    let url = item.dataset_id.as_deref().map(|ds| dataset_url.replace("{id}", &path_segment(ds)));
+    let time = |t: Option<DateTime<Utc>>| t.map(|t| t.to_rfc3339());
+    match format {
+        OsdrExportFormat::Csv => {
+            let fields = [
+                item.dataset_id.clone(),
+                item.title.clone(),
+                item.status.clone(),
+                time(item.updated_at),
+                time(Some(item.inserted_at)),
+                time(item.removed_at),
+                url,
+            ];
+            let cells: Vec<String> = fields.iter().map(|f| csv_field(f.as_deref().unwrap_or(""))).collect();
+            out.push_str(&cells.join(","));
+            out.push_str("\r\n");
+        }
+        OsdrExportFormat::Ndjson => {
+            out.push_str(&serde_json::to_string(item).unwrap_or_default());
+            out.push('\n');
         }
-        Ok(written)
+        OsdrExportFormat::JsonLd => {
+            let mut d = serde_json::Map::new();
+            d.insert("@type".into(), "Dataset".into());
+            if let Some(url) = &url {
+                d.insert("@id".into(), url.as_str().into());
+                d.insert("url".into(), url.as_str().into());
+            }
+            let mut put = |k: &str, v: Option<String>| {
+                if let Some(v) = v {
+                    d.insert(k.into(), v.into());
+                }
+            };
+            put("identifier", item.dataset_id.clone());
+            put("name", item.title.clone().or_else(|| item.dataset_id.clone()));
+            put("description", s_pick(&item.raw, &["description", "study description"]));
+            put("creativeWorkStatus", item.status.clone());
+            put("dateModified", time(item.updated_at));
+            out.push_str(&Value::Object(d).to_string());
+        }
+    }
+}
+
+/// RFC 4180 quoting; cells a spreadsheet would read as a formula get a
+/// leading `'`.
+fn csv_field(s: &str) -> String {
+    let s = if s.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{s}")
+    } else {
+        s.to_string()
+    };
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s
     }
+}
 
-    pub async fn list(&self, limit: i64) -> Result<Vec<crate::domain::OsdrItem>, ApiError> {
-        Ok(self.repo.list(limit).await?)
+fn cursor_for(item: &OsdrItem, sort: OsdrSort, desc: bool) -> OsdrCursor {
+    let key = match sort {
+        OsdrSort::InsertedAt => Some(item.inserted_at.to_rfc3339()),
+        OsdrSort::UpdatedAt => item.updated_at.map(|t| t.to_rfc3339()),
+        OsdrSort::Title => item.title.clone(),
+    };
+    OsdrCursor {
+        sort,
+        desc,
+        key,
+        id: item.id,
     }
 }
 
+/// Cursors are opaque to clients: hex-encoded JSON of the last row's sort key.
+pub(crate) fn encode_cursor<T: Serialize>(c: &T) -> String {
+    hex::encode(serde_json::to_vec(c).unwrap_or_default())
+}
+
+pub(crate) fn decode_cursor<T: DeserializeOwned>(s: &str) -> Result<T, ApiError> {
+    hex::decode(s)
+        .ok()
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        .ok_or_else(|| ApiError::Invalid("malformed cursor".to_string()))
+}
+
 #[derive(Clone)]
 pub struct SpaceService {
-    cache_repo: CacheRepo,
+    cache_repo: Arc<dyn CacheRepo>,
     clients: UpstreamClients,
 }
 
 impl SpaceService {
-    pub fn new(cache_repo: CacheRepo, clients: UpstreamClients) -> Self {
+    pub fn new(cache_repo: Arc<dyn CacheRepo>, clients: UpstreamClients) -> Self {
         Self {
             cache_repo,
             clients,
@@ -139,6 +1418,90 @@ impl SpaceService {
     pub async fn latest(&self, source: &str) -> Result<Option<SpaceCacheItem>, ApiError> {
         Ok(self.cache_repo.latest(source).await?)
     }
+
+    pub async fn history(&self, source: &str, limit: i64) -> Result<Vec<SpaceCacheItem>, ApiError> {
+        Ok(self.cache_repo.history(source, limit).await?)
+    }
+}
+
+#[derive(Clone)]
+pub struct RetentionService {
+    repo: Arc<dyn RetentionRepo>,
+    iss_repo: Arc<dyn IssRepo>,
+    policies: Vec<RetentionPolicy>,
+    partitions_ahead: u32,
+}
+
+impl RetentionService {
+    pub fn new(
+        repo: Arc<dyn RetentionRepo>,
+        iss_repo: Arc<dyn IssRepo>,
+        policies: Vec<RetentionPolicy>,
+        partitions_ahead: u32,
+    ) -> Self {
+        Self {
+            repo,
+            iss_repo,
+            policies,
+            partitions_ahead,
+        }
+    }
+
+    pub async fn run(&self) -> Result<Vec<RetentionRun>, ApiError> {
+        let now = Utc::now();
+        let created = self
+            .iss_repo
+            .ensure_partitions(now, self.partitions_ahead)
+            .await?;
+        if !created.is_empty() {
+            tracing::info!(partitions = ?created, "iss_fetch_log partitions created");
+        }
+        let mut runs = Vec::new();
+        for policy in &self.policies {
+            let mut dropped_rows = 0;
+            if let (true, Some(max)) = (policy.table == "iss_fetch_log", policy.max) {
+                let before = now - chrono::Duration::from_std(max).map_err(anyhow::Error::from)?;
+                let (dropped, rows) = self.iss_repo.drop_partitions_before(before).await?;
+                if !dropped.is_empty() {
+                    tracing::info!(partitions = ?dropped, rows, "iss_fetch_log partitions dropped");
+                }
+                dropped_rows = rows;
+            }
+            let skip = sources_with_own_policy(&self.policies, policy);
+            let (downsampled, expired) = self.repo.apply(policy, &skip, now).await?;
+            runs.push(
+                self.repo
+                    .record(&policy.target(), downsampled, expired + dropped_rows)
+                    .await?,
+            );
+        }
+        Ok(runs)
+    }
+
+    pub async fn report(&self) -> Result<RetentionReport, ApiError> {
+        Ok(RetentionReport {
+            iss_storage: self.iss_repo.storage().await?,
+            policies: self.policies.clone(),
+            tables: self.repo.table_stats().await?,
+            last_runs: self.repo.last_runs().await?,
+        })
+    }
+}
+
+/// For a table-wide policy, the sources of the same table that are governed
+/// by a more specific policy and must be left alone.
+pub(crate) fn sources_with_own_policy(
+    policies: &[RetentionPolicy],
+    policy: &RetentionPolicy,
+) -> Vec<String> {
+    if policy.source.is_some() {
+        return Vec::new();
+    }
+    policies
+        .iter()
+        .filter(|p| p.table == policy.table)
+        .filter_map(|p| p.source.clone())
+        .collect()
 }
 
 pub(crate) fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
@@ -153,65 +1516,203 @@ pub(crate) fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
 }
 
 pub(crate) fn s_pick(v: &Value, keys: &[&str]) -> Option<String> {
-    for k in keys {
-        if let Some(x) = v.get(*k) {
-            if let Some(s) = x.as_str() {
-                if !s.is_empty() {
-                    return Some(s.to_string());
-                }
-            } else if x.is_number() {
-                return Some(x.to_string());
+    keys.iter().find_map(|k| v.get(*k).and_then(str_value))
+}
+
+/// [`s_pick`] over JSON Pointer paths.
+pub(crate) fn s_pointer(v: &Value, paths: &[String]) -> Option<String> {
+    paths.iter().find_map(|p| v.pointer(p).and_then(str_value))
+}
+
+/// The first timestamp under `paths`, skipping absent and null values. When
+/// values exist but none parses, the first rejection is returned.
+pub(crate) fn t_pointer(
+    v: &Value,
+    paths: &[String],
+    parser: &TimeParser,
+) -> Result<Option<DateTime<Utc>>, TimeError> {
+    let mut first_err = None;
+    for x in paths.iter().filter_map(|p| v.pointer(p)).filter(|x| !x.is_null()) {
+        match parser.parse_value(x) {
+            Ok(t) => return Ok(Some(t)),
+            Err(e) => {
+                first_err.get_or_insert(e);
             }
         }
     }
-    None
+    first_err.map_or(Ok(None), Err)
 }
 
-pub(crate) fn t_pick(v: &Value, keys: &[&str]) -> Option<DateTime<Utc>> {
-    for k in keys {
-        if let Some(x) = v.get(*k) {
-            if let Some(s) = x.as_str() {
-                if let Ok(dt) = s.parse::<DateTime<Utc>>() {
-                    return Some(dt);
-                }
-                if let Ok(ndt) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
-                    return Some(Utc.from_utc_datetime(&ndt));
-                }
-            } else if let Some(n) = x.as_i64() {
-                if let Some(dt) = Utc.timestamp_opt(n, 0).single() {
-                    return Some(dt);
-                }
-            }
-        }
+/// A non-empty string, or a number rendered as text.
+fn str_value(x: &Value) -> Option<String> {
+    match x {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
     }
-    None
 }
 
-pub(crate) fn normalize_osdr_items(json: &Value) -> Vec<OsdrUpsert> {
+/// Splits an upstream catalog into items and maps their fields through `map`.
+/// A payload without any of the `map.items` arrays is taken as one item.
+/// Rejected `updated_at` values leave it empty and are appended to
+/// `rejected` with the item's position.
+pub(crate) fn normalize_osdr_items(
+    json: &Value,
+    map: &OsdrFieldMap,
+    parser: &TimeParser,
+    rejected: &mut Vec<(usize, TimeError)>,
+) -> Vec<OsdrUpsert> {
     let arr = if let Some(a) = json.as_array() {
         a.clone()
-    } else if let Some(v) = json.get("items").and_then(|x| x.as_array()) {
-        v.clone()
-    } else if let Some(v) = json.get("results").and_then(|x| x.as_array()) {
-        v.clone()
     } else {
-        vec![json.clone()]
+        let mut v = Vec::new();
+        for path in &map.items {
+            if let Some(a) = json.pointer(path).and_then(|x| x.as_array()) {
+                v.extend(a.iter().cloned());
+            }
+        }
+        if v.is_empty() {
+            vec![json.clone()]
+        } else {
+            v
+        }
     };
 
+    let parser = parser.with(&map.time_formats);
     arr.into_iter()
-        .map(|item| {
-            let id = s_pick(&item, &["dataset_id", "id", "uuid", "studyId", "accession", "osdr_id"]);
-            let title = s_pick(&item, &["title", "name", "label"]);
-            let status = s_pick(&item, &["status", "state", "lifecycle"]);
-            let updated = t_pick(&item, &["updated", "updated_at", "modified", "lastUpdated", "timestamp"]);
+        .enumerate()
+        .map(|(index, item)| {
+            let updated_at = t_pointer(&item, &map.updated_at, &parser).unwrap_or_else(|e| {
+                rejected.push((index, e));
+                None
+            });
             OsdrUpsert {
-                dataset_id: id,
-                title,
-                status,
-                updated_at: updated,
+                dataset_id: s_pointer(&item, &map.dataset_id),
+                title: s_pointer(&item, &map.title),
+                status: s_pointer(&item, &map.status),
+                updated_at,
                 raw: item,
             }
         })
         .collect()
 }
 
+/// Extracts structured metadata from one dataset's detail payload. The v2 API
+/// wraps it as `{"OSD-48": {"files": {...}, "metadata": {...}}}`; flatter
+/// shapes with the same keys are accepted too.
+pub(crate) fn parse_osdr_detail(dataset_id: &str, json: &Value) -> OsdrDetail {
+    let root = json
+        .get(dataset_id)
+        .or_else(|| {
+            let obj = json.as_object().filter(|o| o.len() == 1)?;
+            obj.values()
+                .next()
+                .filter(|v| v.get("metadata").is_some() || v.get("files").is_some())
+        })
+        .unwrap_or(json);
+    let meta = root.get("metadata").unwrap_or(root);
+
+    let mission = ["mission", "missions"]
+        .iter()
+        .find_map(|k| meta.get(*k))
+        .filter(|v| !v.is_null())
+        .cloned();
+    let mission_name = mission.as_ref().and_then(|m| match m {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(a) => a.first().and_then(|x| s_pick(x, &["name", "mission name", "identifier"])),
+        _ => s_pick(m, &["name", "mission name", "identifier"]),
+    });
+
+    let assays = match meta.get("assays").and_then(Value::as_array) {
+        Some(list) => list
+            .iter()
+            .map(|a| OsdrAssay {
+                measurement: s_pick(a, &["measurement", "measurement type", "study assay measurement type"]),
+                technology: s_pick(a, &["technology", "technology type", "study assay technology type"]),
+                platform: s_pick(a, &["platform", "technology platform", "study assay technology platform"]),
+            })
+            .collect(),
+        None => {
+            let column = |key: &str| -> Vec<String> {
+                match meta.get(key) {
+                    Some(Value::String(s)) => vec![s.clone()],
+                    Some(Value::Array(a)) => {
+                        a.iter().map(|x| x.as_str().unwrap_or_default().to_string()).collect()
+                    }
+                    _ => Vec::new(),
+                }
+            };
+            let m = column("study assay measurement type");
+            let t = column("study assay technology type");
+            let p = column("study assay technology platform");
+            let cell = |c: &[String], i: usize| c.get(i).filter(|s| !s.is_empty()).cloned();
+            (0..m.len().max(t.len()).max(p.len()))
+                .map(|i| OsdrAssay {
+                    measurement: cell(&m, i),
+                    technology: cell(&t, i),
+                    platform: cell(&p, i),
+                })
+                .collect()
+        }
+    };
+
+    let mut files = BTreeMap::new();
+    let file_entry = |name: String, v: &Value| OsdrFile {
+        url: s_pick(v, &["URL", "url", "REST_URL", "remote_url"]),
+        size_bytes: ["size", "file_size", "size_bytes"]
+            .iter()
+            .find_map(|k| v.get(*k))
+            .and_then(|x| x.as_i64().or_else(|| x.as_str()?.parse().ok())),
+        category: s_pick(v, &["category", "type"]),
+        name,
+    };
+    match root.get("files") {
+        Some(Value::Object(map)) => {
+            for (name, v) in map {
+                files.insert(name.clone(), file_entry(name.clone(), v));
+            }
+        }
+        Some(Value::Array(list)) => {
+            for v in list {
+                if let Some(name) = s_pick(v, &["file_name", "name", "filename"]) {
+                    files.insert(name.clone(), file_entry(name, v));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    OsdrDetail {
+        dataset_id: dataset_id.to_string(),
+        source_updated_at: None,
+        enriched_at: Utc::now(),
+        mission_name,
+        mission,
+        organisms: names(meta, &["organism", "organisms", "study organism"]),
+        factors: names(meta, &["study factor name", "factors", "study factors"]),
+        assays,
+        files: files.into_values().collect(),
+        raw: json.clone(),
+    }
+}
+
+/// Sorted, de-duplicated names under the first present key: a string, or an
+/// array of strings / objects with a `name`.
+fn names(v: &Value, keys: &[&str]) -> Vec<String> {
+    let mut out = BTreeSet::new();
+    match keys.iter().find_map(|k| v.get(*k)) {
+        Some(Value::String(s)) => {
+            out.insert(s.trim().to_string());
+        }
+        Some(Value::Array(list)) => {
+            for x in list {
+                if let Some(s) = x.as_str().map(str::to_string).or_else(|| s_pick(x, &["name"])) {
+                    out.insert(s.trim().to_string());
+                }
+            }
+        }
+        _ => {}
+    }
+    out.remove("");
+    out.into_iter().collect()
+}
@@ -0,0 +1,78 @@
+//! Where the Sun is, offline: its geocentric position, the subsolar point,
+//! the day-night terminator and the Earth's shadow at a satellite.
+
+use chrono::{DateTime, Utc};
+
+use crate::domain::Illumination;
+use crate::orbit::{dot, gmst, julian_date, norm, sub, WGS84_A_KM};
+
+const AU_KM: f64 = 149_597_870.7;
+const SUN_RADIUS_KM: f64 = 696_000.0;
+
+/// Geocentric position of the Sun in km (TEME, to the precision used here),
+/// from the low-precision solar coordinates of the Astronomical Almanac:
+/// about 0.01° until 2050.
+pub fn sun_position(at: DateTime<Utc>) -> [f64; 3] {
+    let t = (julian_date(at) - 2_451_545.0) / 36_525.0;
+    let mean_lon = 280.460 + 36_000.771 * t;
+    let m = (357.529_109_2 + 35_999.050_34 * t).to_radians();
+    let lon = (mean_lon + 1.914_666_471 * m.sin() + 0.019_994_643 * (2.0 * m).sin()).to_radians();
+    let dist = 1.000_140_612 - 0.016_708_617 * m.cos() - 0.000_139_589 * (2.0 * m).cos();
+    let eps = (23.439_291 - 0.013_004_2 * t).to_radians();
+    let r = dist * AU_KM;
+    [r * lon.cos(), r * eps.cos() * lon.sin(), r * eps.sin() * lon.sin()]
+}
+
+/// The Earth's shadow at `position` with the Sun at `sun`, both TEME km:
+/// the conical model, comparing the apparent radii of the Sun and the Earth
+/// with the angle between their centres as seen from the satellite.
+pub fn illumination(position: [f64; 3], sun: [f64; 3]) -> Illumination {
+    let to_sun = sub(sun, position);
+    let (d_sun, d_earth) = (norm(to_sun), norm(position));
+    let sun_radius = (SUN_RADIUS_KM / d_sun).asin();
+    let earth_radius = (WGS84_A_KM / d_earth).min(1.0).asin();
+    let apart = (-dot(position, to_sun) / (d_sun * d_earth)).clamp(-1.0, 1.0).acos();
+    if apart >= sun_radius + earth_radius {
+        Illumination::Sunlit
+    } else if apart <= earth_radius - sun_radius {
+        Illumination::Umbra
+    } else {
+        Illumination::Penumbra
+    }
+}
+
+/// Latitude and longitude in degrees where the Sun is at the zenith.
+pub fn subsolar_point(at: DateTime<Utc>) -> (f64, f64) {
+    let s = sun_position(at);
+    let lat = (s[2] / norm(s)).asin().to_degrees();
+    let lon = (s[1].atan2(s[0]) - gmst(at)).to_degrees();
+    (lat, (lon + 180.0).rem_euclid(360.0) - 180.0)
+}
+
+/// The night side as a closed `[lon, lat]` ring: the terminator from 180°W
+/// to 180°E every `step` degrees, closed through the pole in darkness.
+pub fn night_ring(at: DateTime<Utc>, step: f64) -> Vec<[f64; 2]> {
+    let (lat_s, lon_s) = subsolar_point(at);
+    // at the equinoxes the terminator runs through the poles; keep it finite
+    let tan_dec = match lat_s.to_radians().tan() {
+        t if t.abs() < 1e-9 => 1e-9_f64.copysign(t),
+        t => t,
+    };
+    let n = (360.0 / step).ceil() as usize;
+    let mut ring: Vec<[f64; 2]> = (0..=n)
+        .map(|i| {
+            let lon = (-180.0 + i as f64 * step).min(180.0);
+            let lat = (-(lon - lon_s).to_radians().cos() / tan_dec).atan().to_degrees();
+            [lon, lat]
+        })
+        .collect();
+    let pole = if lat_s >= 0.0 { -90.0 } else { 90.0 };
+    ring.push([180.0, pole]);
+    ring.push([-180.0, pole]);
+    ring.push(ring[0]);
+    // counterclockwise, as RFC 7946 asks of exterior rings
+    if pole < 0.0 {
+        ring.reverse();
+    }
+    ring
+}
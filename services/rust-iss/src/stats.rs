@@ -0,0 +1,208 @@
+//! Orbit statistics from logged positions: period, daily distance and
+//! altitude, the decay rate and reboosts. Orbits are cut at ascending nodes
+//! and their mean altitude is used throughout, which averages out the
+//! ±10 km that the geodetic altitude swings through within one revolution.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::domain::{DayStats, IssPoint, IssStats, Reboost};
+use crate::services::{haversine_km, INTERPOLATION_MAX_GAP_SECONDS};
+
+/// Rise of the orbit-mean altitude, in km, that counts as a reboost.
+const REBOOST_MIN_KM: f64 = 0.5;
+/// Orbit-to-orbit rise, in km, that continues a reboost spread over
+/// several orbits.
+const REBOOST_STEP_KM: f64 = 0.05;
+/// Orbits logged with fewer positions are not averaged.
+const ORBIT_MIN_SAMPLES: usize = 8;
+/// Longest period of a near-Earth orbit, as SGP4 defines it.
+const ORBIT_MAX_SECONDS: f64 = 225.0 * 60.0;
+
+/// One revolution between consecutive ascending nodes.
+#[derive(Debug, Clone, Copy)]
+struct Orbit {
+    start: DateTime<Utc>,
+    seconds: f64,
+    altitude: f64,
+}
+
+/// Statistics of `points`, oldest first, logged between `from` and `to`.
+pub fn compute(
+    norad_id: i64,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    points: &[IssPoint],
+) -> IssStats {
+    let located: Vec<&IssPoint> =
+        points.iter().filter(|p| p.latitude.is_some() && p.longitude.is_some()).collect();
+    let linked = |i: usize| {
+        (located[i + 1].at - located[i].at).num_seconds() <= INTERPOLATION_MAX_GAP_SECONDS
+    };
+
+    // ascending nodes: the pair index and the interpolated time
+    let nodes: Vec<(usize, DateTime<Utc>)> = (0..located.len().saturating_sub(1))
+        .filter(|&i| linked(i))
+        .filter_map(|i| {
+            let (a, b) = (located[i], located[i + 1]);
+            let (lat_a, lat_b) = (a.latitude?, b.latitude?);
+            (lat_a < 0.0 && lat_b >= 0.0).then(|| {
+                let f = -lat_a / (lat_b - lat_a);
+                let span = (b.at - a.at).num_milliseconds() as f64;
+                (i, a.at + chrono::Duration::milliseconds((span * f) as i64))
+            })
+        })
+        .collect();
+
+    let orbits: Vec<Orbit> = nodes
+        .windows(2)
+        .filter_map(|w| {
+            let ((i, start), (j, end)) = (w[0], w[1]);
+            let seconds = (end - start).num_milliseconds() as f64 / 1000.0;
+            if seconds > ORBIT_MAX_SECONDS || !(i..=j).all(linked) {
+                return None;
+            }
+            let alts: Vec<f64> = located[i + 1..=j].iter().filter_map(|p| p.altitude).collect();
+            (alts.len() >= ORBIT_MIN_SAMPLES).then(|| Orbit {
+                start,
+                seconds,
+                altitude: alts.iter().sum::<f64>() / alts.len() as f64,
+            })
+        })
+        .collect();
+
+    let period = median(orbits.iter().map(|o| o.seconds).collect());
+    let (reboosts, segments) = reboosts(&orbits);
+    IssStats {
+        norad_id,
+        from,
+        to,
+        samples: points.len(),
+        period_minutes: period.map(|s| s / 60.0),
+        orbits_per_day: period.map(|s| 86_400.0 / s),
+        mean_altitude: mean(orbits.iter().map(|o| o.altitude)),
+        altitude_trend_m_per_day: trend(&segments),
+        reboosts,
+        days: days(&located, &nodes, linked),
+    }
+}
+
+/// Reboosts, and the runs of orbits between them over which the altitude
+/// only decays.
+fn reboosts(orbits: &[Orbit]) -> (Vec<Reboost>, Vec<&[Orbit]>) {
+    let mut found = Vec::new();
+    let mut segments = Vec::new();
+    let (mut segment_start, mut i) = (0, 0);
+    while i + 1 < orbits.len() {
+        // climb while each orbit is higher than the one before
+        let mut j = i;
+        let climbs = |j: usize| orbits[j + 1].altitude - orbits[j].altitude > REBOOST_STEP_KM;
+        while j + 1 < orbits.len() && climbs(j) {
+            j += 1;
+        }
+        let rise = orbits[j].altitude - orbits[i].altitude;
+        if rise >= REBOOST_MIN_KM {
+            found.push(Reboost {
+                at: orbits[i + 1].start,
+                altitude_before: orbits[i].altitude,
+                altitude_after: orbits[j].altitude,
+                delta_m: rise * 1000.0,
+            });
+            segments.push(&orbits[segment_start..=i]);
+            segment_start = j;
+        }
+        i = j.max(i + 1);
+    }
+    segments.push(&orbits[segment_start..]);
+    (found, segments)
+}
+
+/// Least-squares slope of the orbit-mean altitude in m/day, shared by all
+/// segments but with an intercept of its own for each.
+fn trend(segments: &[&[Orbit]]) -> Option<f64> {
+    let (mut cov, mut var) = (0.0, 0.0);
+    for seg in segments.iter().filter(|s| s.len() >= 2) {
+        let day = |o: &Orbit| o.start.timestamp() as f64 / 86_400.0;
+        let t_mean = seg.iter().map(day).sum::<f64>() / seg.len() as f64;
+        let h_mean = seg.iter().map(|o| o.altitude).sum::<f64>() / seg.len() as f64;
+        for o in seg.iter() {
+            cov += (day(o) - t_mean) * (o.altitude - h_mean);
+            var += (day(o) - t_mean).powi(2);
+        }
+    }
+    (var > 0.0).then(|| cov / var * 1000.0)
+}
+
+#[derive(Default)]
+struct Day {
+    samples: usize,
+    covered_seconds: f64,
+    ascending_nodes: usize,
+    distance_km: Option<f64>,
+    ground_distance_km: f64,
+    altitudes: Vec<f64>,
+}
+
+/// Per UTC day; a stretch between two positions counts for the day it
+/// starts in.
+fn days(
+    located: &[&IssPoint],
+    nodes: &[(usize, DateTime<Utc>)],
+    linked: impl Fn(usize) -> bool,
+) -> Vec<DayStats> {
+    let mut days: BTreeMap<NaiveDate, Day> = BTreeMap::new();
+    for (i, p) in located.iter().enumerate() {
+        let day = days.entry(p.at.date_naive()).or_default();
+        day.samples += 1;
+        day.altitudes.extend(p.altitude);
+        if i + 1 == located.len() || !linked(i) {
+            continue;
+        }
+        let next = located[i + 1];
+        let seconds = (next.at - p.at).num_milliseconds() as f64 / 1000.0;
+        day.covered_seconds += seconds;
+        if let (Some(lat1), Some(lon1), Some(lat2), Some(lon2)) =
+            (p.latitude, p.longitude, next.latitude, next.longitude)
+        {
+            day.ground_distance_km += haversine_km(lat1, lon1, lat2, lon2);
+        }
+        if let (Some(v1), Some(v2)) = (p.velocity, next.velocity) {
+            // km/h
+            let km = (v1 + v2) / 2.0 / 3600.0 * seconds;
+            day.distance_km = Some(day.distance_km.unwrap_or(0.0) + km);
+        }
+    }
+    for (_, at) in nodes {
+        days.entry(at.date_naive()).or_default().ascending_nodes += 1;
+    }
+    days.into_iter()
+        .map(|(date, d)| DayStats {
+            date,
+            samples: d.samples,
+            covered_hours: (d.covered_seconds / 3600.0).min(24.0),
+            ascending_nodes: d.ascending_nodes,
+            distance_km: d.distance_km,
+            ground_distance_km: d.ground_distance_km,
+            mean_altitude: mean(d.altitudes.into_iter()),
+        })
+        .collect()
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, n) = values.fold((0.0, 0usize), |(s, n), v| (s + v, n + 1));
+    (n > 0).then(|| sum / n as f64)
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
@@ -1,22 +1,67 @@
 #[cfg(test)]
 mod tests {
-    use chrono::{TimeZone, Utc};
+    use chrono::{DateTime, TimeZone, Utc};
     use serde_json::json;
 
-    use crate::services::{haversine_km, normalize_osdr_items, s_pick, t_pick};
+    use std::time::Duration;
+
+    use crate::config::{
+        parse_duration, parse_geofences, parse_osdr_field_map, parse_retention_policies,
+        parse_tracked_satellites,
+    };
+    use crate::domain::{
+        GeofenceTransition, Illumination, IssPoint, OsdrFieldMap, SearchLang, TrackFormat,
+        TrackMode, TrendSeries,
+    };
+    use crate::orbit::{
+        geodetic, magnitude, parse_tle_text, teme_from_geodetic, CircularOrbit, Observer,
+        OrbitError, Sgp4, Tle, TleError, WGS84_A_KM,
+    };
+    use crate::repo::{
+        add_months, json_diff, like_prefix, month_start, osdr_diff, osdr_search_body, parse_partition_name, partition_name, payload_hash,
+        plan_osdr_batch,
+    };
+    use crate::solar::{illumination, night_ring, subsolar_point, sun_position};
+    use crate::services::{
+        haversine_km, interpolate, lttb, normalize_osdr_items, parse_osdr_detail, s_pick, s_pointer,
+        sources_with_own_policy, t_pointer,
+    };
+    use crate::stats;
+    use crate::track::{render, split_track};
+    use crate::timeparse::{check_format, EpochUnit, TimeError, TimeParser};
+
+    /// ISS elements from the SGP4 validation set, epoch 2008-09-20 12:25:40 UTC.
+    pub(crate) const ISS_TLE: &str = "ISS (ZARYA)
+1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927
+2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537
+";
+
+    /// A logged position without velocity or visibility.
+    pub(crate) fn point(
+        at: DateTime<Utc>,
+        latitude: Option<f64>,
+        longitude: Option<f64>,
+        altitude: Option<f64>,
+    ) -> IssPoint {
+        IssPoint { at, latitude, longitude, altitude, velocity: None, visibility: None }
+    }
+
+    fn normalize(v: &serde_json::Value, map: &OsdrFieldMap) -> Vec<crate::domain::OsdrUpsert> {
+        normalize_osdr_items(v, map, &TimeParser::default(), &mut Vec::new())
+    }
 
     #[test]
     fn pick_string_and_time() {
         let v = json!({"id":"OSD-1","updated_at":"2025-01-01T00:00:00Z"});
         assert_eq!(s_pick(&v, &["id"]), Some("OSD-1".to_string()));
-        let t = t_pick(&v, &["updated_at"]).unwrap();
+        let t = t_pointer(&v, &["/updated_at".into()], &TimeParser::default()).unwrap().unwrap();
         assert_eq!(t, Utc.with_ymd_and_hms(2025,1,1,0,0,0).unwrap());
     }
 
     #[test]
     fn normalize_osdr_uses_business_key() {
         let v = json!([{"dataset_id":"OSD-1","title":"x","status":"ok","updated":"2025-01-02T03:04:05Z"}]);
-        let items = normalize_osdr_items(&v);
+        let items = normalize(&v, &OsdrFieldMap::default());
         assert_eq!(items.len(), 1);
         assert_eq!(items[0].dataset_id.as_deref(), Some("OSD-1"));
         assert_eq!(items[0].title.as_deref(), Some("x"));
@@ -29,6 +74,12 @@ mod tests {
         assert!(d > 0.0);
     }
 
+    #[test]
+    fn dataset_id_is_encoded_as_one_path_segment() {
+        assert_eq!(crate::clients::path_segment("OSD-379.v2_a~b"), "OSD-379.v2_a~b");
+        assert_eq!(crate::clients::path_segment("../x?y#z é"), "..%2Fx%3Fy%23z%20%C3%A9");
+    }
+
     #[test]
     fn s_pick_prefers_first_key_and_numbers() {
         let v = json!({"b":"second","a":"first","n":42});
@@ -37,14 +88,91 @@ mod tests {
     }
 
     #[test]
-    fn t_pick_handles_unix_timestamp_and_fallback_format() {
+    fn t_pointer_handles_unix_timestamp_and_fallback_format() {
         let v = json!({"ts": 1_600_000_000, "dt": "2025-05-06 07:08:09"});
-        let t1 = t_pick(&v, &["ts"]).unwrap();
+        let parser = TimeParser::default();
+        let t1 = t_pointer(&v, &["/ts".into()], &parser).unwrap().unwrap();
         assert_eq!(t1, Utc.timestamp_opt(1_600_000_000, 0).unwrap());
-        let t2 = t_pick(&v, &["dt"]).unwrap();
+        let t2 = t_pointer(&v, &["/dt".into()], &parser).unwrap().unwrap();
         assert_eq!(t2, Utc.with_ymd_and_hms(2025, 5, 6, 7, 8, 9).unwrap());
     }
 
+    #[test]
+    fn time_parser_accepts_upstream_variants() {
+        let p = TimeParser::default();
+        let at = |s: &str| p.parse_str(s).unwrap();
+        let utc = |y, mo, d, h, mi, s| Utc.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap();
+        assert_eq!(at("2025-01-01T12:34Z"), utc(2025, 1, 1, 12, 34, 0));
+        assert_eq!(at("2025-01-01T12:34:56+03:00"), utc(2025, 1, 1, 9, 34, 56));
+        assert_eq!(at("2025-01-01 12:34:56-0130"), utc(2025, 1, 1, 14, 4, 56));
+        assert_eq!(at("Wed, 01 Jan 2025 12:00:00 +0100"), utc(2025, 1, 1, 11, 0, 0));
+        assert_eq!(at("2025-01-01"), utc(2025, 1, 1, 0, 0, 0));
+        assert_eq!(at("20250101"), utc(2025, 1, 1, 0, 0, 0));
+        assert_eq!(at("2025-01-01 12:34:56.250").timestamp_subsec_millis(), 250);
+
+        let secs = utc(2023, 11, 14, 22, 13, 20);
+        for v in [
+            json!(1_700_000_000),
+            json!(1_700_000_000_000i64),
+            json!(1_700_000_000_000_000i64),
+            json!(1_700_000_000_000_000_000i64),
+            json!("1700000000000"),
+        ] {
+            assert_eq!(p.parse_value(&v).unwrap(), secs, "{v}");
+        }
+        let half = p.parse_value(&json!(1_700_000_000.5)).unwrap();
+        assert_eq!((half.timestamp(), half.timestamp_subsec_millis()), (1_700_000_000, 500));
+        assert_eq!(EpochUnit::detect(1.7e12), EpochUnit::Millis);
+
+        assert_eq!(p.parse_str("yesterday"), Err(TimeError::NoMatch("yesterday".into())));
+        assert_eq!(p.parse_str(" "), Err(TimeError::Empty));
+        assert_eq!(p.parse_value(&json!(true)), Err(TimeError::Type("a boolean")));
+        let err = p.parse_value(&json!(1e300)).unwrap_err();
+        assert_eq!(err.to_string(), "epoch 1e300 read as nanoseconds is out of range");
+
+        let custom = TimeParser::new(["%d.%m.%Y %H:%M".to_string()]);
+        assert_eq!(custom.parse_str("03.02.2025 10:00").unwrap(), utc(2025, 2, 3, 10, 0, 0));
+        assert!(p.parse_str("03.02.2025 10:00").is_err());
+        assert!(check_format("%Y-%Q").is_err());
+
+        let mut rejected = Vec::new();
+        let v = json!([{"id": "A", "updated": "soon", "updated_at": null}, {"id": "B", "updated": 0}]);
+        let items = normalize_osdr_items(&v, &OsdrFieldMap::default(), &p, &mut rejected);
+        assert_eq!(items[0].updated_at, None);
+        assert_eq!(items[1].updated_at, Some(utc(1970, 1, 1, 0, 0, 0)));
+        assert_eq!(rejected, [(0, TimeError::NoMatch("soon".into()))]);
+    }
+
+    #[test]
+    fn normalize_osdr_follows_configured_pointers() {
+        let map = parse_osdr_field_map(
+            r#"{"items": ["/data/studies"],
+                "dataset_id": ["/meta/accession", "/id"],
+                "updated_at": ["/meta/modified"],
+                "time_formats": ["%d.%m.%Y", "%d/%m/%Y %H:%M %z"]}"#,
+        )
+        .unwrap();
+        assert_eq!(map.title, OsdrFieldMap::default().title);
+        let v = json!({"data": {"studies": [
+            {"meta": {"accession": "OSD-9", "modified": "03.02.2025"}, "title": "nested"},
+            {"id": 7, "meta": {"modified": "03/02/2025 10:00 +0300"}},
+            {"meta": {"accession": ""}}
+        ]}});
+        let items = normalize(&v, &map);
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].dataset_id.as_deref(), Some("OSD-9"));
+        assert_eq!(items[0].title.as_deref(), Some("nested"));
+        assert_eq!(items[0].updated_at, Some(Utc.with_ymd_and_hms(2025, 2, 3, 0, 0, 0).unwrap()));
+        assert_eq!(items[1].dataset_id.as_deref(), Some("7"));
+        assert_eq!(items[1].updated_at, Some(Utc.with_ymd_and_hms(2025, 2, 3, 7, 0, 0).unwrap()));
+        assert_eq!(items[2].dataset_id, None);
+        assert_eq!(s_pointer(&v, &["/data/studies/0/meta/accession".into()]).as_deref(), Some("OSD-9"));
+
+        assert!(parse_osdr_field_map(r#"{"title": ["name"]}"#).is_err());
+        assert!(parse_osdr_field_map(r#"{"dataset_id": []}"#).is_err());
+        assert!(parse_osdr_field_map(r#"{"titel": ["/name"]}"#).is_err());
+    }
+
     #[test]
     fn normalize_osdr_accepts_results_and_items_arrays() {
         let data = json!({
@@ -55,7 +183,7 @@ mod tests {
             {"id":"I1","name":"item","state":"ready","modified":"2025-02-02T00:00:00Z"}
           ]
         });
-        let mut items = normalize_osdr_items(&data);
+        let mut items = normalize(&data, &OsdrFieldMap::default());
         items.sort_by_key(|x| x.dataset_id.clone());
         assert_eq!(items.len(), 2);
         assert_eq!(items[0].dataset_id.as_deref(), Some("I1"));
@@ -67,7 +195,7 @@ mod tests {
     #[test]
     fn normalize_osdr_keeps_raw_payload() {
         let src = json!({"id":"X","title":"t","updated_at":"2025-03-04T05:06:07Z"});
-        let items = normalize_osdr_items(&src);
+        let items = normalize(&src, &OsdrFieldMap::default());
         assert_eq!(items.len(), 1);
         assert_eq!(items[0].raw["id"], "X");
     }
@@ -91,5 +219,2062 @@ mod tests {
         let d = haversine_km(55.7558, 37.6176, 40.7128, -74.0060);
         assert!(d > 7400.0 && d < 7600.0);
     }
-}
 
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1h"), Some(Duration::from_secs(3_600)));
+        assert_eq!(parse_duration("30d"), Some(Duration::from_secs(30 * 86_400)));
+        assert_eq!(parse_duration("0d"), None);
+        assert_eq!(parse_duration("5y"), None);
+    }
+
+    #[test]
+    fn retention_policies_per_table_and_source() {
+        let p = parse_retention_policies(
+            "iss_fetch_log:raw=30d,bucket=1h; space_cache:raw=30d; space_cache/APOD:max=365d",
+        )
+        .unwrap();
+        assert_eq!(p.len(), 3);
+        assert_eq!(p[0].bucket, Some(Duration::from_secs(3_600)));
+        assert_eq!(p[2].source.as_deref(), Some("apod"));
+        assert_eq!(p[2].target(), "space_cache/apod");
+        assert_eq!(sources_with_own_policy(&p, &p[1]), vec!["apod".to_string()]);
+        assert!(sources_with_own_policy(&p, &p[0]).is_empty());
+    }
+
+    #[test]
+    fn retention_policies_reject_bad_specs() {
+        assert!(parse_retention_policies("osdr_items:raw=1d").is_err());
+        assert!(parse_retention_policies("iss_fetch_log/x:raw=1d").is_err());
+        assert!(parse_retention_policies("iss_fetch_log:bucket=1h").is_err());
+        assert!(parse_retention_policies("space_cache:keep=1d").is_err());
+        assert!(parse_retention_policies("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn tracked_satellites_spec() {
+        let poll = "https://example.test/sat/{id}";
+        let sats = parse_tracked_satellites(
+            "25544:name=ISS,mode=poll; 48274:name=CSS,every=5m ;20580",
+            120,
+            "https://iss.test/now",
+            poll,
+        )
+        .unwrap();
+        assert_eq!(sats.len(), 3);
+        assert_eq!(sats[0].mode, TrackMode::Poll);
+        assert_eq!(sats[0].poll_url.as_deref(), Some("https://iss.test/now"));
+        assert_eq!((sats[1].name.as_str(), sats[1].every_seconds), ("CSS", 300));
+        assert_eq!(sats[1].mode, TrackMode::Propagate);
+        assert_eq!(sats[1].poll_url, None);
+        assert_eq!((sats[2].name.as_str(), sats[2].every_seconds), ("NORAD 20580", 120));
+        let polled = parse_tracked_satellites("20580:mode=poll", 60, "", poll).unwrap();
+        assert_eq!(polled[0].poll_url.as_deref(), Some("https://example.test/sat/20580"));
+
+        for bad in ["", "iss", "0", "1;1", "25544:mode=fly", "25544:every=soon", "25544:name"] {
+            assert!(parse_tracked_satellites(bad, 60, "", poll).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn search_cursor_keeps_the_exact_rank() {
+        use crate::domain::OsdrSearchCursor;
+        use crate::services::{decode_cursor, encode_cursor};
+        for rank in [0.0, 0.1, f32::from_bits(0x3dcc_ccce), 1.0e-30, f32::MAX] {
+            let cursor = OsdrSearchCursor { rank, id: 7 };
+            let back: OsdrSearchCursor = decode_cursor(&encode_cursor(&cursor)).unwrap();
+            assert_eq!(back.rank.to_bits(), rank.to_bits());
+        }
+        let text = hex::decode(encode_cursor(&OsdrSearchCursor { rank: 1.0, id: 7 })).unwrap();
+        assert_eq!(String::from_utf8(text).unwrap(), r#"{"rank":1065353216,"id":7}"#);
+    }
+
+    #[test]
+    fn payload_hash_ignores_key_order() {
+        let a: serde_json::Value = serde_json::from_str(r#"{"b":1,"a":{"y":2,"x":3}}"#).unwrap();
+        let b: serde_json::Value = serde_json::from_str(r#"{"a":{"x":3,"y":2},"b":1}"#).unwrap();
+        assert_eq!(payload_hash(&a), payload_hash(&b));
+        assert_ne!(payload_hash(&a), payload_hash(&json!({"b":2})));
+        assert_eq!(payload_hash(&a).len(), 64);
+        // the hashed text is fixed: sorted keys, no whitespace, arrays in order
+        use sha2::{Digest, Sha256};
+        let nested = json!({"b": [{"d": 1, "c": 2}], "a": null});
+        let expected = hex::encode(Sha256::digest(br#"{"a":null,"b":[{"c":2,"d":1}]}"#));
+        assert_eq!(payload_hash(&nested), expected);
+    }
+
+    #[test]
+    fn monthly_partition_names_roundtrip() {
+        let t = Utc.with_ymd_and_hms(2025, 12, 17, 13, 0, 0).unwrap();
+        let m = month_start(t);
+        assert_eq!(m, Utc.with_ymd_and_hms(2025, 12, 1, 0, 0, 0).unwrap());
+        assert_eq!(add_months(m, 1), Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap());
+        assert_eq!(add_months(m, 14), Utc.with_ymd_and_hms(2027, 2, 1, 0, 0, 0).unwrap());
+        assert_eq!(partition_name(m), "iss_fetch_log_y2025m12");
+        assert_eq!(parse_partition_name("iss_fetch_log_y2025m12"), Some(m));
+        assert_eq!(parse_partition_name("iss_fetch_log_unpartitioned"), None);
+    }
+
+    #[test]
+    fn osdr_detail_from_v2_payload() {
+        let json = json!({"OSD-48": {
+            "files": {
+                "b.zip": {"URL": "https://x/b.zip", "size": 10},
+                "a.csv": {"URL": "https://x/a.csv", "size": "7"}
+            },
+            "metadata": {
+                "organism": "Mus musculus",
+                "mission": {"name": "SpaceX-4", "start date": "2014-09-21"},
+                "study factor name": ["Spaceflight", "Spaceflight", ""],
+                "study assay measurement type": ["transcription profiling", "proteomics"],
+                "study assay technology type": ["RNA Sequencing (RNA-Seq)"]
+            }
+        }});
+        let d = parse_osdr_detail("OSD-48", &json);
+        assert_eq!(d.organisms, ["Mus musculus"]);
+        assert_eq!(d.factors, ["Spaceflight"]);
+        assert_eq!(d.mission_name.as_deref(), Some("SpaceX-4"));
+        assert_eq!(d.assays.len(), 2);
+        assert_eq!(d.assays[0].technology.as_deref(), Some("RNA Sequencing (RNA-Seq)"));
+        assert_eq!(d.assays[1].technology, None);
+        let names: Vec<_> = d.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["a.csv", "b.zip"]);
+        assert_eq!(d.files[0].size_bytes, Some(7));
+    }
+
+    #[test]
+    fn osdr_detail_from_flat_payload() {
+        let json = json!({
+            "organisms": [{"name": "Homo sapiens"}, "Mus musculus"],
+            "assays": [{"measurement": "m", "platform": "p"}],
+            "files": [{"file_name": "x.txt", "url": "u"}, {"url": "nameless"}]
+        });
+        let d = parse_osdr_detail("OSD-1", &json);
+        assert_eq!(d.organisms, ["Homo sapiens", "Mus musculus"]);
+        assert_eq!(d.assays[0].platform.as_deref(), Some("p"));
+        assert_eq!(d.files.len(), 1);
+        assert_eq!(d.mission, None);
+    }
+
+    #[test]
+    fn json_diff_emits_pointer_ops() {
+        let old = json!({"a": 1, "b": {"c": [1, 2, 3]}, "d/e": "x"});
+        let new = json!({"a": 2, "b": {"c": [1]}, "f": null});
+        let mut ops = Vec::new();
+        json_diff(&old, &new, &mut String::new(), &mut ops);
+        assert_eq!(
+            ops,
+            [
+                json!({"op": "replace", "path": "/a", "old": 1, "new": 2}),
+                json!({"op": "remove", "path": "/b/c/2", "old": 3}),
+                json!({"op": "remove", "path": "/b/c/1", "old": 2}),
+                json!({"op": "remove", "path": "/d~1e", "old": "x"}),
+                json!({"op": "add", "path": "/f", "new": null}),
+            ]
+        );
+    }
+
+    #[test]
+    fn osdr_diff_skips_identical_rows() {
+        let upsert = |title: &str| crate::domain::OsdrUpsert {
+            dataset_id: Some("OSD-1".into()),
+            title: Some(title.into()),
+            status: None,
+            updated_at: None,
+            raw: json!({"title": title}),
+        };
+        let created = osdr_diff(None, &upsert("a")).unwrap();
+        assert_eq!(created["fields"]["title"], json!({"old": null, "new": "a"}));
+        assert_eq!(created["raw"][0]["path"], "");
+
+        let stored = crate::domain::OsdrItem {
+            id: 1,
+            dataset_id: Some("OSD-1".into()),
+            title: Some("a".into()),
+            status: None,
+            updated_at: None,
+            inserted_at: Utc::now(),
+            removed_at: None,
+            raw: json!({"title": "a"}),
+        };
+        assert_eq!(osdr_diff(Some(&stored), &upsert("a")), None);
+        let diff = osdr_diff(Some(&stored), &upsert("b")).unwrap();
+        assert_eq!(diff["fields"].as_object().unwrap().len(), 1);
+        assert_eq!(diff["raw"], json!([{"op": "replace", "path": "/title", "old": "a", "new": "b"}]));
+    }
+
+    #[test]
+    fn plan_osdr_batch_classifies_and_rejects_bad_ids() {
+        use crate::domain::{OsdrChange, OsdrUpsert};
+        use std::collections::HashMap;
+
+        let upsert = |id: Option<&str>, title: &str| OsdrUpsert {
+            dataset_id: id.map(str::to_string),
+            title: Some(title.into()),
+            status: None,
+            updated_at: None,
+            raw: json!({"title": title}),
+        };
+        let stored = |title: &str, removed: bool| crate::domain::OsdrItem {
+            id: 1,
+            dataset_id: None,
+            title: Some(title.into()),
+            status: None,
+            updated_at: None,
+            inserted_at: Utc::now(),
+            removed_at: removed.then(Utc::now),
+            raw: json!({"title": title}),
+        };
+        let old = HashMap::from([
+            ("A".to_string(), stored("a", false)),
+            ("B".to_string(), stored("b", false)),
+            ("C".to_string(), stored("c", true)),
+        ]);
+        let items = [
+            upsert(Some("A"), "a"),
+            upsert(Some("B"), "b2"),
+            upsert(Some("C"), "c"),
+            upsert(Some("D"), "d"),
+        ];
+        let (changes, writes) = plan_osdr_batch(&old, &items).unwrap();
+        assert_eq!(
+            changes,
+            [OsdrChange::Unchanged, OsdrChange::Updated, OsdrChange::Restored, OsdrChange::Created]
+        );
+        let ids: Vec<_> = writes.iter().map(|w| w.dataset_id).collect();
+        assert_eq!(ids, ["B", "C", "D"]);
+
+        assert!(plan_osdr_batch(&old, &[upsert(None, "x")]).is_err());
+        assert!(plan_osdr_batch(&old, &[upsert(Some("D"), "x"), upsert(Some("D"), "y")]).is_err());
+    }
+
+    #[test]
+    fn osdr_search_body_picks_metadata_fields() {
+        let raw = json!({
+            "title": "ignored here",
+            "Study Description": "Rodent research on bone loss",
+            "characteristics": {"organism": ["Mus musculus", "Mus musculus"]},
+            "study assay technology type": "RNA Sequencing",
+            "accession": "OSD-48"
+        });
+        assert_eq!(
+            osdr_search_body(&raw),
+            "Rodent research on bone loss\nMus musculus\nRNA Sequencing"
+        );
+        assert_eq!(SearchLang::detect("мыши кости"), SearchLang::Russian);
+        assert_eq!(SearchLang::detect("mouse bone"), SearchLang::English);
+    }
+
+    #[test]
+    fn sgp4_matches_reference_vectors() {
+        // Spacetrack Report #3 test case; values from Vallado's WGS-72 verification run
+        let tle = Tle::parse(
+            None,
+            "1 88888U          80275.98708465  .00073094  13844-3  66816-4 0    87",
+            "2 88888  72.8435 115.9689 0086731  52.6988 110.5714 16.05824518  1058",
+        )
+        .unwrap();
+        assert_eq!(tle.norad_id, 88888);
+        assert!((tle.bstar - 0.66816e-4).abs() < 1e-12);
+        let sgp4 = Sgp4::new(&tle).unwrap();
+        for (t, r, v) in [
+            (
+                0.0,
+                [2328.96975262, -5995.22051338, 1719.97297192],
+                [2.91207328, -0.98341796, -7.09081621],
+            ),
+            (
+                360.0,
+                [2456.10706533, -6071.93855503, 1222.89768554],
+                [2.67939004, -0.44829081, -7.22879215],
+            ),
+        ] {
+            let s = sgp4.propagate(t).unwrap();
+            for i in 0..3 {
+                assert!((s.position[i] - r[i]).abs() < 1e-6, "t={t} r{i}={}", s.position[i]);
+                assert!((s.velocity[i] - v[i]).abs() < 1e-8, "t={t} v{i}={}", s.velocity[i]);
+            }
+        }
+
+        let (sets, errors) = parse_tle_text(ISS_TLE);
+        assert!(errors.is_empty());
+        assert_eq!(sets[0].name.as_deref(), Some("ISS (ZARYA)"));
+        let epoch = Utc.with_ymd_and_hms(2008, 9, 20, 12, 25, 40).unwrap();
+        assert_eq!((sets[0].epoch - epoch).num_milliseconds(), 104);
+        let state = Sgp4::new(&sets[0]).unwrap().at(sets[0].epoch).unwrap();
+        let (lat, lon, alt) = geodetic(state.position, sets[0].epoch);
+        assert!(lat.abs() <= 51.7 && (-180.0..=180.0).contains(&lon), "{lat} {lon}");
+        assert!((330.0..370.0).contains(&alt), "{alt}");
+        assert!((7.6..7.8).contains(&state.speed_kms()));
+
+        let broken = ISS_TLE.replace("2927", "2926");
+        let (sets, errors) = parse_tle_text(&format!("{broken}{}", ISS_TLE.trim_start_matches("ISS (ZARYA)\n")));
+        assert_eq!(sets.len(), 1);
+        assert_eq!(errors, [TleError::Checksum { line: 1, expected: 7, found: 6 }]);
+
+        let geo = Tle::parse(
+            None,
+            "1 28626U 05008A   06176.46683397 -.00000205  00000-0  10000-3 0  2190",
+            "2 28626   0.0019 286.9433 0000335  13.7918  55.6504  1.00270176  4891",
+        )
+        .unwrap();
+        assert!(matches!(Sgp4::new(&geo), Err(OrbitError::DeepSpace(_))));
+    }
+
+    #[test]
+    fn sun_shadow_and_circular_fit() {
+        // March equinox 2024: the Sun is close to the +x axis
+        let sun = sun_position(Utc.with_ymd_and_hms(2024, 3, 20, 3, 6, 0).unwrap());
+        let d = (sun[0] * sun[0] + sun[1] * sun[1] + sun[2] * sun[2]).sqrt();
+        assert!((d / 149_597_870.7 - 0.996).abs() < 0.002, "{d}");
+        assert!(sun[0] / d > 0.9999, "{sun:?}");
+        assert_eq!(illumination([7000.0, 0.0, 0.0], sun), Illumination::Sunlit);
+        assert_eq!(illumination([-7000.0, 0.0, 0.0], sun), Illumination::Umbra);
+        assert_eq!(illumination([-7000.0, 0.0, 6500.0], sun), Illumination::Sunlit);
+        // on the line grazing the limb: half the solar disc is hidden
+        let sun = [149_597_870.7, 0.0, 0.0];
+        let x = (6778.0_f64.powi(2) - WGS84_A_KM.powi(2)).sqrt();
+        assert_eq!(illumination([-x, WGS84_A_KM, 0.0], sun), Illumination::Penumbra);
+        assert_eq!(illumination([-x, WGS84_A_KM - 200.0, 0.0], sun), Illumination::Umbra);
+        assert_eq!(Illumination::Penumbra.visibility(), "daylight");
+
+        let at = Utc.with_ymd_and_hms(2008, 9, 20, 13, 0, 0).unwrap();
+        let r = teme_from_geodetic(51.2, -120.5, 410.0, at);
+        let (lat, lon, alt) = geodetic(r, at);
+        assert!((lat - 51.2).abs() < 1e-9 && (lon + 120.5).abs() < 1e-9 && (alt - 410.0).abs() < 1e-6);
+
+        let (tles, _) = parse_tle_text(ISS_TLE);
+        let sgp4 = Sgp4::new(&tles[0]).unwrap();
+        let pos = |m: i64| {
+            let t = at + chrono::Duration::minutes(m);
+            (t, sgp4.at(t).unwrap().position)
+        };
+        let fit = CircularOrbit::fit(pos(0), pos(10)).unwrap();
+        for m in [10, 40, 90] {
+            let (t, r) = pos(m);
+            let f = fit.at(t);
+            let miss = ((f[0] - r[0]).powi(2) + (f[1] - r[1]).powi(2) + (f[2] - r[2]).powi(2)).sqrt();
+            assert!(miss < 150.0, "{m} min: {miss} km");
+        }
+        assert!((0.95..1.05).contains(&fit.revolutions(pos(10 + 92).0)));
+        assert!((0.05..0.15).contains(&fit.revolutions(pos(0).0)));
+        assert!(CircularOrbit::fit(pos(0), pos(0)).is_none());
+        assert!(CircularOrbit::fit(pos(10), pos(0)).is_none());
+    }
+
+    #[test]
+    fn passes_stay_inside_the_window() {
+        use crate::services::find_passes;
+        let sgp4 = Sgp4::new(&parse_tle_text(ISS_TLE).0[0]).unwrap();
+        let obs = Observer { latitude: 55.75, longitude: 37.62, altitude: 0.0 };
+        let from = Utc.with_ymd_and_hms(2008, 9, 20, 12, 0, 0).unwrap();
+        let day = find_passes(&sgp4, &obs, from, from + chrono::Duration::days(1), None).unwrap();
+        assert!(!day.is_empty());
+        let (rise, set) = (day[0].rise.at, day[0].set.at);
+
+        // a window ending mid-pass leaves that pass out
+        let cut = find_passes(&sgp4, &obs, from, rise + chrono::Duration::seconds(60), None);
+        assert!(cut.unwrap().is_empty());
+        let whole = find_passes(&sgp4, &obs, from, set + chrono::Duration::seconds(1), None);
+        let whole = whole.unwrap();
+        assert_eq!(whole.len(), 1);
+        assert!((whole[0].rise.at - rise).num_seconds().abs() <= 1);
+        assert!(whole[0].set.at <= set + chrono::Duration::seconds(1));
+    }
+
+    #[test]
+    fn lttb_keeps_the_shape() {
+        let t0 = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let points: Vec<IssPoint> = (0..1000)
+            .map(|i| {
+                // two orbits of a 51.6° track
+                let lat = 51.6 * (i as f64 * 4.0 * std::f64::consts::PI / 1000.0).sin();
+                let alt = (i % 7 != 3).then_some(420.0);
+                point(t0 + chrono::Duration::seconds(10 * i), Some(lat), None, alt)
+            })
+            .collect();
+        let thin = lttb(points.clone(), 50, TrendSeries::Latitude);
+        assert_eq!(thin.len(), 50);
+        assert_eq!(thin[0].at, points[0].at);
+        assert_eq!(thin[49].at, points[999].at);
+        assert!(thin.windows(2).all(|w| w[0].at < w[1].at));
+        let peak = thin.iter().filter_map(|p| p.latitude).fold(f64::MIN, f64::max);
+        assert!(peak > 51.0, "{peak}");
+        let trough = thin.iter().filter_map(|p| p.latitude).fold(f64::MAX, f64::min);
+        assert!(trough < -51.0, "{trough}");
+
+        // short series come back whole, points without the value are dropped
+        assert_eq!(lttb(points[..40].to_vec(), 50, TrendSeries::Latitude).len(), 40);
+        assert_eq!(lttb(points[..40].to_vec(), 50, TrendSeries::Altitude).len(), 34);
+        assert!(lttb(points, 50, TrendSeries::Velocity).is_empty());
+    }
+
+    #[test]
+    fn track_split_at_antimeridian() {
+        let t0 = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let logged = |s: i64, lat: f64, lon: Option<f64>| {
+            point(t0 + chrono::Duration::seconds(s), Some(lat), lon, Some(420.0))
+        };
+        let points = [
+            logged(0, 10.0, Some(170.0)),
+            logged(10, 11.0, Some(178.0)),
+            logged(20, 13.0, Some(-178.0)),
+            logged(25, 13.5, None),
+            logged(30, 14.0, Some(-170.0)),
+            // and back west
+            logged(40, 15.0, Some(179.0)),
+            // past a gap in the log
+            logged(40 + 1201, 16.0, Some(-100.0)),
+            logged(40 + 1210, 17.0, Some(-99.0)),
+        ];
+        let runs = split_track(&points);
+        assert_eq!(runs.len(), 4);
+        assert_eq!(runs[3].iter().map(|v| v.longitude).collect::<Vec<_>>(), [-100.0, -99.0]);
+        let runs = split_track(&points[..6]);
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].len(), 3);
+        let (end, start) = (runs[0][2], runs[1][0]);
+        assert_eq!((end.longitude, start.longitude), (180.0, -180.0));
+        assert!((end.latitude - 12.0).abs() < 1e-9 && end.at == t0 + chrono::Duration::seconds(15));
+        assert_eq!(start.at, end.at);
+        assert_eq!(runs[1].len(), 4);
+        assert_eq!((runs[1][3].longitude, runs[2][0].longitude), (-180.0, 180.0));
+        assert!(runs.iter().flatten().all(|v| v.altitude == Some(420.0)));
+        assert!(split_track(&points[..1]).is_empty());
+
+        let geo: serde_json::Value =
+            serde_json::from_str(&render(TrackFormat::GeoJson, "ISS", 25544, &runs)).unwrap();
+        assert_eq!(geo["geometry"]["type"], "MultiLineString");
+        assert_eq!(geo["geometry"]["coordinates"][0][0], json!([170.0, 10.0, 420_000.0]));
+        assert_eq!(geo["properties"]["coordTimes"][0][0], "2024-01-01T00:00:00Z");
+        let geo: serde_json::Value =
+            serde_json::from_str(&render(TrackFormat::GeoJson, "ISS", 25544, &runs[..1])).unwrap();
+        assert_eq!(geo["geometry"]["type"], "LineString");
+
+        let kml = render(TrackFormat::Kml, "A&B", 25544, &runs);
+        assert_eq!(kml.matches("<Placemark>").count(), 3);
+        assert!(kml.contains("<name>A&amp;B (25544)</name>"), "{kml}");
+        assert!(kml.contains("<altitudeMode>absolute</altitudeMode>"), "{kml}");
+        assert!(kml.contains("<coordinates>170,10,420000 178,11,420000 180,12,420000<"), "{kml}");
+        let gpx = render(TrackFormat::Gpx, "ISS", 25544, &runs);
+        assert_eq!(gpx.matches("<trkseg>").count(), 3);
+        let first = r#"<trkpt lat="10" lon="170"><ele>420000</ele><time>2024-01-01T00:00:00Z"#;
+        assert!(gpx.contains(first), "{gpx}");
+    }
+
+    #[test]
+    fn great_circle_interpolation() {
+        let t0 = Utc.with_ymd_and_hms(2008, 9, 20, 13, 0, 0).unwrap();
+        let logged = |s: i64, lat: f64, lon: f64| {
+            let alt = 400.0 + s as f64 / 60.0;
+            point(t0 + chrono::Duration::seconds(s), Some(lat), Some(lon), Some(alt))
+        };
+        let (a, b) = (logged(0, 0.0, 175.0), logged(120, 0.0, -175.0));
+        let (lat, lon, alt, err) = interpolate(&a, &b, t0 + chrono::Duration::seconds(60)).unwrap();
+        assert!(lat.abs() < 1e-9 && (lon.abs() - 180.0).abs() < 1e-9, "{lat} {lon}");
+        assert_eq!(alt, Some(401.0));
+        assert!((1.0..5.0).contains(&err), "{err}");
+        let (_, _, _, at_sample) = interpolate(&a, &b, t0).unwrap();
+        assert!((at_sample - 1.0).abs() < 1e-9);
+        assert_eq!(interpolate(&a, &a, t0).map(|r| r.3), Some(1.0));
+        assert!(interpolate(&a, &b, t0 + chrono::Duration::seconds(121)).is_none());
+        assert!(interpolate(&a, &logged(1800, 10.0, 0.0), t0).is_none());
+
+        // against SGP4 itself: samples every two minutes, the midpoints
+        // within the reported error
+        let tle = parse_tle_text(ISS_TLE).0.remove(0);
+        let sgp4 = Sgp4::new(&tle).unwrap();
+        let sample = |s: i64| {
+            let at = t0 + chrono::Duration::seconds(s);
+            let (lat, lon, alt) = geodetic(sgp4.at(at).unwrap().position, at);
+            point(at, Some(lat), Some(lon), Some(alt))
+        };
+        for s in (0..5400).step_by(120) {
+            let truth = sample(s + 60);
+            let (lat, lon, _, err) = interpolate(&sample(s), &sample(s + 120), truth.at).unwrap();
+            let miss = haversine_km(lat, lon, truth.latitude.unwrap(), truth.longitude.unwrap());
+            assert!(miss < err, "{s}: {miss} km off, {err} km expected");
+        }
+    }
+
+    #[test]
+    fn orbit_stats_find_decay_and_reboost() {
+        let tle = parse_tle_text(ISS_TLE).0.remove(0);
+        let sgp4 = Sgp4::new(&tle).unwrap();
+        let t0 = Utc.with_ymd_and_hms(2008, 9, 20, 0, 0, 0).unwrap();
+        let to = t0 + chrono::Duration::days(2);
+        // a minute apart for two days, with an hour missing, decaying 80 m
+        // a day and raised by 1.5 km at noon on the second day
+        let points: Vec<IssPoint> = (0..2 * 1440)
+            .filter(|m| !(600..660).contains(m))
+            .map(|m| {
+                let at = t0 + chrono::Duration::minutes(m);
+                let state = sgp4.at(at).unwrap();
+                let (lat, lon, alt) = geodetic(state.position, at);
+                let days = m as f64 / 1440.0;
+                let boost = if days >= 1.5 { 1.5 } else { 0.0 };
+                IssPoint {
+                    velocity: Some(state.speed_kms() * 3600.0),
+                    ..point(at, Some(lat), Some(lon), Some(alt - 0.08 * days + boost))
+                }
+            })
+            .collect();
+        let plain: Vec<IssPoint> = points
+            .iter()
+            .map(|p| {
+                let days = (p.at - t0).num_seconds() as f64 / 86_400.0;
+                let boost = if days >= 1.5 { 1.5 } else { 0.0 };
+                IssPoint { altitude: p.altitude.map(|a| a + 0.08 * days - boost), ..p.clone() }
+            })
+            .collect();
+
+        let s = stats::compute(25544, t0, to, &points);
+        assert_eq!(s.samples, 2 * 1440 - 60);
+        let period = s.period_minutes.unwrap();
+        assert!((91.0..92.5).contains(&period), "{period}");
+        assert!((s.orbits_per_day.unwrap() - 1440.0 / period).abs() < 1e-9);
+        assert_eq!(s.reboosts.len(), 1, "{:?}", s.reboosts);
+        let r = &s.reboosts[0];
+        assert!((r.delta_m - 1500.0).abs() < 100.0, "{r:?}");
+        let noon = t0 + chrono::Duration::hours(36);
+        assert!((r.at - noon).num_minutes().abs() < 95, "{r:?}");
+        let base = stats::compute(25544, t0, to, &plain);
+        assert!(base.reboosts.is_empty(), "{:?}", base.reboosts);
+        let trend = s.altitude_trend_m_per_day.unwrap();
+        let decay = trend - base.altitude_trend_m_per_day.unwrap();
+        assert!((decay + 80.0).abs() < 10.0, "{trend} vs base");
+        let mean = s.mean_altitude.unwrap();
+        assert!((330.0..380.0).contains(&mean), "{mean}");
+
+        assert_eq!(s.days.len(), 2);
+        let day = &s.days[0];
+        assert_eq!(day.date, t0.date_naive());
+        assert_eq!(day.samples, 1380);
+        assert!((day.covered_hours - 23.0).abs() < 0.05, "{}", day.covered_hours);
+        assert!((14..=16).contains(&day.ascending_nodes), "{}", day.ascending_nodes);
+        let km = day.distance_km.unwrap();
+        assert!((km / 23.0 / 3600.0 - 7.7).abs() < 0.1, "{km}");
+        assert!(day.ground_distance_km < km, "{}", day.ground_distance_km);
+        assert!(s.days[1].covered_hours > 23.9);
+
+        let empty = stats::compute(25544, t0, to, &[]);
+        assert!(empty.period_minutes.is_none() && empty.altitude_trend_m_per_day.is_none());
+        assert!(empty.days.is_empty() && empty.reboosts.is_empty());
+    }
+
+    #[test]
+    fn subsolar_point_and_terminator() {
+        // June solstice 2024: the Sun stands over the Tropic of Cancer near noon UTC
+        let at = Utc.with_ymd_and_hms(2024, 6, 21, 12, 0, 0).unwrap();
+        let (lat, lon) = subsolar_point(at);
+        assert!((lat - 23.44).abs() < 0.05, "{lat}");
+        assert!(lon.abs() < 1.0, "{lon}");
+
+        let ring = night_ring(at, 1.0);
+        assert_eq!(ring.first(), ring.last());
+        assert!(ring.contains(&[180.0, -90.0]) && ring.contains(&[-180.0, -90.0]));
+        // the midnight meridian: polar day north of the Arctic circle
+        let midnight = ring.iter().find(|p| p[0] == 180.0 && p[1] > 0.0).unwrap();
+        assert!((midnight[1] - (90.0 - lat)).abs() < 0.5, "{midnight:?}");
+        let area: f64 = ring.windows(2).map(|w| w[0][0] * w[1][1] - w[1][0] * w[0][1]).sum();
+        assert!(area > 0.0, "exterior ring must be counterclockwise");
+    }
+
+    #[test]
+    fn observer_look_angles_and_magnitude() {
+        let at = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let obs = Observer { latitude: 0.0, longitude: 30.0, altitude: 0.0 };
+        let look = |lat, lon, alt| obs.look(teme_from_geodetic(lat, lon, alt, at), at);
+        let zenith = look(0.0, 30.0, 400.0);
+        assert!((zenith.elevation - 90.0).abs() < 1e-6 && (zenith.range - 400.0).abs() < 1e-6);
+        let north = look(5.0, 30.0, 400.0);
+        assert!(north.azimuth < 1e-6 || north.azimuth > 360.0 - 1e-6, "{north:?}");
+        assert!((look(0.0, 35.0, 400.0).azimuth - 90.0).abs() < 1e-6);
+        assert!((look(-5.0, 30.0, 400.0).azimuth - 180.0).abs() < 1e-6);
+        // 20° of arc away a 400 km orbit is below the horizon
+        assert!(look(0.0, 50.0, 400.0).elevation < 0.0);
+
+        // overhead at 1000 km, lit from the side: the standard magnitude
+        let r = [7378.137, 0.0, 0.0];
+        let obs_at = [6378.137, 0.0, 0.0];
+        let side = magnitude(-1.8, r, [0.0, 1.5e8, 0.0], obs_at);
+        assert!((side + 1.8).abs() < 1e-3, "{side}");
+        let full = magnitude(-1.8, r, [-1.5e8, 0.0, 0.0], obs_at);
+        assert!((full - side + 2.5 * std::f64::consts::PI.log10()).abs() < 1e-3, "{full}");
+        let far = magnitude(-1.8, [8378.137, 0.0, 0.0], [0.0, 1.5e8, 0.0], obs_at);
+        assert!((far - side - 5.0 * 2f64.log10()).abs() < 1e-3, "{far}");
+    }
+
+    #[test]
+    fn geofence_shapes_and_transitions() {
+        let fences = parse_geofences(
+            r#"[{"name": "station", "circle": {"lat": 55.75, "lon": 37.62, "radius_km": 1000}},
+                {"name": "box", "polygon": [[30, 50], [60, 50], [60, 70], [30, 70], [30, 50]]},
+                {"name": "chukotka", "polygon": [[170, 60], [-170, 60], [-170, 70], [170, 70]]}]"#,
+        )
+        .unwrap();
+        let [station, boxed, chukotka] = [&fences[0], &fences[1], &fences[2]];
+        assert!(station.contains(59.94, 30.31), "St Petersburg is 630 km away");
+        assert!(!station.contains(48.86, 2.35));
+        assert!(boxed.contains(55.0, 45.0));
+        assert!(!boxed.contains(45.0, 45.0) && !boxed.contains(55.0, 61.0));
+        assert!(chukotka.contains(65.0, 175.0) && chukotka.contains(65.0, -175.0));
+        assert!(!chukotka.contains(65.0, 0.0) && !chukotka.contains(65.0, 160.0));
+
+        let moves = |inside: &[&str], lat, lon| {
+            let inside: Vec<String> = inside.iter().map(|s| s.to_string()).collect();
+            crate::geofence::transitions(&fences, &inside, lat, lon)
+                .into_iter()
+                .map(|(f, k)| (f.name.as_str(), k))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            moves(&[], 55.75, 37.62),
+            [("station", GeofenceTransition::Enter), ("box", GeofenceTransition::Enter)]
+        );
+        assert!(moves(&["station", "box"], 56.0, 38.0).is_empty());
+        assert_eq!(
+            moves(&["station", "box"], 65.0, 179.0),
+            [
+                ("station", GeofenceTransition::Exit),
+                ("box", GeofenceTransition::Exit),
+                ("chukotka", GeofenceTransition::Enter)
+            ]
+        );
+
+        for bad in [
+            "{}",
+            r#"[{"name": "", "circle": {"lat": 0, "lon": 0, "radius_km": 1}}]"#,
+            r#"[{"name": "a", "circle": {"lat": 0, "lon": 0, "radius_km": 0}}]"#,
+            r#"[{"name": "a", "circle": {"lat": 91, "lon": 0, "radius_km": 1}}]"#,
+            r#"[{"name": "a", "polygon": [[0, 0], [1, 1]]}]"#,
+            r#"[{"name": "a", "square": 1}]"#,
+            r#"[{"name": "a", "circle": {"lat": 0, "lon": 0, "radius_km": 1}},
+                {"name": "a", "circle": {"lat": 1, "lon": 1, "radius_km": 1}}]"#,
+        ] {
+            assert!(parse_geofences(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn like_prefix_escapes_wildcards() {
+        assert_eq!(like_prefix("OSD-"), "OSD-%");
+        assert_eq!(like_prefix("a_b%c\\"), "a\\_b\\%c\\\\%");
+    }
+
+    mod in_memory {
+        use std::collections::HashMap;
+
+        use axum::{body::Body, http::{header, Request}, routing::get, Json, Router};
+        use chrono::{Datelike, Duration, SecondsFormat, Timelike, Utc};
+        use serde_json::{json, Value};
+        use tower::ServiceExt;
+
+        use crate::clients::UpstreamClients;
+        use crate::config::AppConfig;
+        use crate::domain::{OsdrChange, OsdrEnrichFailure, OsdrSyncMode, OsdrUpsert};
+        use crate::repo::{CacheRepo, MemoryStore, OsdrRepo, Repos};
+        use crate::{routes, AppState};
+
+        pub(crate) fn memory_state(vars: &[(&str, &str)]) -> (AppState, MemoryStore) {
+            let mut map: HashMap<String, String> = vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            map.entry("DATABASE_URL".into()).or_insert_with(|| "memory://".into());
+            let cfg = AppConfig::from_vars(|k| map.get(k).cloned()).unwrap();
+            let store = MemoryStore::new();
+            let clients = UpstreamClients::new(cfg.clone()).unwrap();
+            (AppState::new(cfg, Repos::memory(store.clone()), clients), store)
+        }
+
+        pub(crate) async fn get_json(app: &Router, uri: &str) -> Value {
+            let resp = app
+                .clone()
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice(&bytes).unwrap()
+        }
+
+        #[tokio::test]
+        async fn track_is_thinned_run_by_run() {
+            let (st, store) = memory_state(&[]);
+            let t0 = Utc::now() - Duration::hours(3);
+            // eastward over the antimeridian, then on after an hour without positions
+            for i in 0..200 {
+                let gap = if i < 100 { 0 } else { 3600 };
+                let lon = 170.0 + 0.4 * (i % 100) as f64;
+                let lon = if lon > 180.0 { lon - 360.0 } else { lon };
+                store.push_iss(
+                    t0 + Duration::seconds(10 * i + gap),
+                    "test",
+                    json!({"latitude": 0.1 * (i % 100) as f64, "longitude": lon, "altitude": 420}),
+                );
+            }
+            let runs = st.iss.track(25544, t0, Utc::now(), Some(30)).await.unwrap();
+            assert_eq!(runs.len(), 4);
+            assert_eq!((runs[0].last().unwrap().longitude, runs[1][0].longitude), (180.0, -180.0));
+            assert_eq!((runs[2].last().unwrap().longitude, runs[3][0].longitude), (180.0, -180.0));
+            let kept: usize = runs.iter().map(Vec::len).sum();
+            assert!((30..=36).contains(&kept), "{kept}");
+        }
+
+        #[tokio::test]
+        async fn trend_and_last_routes() {
+            let (st, store) = memory_state(&[]);
+            let t0 = Utc::now() - Duration::minutes(10);
+            for (i, lon) in [10.0, 12.0, 14.0].iter().enumerate() {
+                let mut payload = json!({"latitude": 50.0, "longitude": lon, "altitude": 420.0});
+                if i == 0 {
+                    payload["visibility"] = json!("eclipsed");
+                }
+                store.push_iss(t0 + Duration::minutes(2 * i as i64), "test", payload);
+            }
+            let app = routes::build_router(st);
+
+            let trend = get_json(&app, "/iss/trend?limit=10").await;
+            assert_eq!(trend["ok"], true);
+            assert_eq!(trend["data"]["points"].as_array().unwrap().len(), 3);
+            assert_eq!(trend["data"]["movement"], true);
+            assert!((trend["data"]["dt_sec"].as_f64().unwrap() - 240.0).abs() < 1e-6);
+            let points = trend["data"]["points"].as_array().unwrap();
+            assert_eq!(points[0]["visibility"], "eclipsed");
+            assert!(points[1..].iter().all(|p| p["visibility"].is_string()), "{points:?}");
+
+            let last = get_json(&app, "/last").await;
+            assert_eq!(last["data"]["payload"]["longitude"], 14.0);
+
+            let from = (t0 + Duration::minutes(1)).to_rfc3339_opts(SecondsFormat::Secs, true);
+            let range = get_json(&app, &format!("/iss/trend?from={from}&points=3")).await;
+            let data = &range["data"];
+            assert_eq!(data["points"].as_array().unwrap().len(), 2);
+            assert_eq!(data["window"]["total"], 2);
+            assert_eq!(data["window"]["series"], "latitude");
+            assert!((data["dt_sec"].as_f64().unwrap() - 120.0).abs() < 1e-6);
+            // bounds go through the same parser as upstream timestamps
+            let epoch = (t0 + Duration::minutes(1)).timestamp();
+            let by_epoch = get_json(&app, &format!("/iss/trend?from={epoch}&points=3")).await;
+            assert_eq!(by_epoch["data"]["window"]["total"], 2, "{by_epoch}");
+            let bad = get_json(&app, "/iss/trend?from=yesterday").await;
+            assert!(bad["error"]["message"].as_str().unwrap().starts_with("from: "), "{bad}");
+            let bad = get_json(&app, &format!("/iss/trend?from={from}&to=2020-01-01")).await;
+            assert_eq!(bad["error"]["code"], "INVALID_INPUT");
+            let bad = get_json(&app, &format!("/iss/trend?from={from}&by=speed")).await;
+            assert_eq!(bad["error"]["code"], "INVALID_INPUT");
+            let long = get_json(&app, "/iss/trend?from=2020-01-01&to=2020-02-02").await;
+            assert_eq!(long["error"]["message"], "at most 31 days", "{long}");
+
+            let resp = app
+                .clone()
+                .oneshot(Request::get("/iss/track?format=gpx").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.headers()[header::CONTENT_TYPE], "application/gpx+xml");
+            assert_eq!(
+                resp.headers()[header::CONTENT_DISPOSITION],
+                "attachment; filename=\"track-25544.gpx\""
+            );
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(String::from_utf8_lossy(&bytes).matches("<trkpt").count(), 3);
+            let track = get_json(&app, "/iss/track").await;
+            assert_eq!(track["geometry"]["type"], "LineString");
+            let bad = get_json(&app, "/iss/track?format=shp").await;
+            assert_eq!(bad["error"]["code"], "INVALID_INPUT");
+            let long = get_json(&app, "/iss/track?from=2020-01-01&to=2020-03-01").await;
+            assert_eq!(long["error"]["message"], "at most 31 days", "{long}");
+
+            let at = (t0 + Duration::minutes(1)).timestamp();
+            let single = get_json(&app, &format!("/iss/at?ts={at}")).await;
+            let p = &single["data"];
+            assert_eq!(p["method"], "interpolated");
+            // `ts` in whole seconds: about the midpoint
+            assert!((p["longitude"].as_f64().unwrap() - 11.0).abs() < 0.02, "{p}");
+            assert!(p["latitude"].as_f64().unwrap() > 50.0, "{p}");
+            assert_eq!(p["samples"].as_array().unwrap().len(), 2);
+            let v = get_json(&app, "/iss/at?ts=2020-01-01").await;
+            assert_eq!(v["data"]["message"], "no position data");
+            let v = get_json(&app, "/iss/at").await;
+            assert_eq!(v["error"]["code"], "INVALID_INPUT");
+
+            let v = get_json(&app, "/iss/stats").await;
+            assert_eq!(v["data"]["samples"], 3);
+            assert_eq!(v["data"]["days"].as_array().unwrap().len(), 1);
+            assert!(v["data"]["period_minutes"].is_null());
+            let v = get_json(&app, "/iss/stats?from=2024-01-01&to=2024-06-01").await;
+            assert_eq!(v["error"]["code"], "INVALID_INPUT");
+
+            let body = json!({"ts": [at, "2020-01-01T00:00:00Z", at * 1000]}).to_string();
+            let resp = app
+                .clone()
+                .oneshot(
+                    Request::post("/iss/at")
+                        .header(header::CONTENT_TYPE, "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let v: Value = serde_json::from_slice(&bytes).unwrap();
+            let batch = v["data"].as_array().unwrap();
+            assert_eq!(batch.len(), 3);
+            assert!(batch[1].is_null());
+            // grouped range reads give what one read per `ts` does
+            assert_eq!(batch[0], single["data"]);
+            assert_eq!(batch[0], batch[2]);
+        }
+
+        #[tokio::test]
+        async fn cache_dedup_and_history_routes() {
+            let (st, store) = memory_state(&[]);
+            assert!(store.write("apod", json!({"a": 1})).await.unwrap());
+            assert!(!store.write("apod", json!({"a": 1})).await.unwrap());
+            assert!(store.write("apod", json!({"a": 2})).await.unwrap());
+
+            let app = routes::build_router(st);
+            let history = get_json(&app, "/space/apod/history").await;
+            let history = history["data"].as_array().unwrap();
+            assert_eq!(history.len(), 2);
+            assert_eq!(history[0]["payload"]["a"], 2);
+            assert_eq!(history[1]["check_count"], 2);
+            let latest = get_json(&app, "/space/neo/latest").await;
+            assert_eq!(latest["data"]["message"], "no data");
+        }
+
+        #[tokio::test]
+        async fn osdr_list_and_summary_count() {
+            let (st, store) = memory_state(&[]);
+            for id in ["OSD-1", "OSD-2", "OSD-1"] {
+                OsdrRepo::upsert(&store, OsdrUpsert {
+                    dataset_id: Some(id.to_string()),
+                    title: Some(format!("t-{id}")),
+                    status: None,
+                    updated_at: None,
+                    raw: json!({"id": id}),
+                })
+                .await
+                .unwrap();
+            }
+            let app = routes::build_router(st);
+            let list = get_json(&app, "/osdr/list?limit=5").await;
+            assert_eq!(list["data"]["items"].as_array().unwrap().len(), 2);
+            let summary = get_json(&app, "/space/summary").await;
+            assert_eq!(summary["data"]["osdr_count"], 2);
+        }
+
+        #[tokio::test]
+        async fn osdr_list_keyset_pages_and_filters() {
+            let (st, store) = memory_state(&[]);
+            let t0 = Utc::now() - Duration::days(10);
+            for (i, (id, status)) in [
+                ("OSD-10", "public"),
+                ("OSD-11", "draft"),
+                ("OSD-12", "public"),
+                ("OSD_2", "public"),
+                ("GLDS-1", "public"),
+            ]
+            .iter()
+            .enumerate()
+            {
+                OsdrRepo::upsert(&store, OsdrUpsert {
+                    dataset_id: Some(id.to_string()),
+                    title: Some(format!("title {}", 5 - i)),
+                    status: Some(status.to_string()),
+                    updated_at: (i != 2).then(|| t0 + Duration::days(i as i64)),
+                    raw: json!({"id": id}),
+                })
+                .await
+                .unwrap();
+            }
+            let app = routes::build_router(st);
+
+            let mut uri = "/osdr/list?limit=2&sort=title&order=asc".to_string();
+            let mut titles = Vec::new();
+            loop {
+                let page = get_json(&app, &uri).await;
+                assert_eq!(page["data"]["total_estimate"], 5);
+                for item in page["data"]["items"].as_array().unwrap() {
+                    titles.push(item["title"].as_str().unwrap().to_string());
+                }
+                match page["data"]["next_cursor"].as_str() {
+                    Some(c) => uri = format!("/osdr/list?limit=2&sort=title&order=asc&cursor={c}"),
+                    None => break,
+                }
+            }
+            assert_eq!(titles, ["title 1", "title 2", "title 3", "title 4", "title 5"]);
+
+            // NULL updated_at sorts last in descending order.
+            let page = get_json(&app, "/osdr/list?sort=updated_at&limit=5").await;
+            let ids: Vec<_> = page["data"]["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|i| i["dataset_id"].as_str().unwrap().to_string())
+                .collect();
+            assert_eq!(ids, ["GLDS-1", "OSD_2", "OSD-11", "OSD-10", "OSD-12"]);
+
+            let page = get_json(&app, "/osdr/list?prefix=OSD-1&status=public").await;
+            assert_eq!(page["data"]["total_estimate"], 2);
+            let page = get_json(&app, "/osdr/list?prefix=OSD_").await;
+            assert_eq!(page["data"]["total_estimate"], 1);
+            let from = (t0 + Duration::days(1)).date_naive();
+            let page = get_json(&app, &format!("/osdr/list?updated_from={from}")).await;
+            assert_eq!(page["data"]["items"].as_array().unwrap().len(), 3);
+
+            let page = get_json(&app, "/osdr/list?limit=1").await;
+            let cursor = page["data"]["next_cursor"].as_str().unwrap();
+            let bad = get_json(&app, &format!("/osdr/list?sort=title&cursor={cursor}")).await;
+            assert_eq!(bad["ok"], false);
+            let bad = get_json(&app, "/osdr/list?updated_to=yesterday").await;
+            assert_eq!(bad["error"]["code"], "INVALID_INPUT");
+        }
+
+        /// Serves `router` on an ephemeral local port, standing in for an
+        /// upstream API.
+        pub(crate) async fn upstream(router: Router) -> String {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+            format!("http://{addr}")
+        }
+
+        #[tokio::test]
+        async fn osdr_enrichment_is_incremental() {
+            let detail = Router::new().route(
+                "/dataset/OSD-1",
+                get(|| async {
+                    Json(json!({"OSD-1": {
+                        "files": {"a.csv": {"URL": "https://x/a.csv"}},
+                        "metadata": {"organism": "Mus musculus", "study factor name": "Spaceflight"}
+                    }}))
+                }),
+            );
+            let base = upstream(detail).await;
+            let url = format!("{base}/dataset/{{id}}");
+            let (st, store) = memory_state(&[("OSDR_DETAIL_URL", &url)]);
+            for id in ["OSD-1", "OSD-2"] {
+                OsdrRepo::upsert(&store, OsdrUpsert {
+                    dataset_id: Some(id.to_string()),
+                    title: None,
+                    status: None,
+                    updated_at: Some(Utc::now()),
+                    raw: json!({}),
+                })
+                .await
+                .unwrap();
+            }
+
+            let run = st.osdr.enrich(10).await.unwrap();
+            assert_eq!((run.candidates, run.enriched, run.failed), (2, 1, 1));
+            // the failed dataset waits one period (900 s), then twice as long
+            let now = Utc::now();
+            assert!(store.enrich_candidates(now, 10).await.unwrap().is_empty());
+            let later = store.enrich_candidates(now + Duration::seconds(901), 10).await.unwrap();
+            assert_eq!((later[0].dataset_id.as_str(), later[0].attempts), ("OSD-2", 1));
+            OsdrRepo::record_enrich_failure(&store, &OsdrEnrichFailure {
+                dataset_id: "OSD-2".into(),
+                attempts: 1,
+                failed_at: now - Duration::seconds(901),
+                retry_at: now - Duration::seconds(1),
+                error: "x".into(),
+            })
+            .await
+            .unwrap();
+            let run = st.osdr.enrich(10).await.unwrap();
+            assert_eq!((run.candidates, run.failed), (1, 1));
+            let retry = |s: i64| store.enrich_candidates(now + Duration::seconds(s), 10);
+            assert!(retry(1790).await.unwrap().is_empty());
+            assert_eq!(retry(1810).await.unwrap()[0].attempts, 2);
+
+            let app = routes::build_router(st.clone());
+            // the route leaves a failed dataset alone until its retry is due
+            let body = get_json(&app, "/osdr/OSD-2").await;
+            assert!(body["data"]["detail"].is_null(), "{body}");
+            assert_eq!(retry(1810).await.unwrap()[0].attempts, 2);
+            let failure = OsdrRepo::enrich_failure(&store, "OSD-2").await.unwrap().unwrap();
+            OsdrRepo::record_enrich_failure(&store, &OsdrEnrichFailure {
+                retry_at: now - Duration::seconds(1),
+                ..failure
+            })
+            .await
+            .unwrap();
+            get_json(&app, "/osdr/OSD-2").await;
+            let failure = OsdrRepo::enrich_failure(&store, "OSD-2").await.unwrap().unwrap();
+            assert_eq!(failure.attempts, 3);
+            assert!(failure.retry_at > now + Duration::seconds(3590), "{failure:?}");
+
+            let body = get_json(&app, "/osdr/OSD-1").await;
+            assert_eq!(body["data"]["detail"]["organisms"], json!(["Mus musculus"]));
+            assert_eq!(body["data"]["detail"]["files"][0]["name"], "a.csv");
+            assert_eq!(body["data"]["detail_stale"], false);
+
+            OsdrRepo::upsert(&store, OsdrUpsert {
+                dataset_id: Some("OSD-1".into()),
+                title: Some("changed".into()),
+                status: None,
+                updated_at: Some(Utc::now() + Duration::seconds(1)),
+                raw: json!({}),
+            })
+            .await
+            .unwrap();
+            let body = get_json(&app, "/osdr/OSD-1").await;
+            assert_eq!(body["data"]["detail_stale"], true);
+            assert_eq!(store.enrich_candidates(Utc::now(), 10).await.unwrap().len(), 1);
+
+            let missing = get_json(&app, "/osdr/OSD-404").await;
+            assert_eq!(missing["data"]["message"], "not found");
+        }
+
+        #[tokio::test]
+        async fn osdr_history_and_changes_feed() {
+            let (st, store) = memory_state(&[]);
+            let upsert = |id: &str, status: &str| OsdrUpsert {
+                dataset_id: Some(id.to_string()),
+                title: Some(format!("t-{id}")),
+                status: Some(status.to_string()),
+                updated_at: None,
+                raw: json!({"id": id, "status": status}),
+            };
+            assert_eq!(store.upsert(upsert("OSD-1", "draft")).await.unwrap(), OsdrChange::Created);
+            assert_eq!(store.upsert(upsert("OSD-1", "draft")).await.unwrap(), OsdrChange::Unchanged);
+            assert_eq!(store.upsert(upsert("OSD-2", "draft")).await.unwrap(), OsdrChange::Created);
+            assert_eq!(store.upsert(upsert("OSD-1", "public")).await.unwrap(), OsdrChange::Updated);
+
+            let app = routes::build_router(st);
+            let history = get_json(&app, "/osdr/OSD-1/history").await;
+            let history = history["data"].as_array().unwrap();
+            assert_eq!(history.len(), 2);
+            assert_eq!(history[0]["version"], 2);
+            assert_eq!(history[0]["kind"], "updated");
+            assert_eq!(
+                history[0]["diff"]["fields"]["status"],
+                json!({"old": "draft", "new": "public"})
+            );
+            assert_eq!(history[0]["diff"]["raw"][0]["path"], "/status");
+
+            let feed = get_json(&app, "/osdr/changes?limit=10").await;
+            let ids: Vec<_> = feed["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| (v["dataset_id"].as_str().unwrap(), v["version"].as_i64().unwrap()))
+                .collect();
+            assert_eq!(ids, [("OSD-1", 2), ("OSD-2", 1)]);
+            let future = (Utc::now() + Duration::hours(1)).to_rfc3339();
+            let feed = get_json(&app, &format!("/osdr/changes?since={}", future.replace('+', "%2B"))).await;
+            assert_eq!(feed["data"], json!([]));
+        }
+
+        #[tokio::test]
+        async fn osdr_sync_tombstones_and_restores() {
+            use std::sync::{
+                atomic::{AtomicBool, Ordering},
+                Arc,
+            };
+            let full = Arc::new(AtomicBool::new(true));
+            let flag = full.clone();
+            let catalog = Router::new().route(
+                "/cat",
+                get(move || {
+                    let full = flag.load(Ordering::SeqCst);
+                    async move {
+                        let mut items = vec![json!({"dataset_id": "OSD-1", "title": "one"})];
+                        if full {
+                            items.push(json!({"dataset_id": "OSD-2", "title": "two"}));
+                            items.push(json!({"title": "no id"}));
+                            items.push(json!({"dataset_id": "OSD-1", "title": "again"}));
+                        }
+                        Json(json!({"items": items}))
+                    }
+                }),
+            );
+            let url = format!("{}/cat", upstream(catalog).await);
+            let (st, _) = memory_state(&[("NASA_API_URL", &url), ("OSDR_SYNC_BATCH", "1")]);
+            let report = st.osdr.sync(true).await.unwrap();
+            // the item without dataset_id is stored on its own, as before batching
+            assert_eq!((report.inserted, report.failed), (3, 1));
+            let errors: Vec<_> = report.errors.iter().map(|e| (e.index, e.message.as_str())).collect();
+            assert_eq!(errors, [(3, "duplicate dataset_id")]);
+            assert_eq!(report.unmapped["dataset_id"], 1);
+            assert_eq!(report.unmapped["updated_at"], 4);
+
+            full.store(false, Ordering::SeqCst);
+            let report = st.osdr.sync(true).await.unwrap();
+            assert_eq!((report.unchanged, report.removed.as_slice()), (1, ["OSD-2".to_string()].as_slice()));
+
+            let app = routes::build_router(st.clone());
+            let list = get_json(&app, "/osdr/list").await;
+            assert_eq!(list["data"]["items"].as_array().unwrap().len(), 2);
+            let list = get_json(&app, "/osdr/list?include_removed=true").await;
+            let items = list["data"]["items"].as_array().unwrap();
+            assert_eq!(items.len(), 3);
+            assert!(items.iter().any(|i| i["dataset_id"] == "OSD-2" && !i["removed_at"].is_null()));
+
+            full.store(true, Ordering::SeqCst);
+            let report = st.osdr.sync(true).await.unwrap();
+            assert_eq!((report.unchanged, report.restored, report.removed.len()), (1, 1, 0));
+            let history = get_json(&app, "/osdr/OSD-2/history").await;
+            let kinds: Vec<_> = history["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| v["kind"].as_str().unwrap().to_string())
+                .collect();
+            assert_eq!(kinds, ["restored", "removed", "created"]);
+            // with no key to match on, every pass stores the id-less item again
+            let list = get_json(&app, "/osdr/list").await;
+            assert_eq!(list["data"]["items"].as_array().unwrap().len(), 4);
+        }
+
+        #[tokio::test]
+        async fn osdr_incremental_sync_uses_watermark() {
+            use axum::extract::Query;
+            use std::sync::{Arc, Mutex};
+
+            type Shared = Arc<Mutex<(Vec<Value>, Option<String>)>>;
+            let shared: Shared = Arc::new(Mutex::new((Vec::new(), None)));
+            let handle = shared.clone();
+            let catalog = Router::new().route(
+                "/cat",
+                get(move |Query(q): Query<HashMap<String, String>>| {
+                    let mut s = handle.lock().unwrap();
+                    s.1 = q.get("since").cloned();
+                    let items = s.0.clone();
+                    async move { Json(json!({"items": items})) }
+                }),
+            );
+            let item = |id: &str, updated: &str| json!({"dataset_id": id, "updated_at": updated});
+            shared.lock().unwrap().0 = vec![
+                item("OSD-1", "2026-01-01T00:00:00Z"),
+                item("OSD-2", "2026-01-02T00:00:00Z"),
+            ];
+            let url = format!("{}/cat", upstream(catalog).await);
+            let (st, store) = memory_state(&[("NASA_API_URL", &url), ("OSDR_SINCE_PARAM", "since")]);
+
+            let report = st.osdr.sync(false).await.unwrap();
+            assert_eq!((report.mode, report.inserted), (OsdrSyncMode::Full, 2));
+            assert_eq!(shared.lock().unwrap().1, None);
+            let watermark = "2026-01-02T00:00:00Z".parse::<chrono::DateTime<Utc>>().unwrap();
+            assert_eq!(report.watermark, Some(watermark));
+            // the next full pass is a day away, on the full job's own clock
+            let due_in = st.osdr.full_sync_due_in().await.unwrap().as_secs();
+            assert!((86_000..=86_400).contains(&due_in), "{due_in}");
+
+            // OSD-1 vanished and OSD-2 is unchanged: nothing is written or tombstoned
+            shared.lock().unwrap().0 = vec![
+                item("OSD-2", "2026-01-02T00:00:00Z"),
+                item("OSD-3", "2026-01-03T00:00:00Z"),
+            ];
+            let report = st.osdr.sync(false).await.unwrap();
+            assert_eq!(report.mode, OsdrSyncMode::Incremental);
+            assert_eq!(shared.lock().unwrap().1.as_deref(), Some("2026-01-02T00:00:00Z"));
+            assert_eq!((report.skipped, report.inserted, report.removed.len()), (1, 1, 0));
+            assert_eq!(store.sync_state().await.unwrap().watermark, report.watermark);
+
+            let report = st.osdr.sync(true).await.unwrap();
+            assert_eq!((report.mode, report.unchanged), (OsdrSyncMode::Full, 2));
+            assert_eq!(report.removed, ["OSD-1"]);
+        }
+
+        #[tokio::test]
+        async fn osdr_export_streams_all_formats() {
+            use crate::domain::OsdrExportFormat;
+            use crate::services::{export_error, export_header};
+
+            let (st, store) = memory_state(&[]);
+            let items: Vec<OsdrUpsert> = (0..501)
+                .map(|i| OsdrUpsert {
+                    dataset_id: Some(if i == 2 { "OSD-2 a/b".into() } else { format!("OSD-{i}") }),
+                    title: Some(match i {
+                        0 => "Mice, \"flown\"".into(),
+                        1 => "=1+2".into(),
+                        _ => format!("t{i}"),
+                    }),
+                    status: Some(if i % 2 == 0 { "public" } else { "draft" }.into()),
+                    updated_at: None,
+                    raw: json!({"description": format!("d{i}")}),
+                })
+                .collect();
+            store.upsert_batch(&items).await.unwrap();
+            let app = routes::build_router(st);
+            let text = |uri: &'static str| {
+                let app = app.clone();
+                async move {
+                    let resp = app
+                        .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                        .await
+                        .unwrap();
+                    let ct = resp.headers()["content-type"].to_str().unwrap().to_string();
+                    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+                    (ct, String::from_utf8(bytes.to_vec()).unwrap())
+                }
+            };
+
+            let (ct, csv) = text("/osdr/export").await;
+            assert_eq!(ct, "text/csv; charset=utf-8");
+            let lines: Vec<_> = csv.split_terminator("\r\n").collect();
+            assert_eq!(lines.len(), 502);
+            assert!(lines[0].starts_with("dataset_id,title,status"));
+            assert!(lines[1].starts_with("OSD-0,\"Mice, \"\"flown\"\"\",public,"));
+            assert!(lines[2].starts_with("OSD-1,'=1+2,draft,"));
+
+            let (_, nd) = text("/osdr/export?format=ndjson&status=draft").await;
+            let rows: Vec<Value> = nd.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+            assert_eq!(rows.len(), 250);
+            assert_eq!(rows[0]["dataset_id"], "OSD-1");
+
+            let (ct, ld) = text("/osdr/export?format=jsonld").await;
+            assert_eq!(ct, "application/ld+json");
+            let ld: Value = serde_json::from_str(&ld).unwrap();
+            assert_eq!(ld["@type"], "DataCatalog");
+            let datasets = ld["dataset"].as_array().unwrap();
+            assert_eq!(datasets.len(), 501);
+            assert_eq!(datasets[500]["identifier"], "OSD-500");
+            assert_eq!(datasets[500]["description"], "d500");
+            assert_eq!(datasets[500]["url"], "https://osdr.nasa.gov/bio/repo/data/studies/OSD-500");
+            assert_eq!(
+                datasets[2]["url"],
+                "https://osdr.nasa.gov/bio/repo/data/studies/OSD-2%20a%2Fb"
+            );
+
+            for format in [OsdrExportFormat::Csv, OsdrExportFormat::Ndjson] {
+                let trailer = export_error(format, "db down");
+                assert!(trailer.contains("db down"), "{trailer}");
+            }
+            let cut = format!(
+                "{}{}",
+                export_header(OsdrExportFormat::JsonLd),
+                export_error(OsdrExportFormat::JsonLd, "db down")
+            );
+            let cut: Value = serde_json::from_str(&cut).unwrap();
+            assert_eq!(cut["error"], "db down");
+
+            let bad = get_json(&app, "/osdr/export?format=xml").await;
+            assert_eq!(bad["error"]["code"], "INVALID_INPUT");
+        }
+
+        #[tokio::test]
+        async fn osdr_search_ranks_highlights_and_pages() {
+            let (st, store) = memory_state(&[]);
+            for (id, title, desc) in [
+                ("OSD-1", "Bone loss in mice", "Mouse femur bone density after flight"),
+                ("OSD-2", "Plant growth", "Arabidopsis roots; no bone here"),
+                ("OSD-3", "Muscle atrophy", "Soleus muscle of Mus musculus"),
+                ("OSD-4", "Костная ткань мышей", "Потеря костной массы в полёте"),
+            ] {
+                store
+                    .upsert(OsdrUpsert {
+                        dataset_id: Some(id.to_string()),
+                        title: Some(title.to_string()),
+                        status: None,
+                        updated_at: None,
+                        raw: json!({"description": desc}),
+                    })
+                    .await
+                    .unwrap();
+            }
+            let app = routes::build_router(st);
+
+            let page = get_json(&app, "/osdr/search?q=bone&limit=1").await;
+            assert_eq!(page["data"]["total_estimate"], 2);
+            let first = &page["data"]["items"][0];
+            assert_eq!(first["dataset_id"], "OSD-1");
+            assert_eq!(first["title_highlight"], "<b>Bone</b> loss in mice");
+            assert!(first["snippet"].as_str().unwrap().contains("<b>bone</b>"));
+            let cursor = page["data"]["next_cursor"].as_str().unwrap();
+            let page = get_json(&app, &format!("/osdr/search?q=bone&limit=1&cursor={cursor}")).await;
+            assert_eq!(page["data"]["items"][0]["dataset_id"], "OSD-2");
+            assert_eq!(page["data"]["next_cursor"], Value::Null);
+
+            let page = get_json(&app, "/osdr/search?q=%D0%BA%D0%BE%D1%81%D1%82%D0%BD").await;
+            assert_eq!(page["data"]["items"][0]["dataset_id"], "OSD-4");
+            let page = get_json(&app, "/osdr/search?q=bone+muscle").await;
+            assert_eq!(page["data"]["total_estimate"], 0);
+            let page = get_json(&app, "/osdr/search?q=bone&prefix=OSD-2").await;
+            assert_eq!(page["data"]["total_estimate"], 1);
+            let bad = get_json(&app, "/osdr/search?q=").await;
+            assert_eq!(bad["error"]["code"], "INVALID_INPUT");
+        }
+
+        #[tokio::test]
+        async fn tle_refresh_and_propagated_position() {
+            let path = std::env::temp_dir().join(format!("tle-{}.txt", uuid::Uuid::new_v4()));
+            std::fs::write(&path, format!("{}1 25544U bad line\n", super::ISS_TLE)).unwrap();
+            let (st, _) = memory_state(&[("TLE_FILE", path.to_str().unwrap())]);
+            let app = routes::build_router(st);
+
+            let v = get_json(&app, "/iss/position").await;
+            assert_eq!(v["data"]["message"], "no orbital elements");
+
+            let v = get_json(&app, "/iss/tle/refresh").await;
+            assert_eq!(v["data"]["parsed"], 1);
+            assert_eq!(v["data"]["inserted"], 1);
+            assert_eq!(v["data"]["errors"].as_array().unwrap().len(), 1);
+            let v = get_json(&app, "/iss/tle/refresh").await;
+            assert_eq!(v["data"]["inserted"], 0);
+            std::fs::remove_file(&path).unwrap();
+            // a missing file is the service's fault, not the request's
+            let v = get_json(&app, "/iss/tle/refresh").await;
+            assert_eq!(v["error"]["code"], "IO_ERROR", "{v}");
+
+            let v = get_json(&app, "/iss/tle").await;
+            assert_eq!(v["data"][0]["norad_id"], 25544);
+            assert_eq!(v["data"][0]["name"], "ISS (ZARYA)");
+
+            let v = get_json(&app, "/iss/position?at=2008-09-20T13:00:00Z").await;
+            let p = &v["data"];
+            assert_eq!(p["at"], "2008-09-20T13:00:00Z");
+            assert!(p["latitude"].as_f64().unwrap().abs() <= 51.7, "{p}");
+            assert!((330.0..370.0).contains(&p["altitude"].as_f64().unwrap()), "{p}");
+            assert!((27_000.0..28_000.0).contains(&p["velocity"].as_f64().unwrap()), "{p}");
+            assert!(p["illumination"].is_string(), "{p}");
+
+            let v = get_json(&app, "/iss/at?ts=2008-09-20T13:00:00Z").await;
+            assert_eq!(v["data"]["method"], "propagated");
+            assert_eq!(v["data"]["latitude"], p["latitude"]);
+            assert!((v["data"]["uncertainty_km"].as_f64().unwrap() - 1.05).abs() < 0.01);
+
+            let v = get_json(&app, "/iss/sun?at=2008-09-20T13:00:00Z").await;
+            assert_eq!(v["data"]["illumination"], p["illumination"]);
+            assert!(v["data"]["subsolar"]["latitude"].as_f64().unwrap().abs() < 1.0);
+
+            let resp = app
+                .clone()
+                .oneshot(Request::get("/iss/terminator").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.headers()[header::CONTENT_TYPE], "application/geo+json");
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let v: Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(v["geometry"]["type"], "Polygon");
+            assert!(v["geometry"]["coordinates"][0].as_array().unwrap().len() > 360);
+        }
+
+        /// The ISS elements moved to the current day, renumbered as `norad_id`,
+        /// so that propagating to now stays within the model's reach.
+        fn fresh_tle(norad_id: i64, name: &str) -> String {
+            let now = Utc::now();
+            let day = now.ordinal() as f64 + now.num_seconds_from_midnight() as f64 / 86_400.0;
+            let epoch = format!("{:02}{:012.8}", now.year() % 100, day);
+            let lines: Vec<String> = super::ISS_TLE
+                .lines()
+                .skip(1)
+                .map(|l| {
+                    let mut l = format!("{}{:05}{}", &l[..2], norad_id, &l[7..68]);
+                    if l.starts_with('1') {
+                        l.replace_range(18..32, &epoch);
+                    }
+                    let sum: u32 = l
+                        .chars()
+                        .map(|c| c.to_digit(10).unwrap_or(u32::from(c == '-')))
+                        .sum();
+                    format!("{l}{}", sum % 10)
+                })
+                .collect();
+            format!("{name}\n{}\n{}\n", lines[0], lines[1])
+        }
+
+        #[tokio::test]
+        async fn satellites_are_tracked_separately() {
+            let path = std::env::temp_dir().join(format!("tle-{}.txt", uuid::Uuid::new_v4()));
+            std::fs::write(&path, fresh_tle(48274, "CSS (TIANHE)")).unwrap();
+            let (st, store) = memory_state(&[
+                ("TLE_FILE", path.to_str().unwrap()),
+                ("TRACKED_SATELLITES", "25544:name=ISS,mode=poll;48274:name=CSS"),
+                ("RETENTION_POLICIES", "iss_fetch_log:max=1d"),
+            ]);
+            let now = Utc::now();
+            for m in [5, 3, 1] {
+                store.push_iss(now - Duration::minutes(m), "test", json!({"longitude": m}));
+            }
+            store.push_sat(48274, now - Duration::days(3), "sgp4", json!({"longitude": 99.0}));
+            // the old CSS row is the newest of its satellite and survives retention
+            let runs = st.retention.run().await.unwrap();
+            assert_eq!(runs[0].expired, 0);
+            let app = routes::build_router(st);
+
+            let v = get_json(&app, "/fetch?sat=css").await;
+            assert_eq!(v["error"]["code"], "INVALID_INPUT", "{v}");
+            let v = get_json(&app, "/iss/tle/refresh").await;
+            assert_eq!(v["data"]["inserted"], 1);
+            std::fs::remove_file(&path).unwrap();
+
+            let v = get_json(&app, "/fetch?sat=CSS").await;
+            assert_eq!(v["data"]["source_url"], "sgp4", "{v}");
+            let p = &v["data"]["payload"];
+            assert_eq!((p["id"].as_i64(), p["name"].as_str()), (Some(48274), Some("CSS")));
+            assert!((330.0..400.0).contains(&p["altitude"].as_f64().unwrap()), "{p}");
+
+            let v = get_json(&app, "/last").await;
+            assert_eq!(v["data"]["payload"]["longitude"], 1);
+            let v = get_json(&app, "/iss/trend?sat=48274").await;
+            assert_eq!(v["data"]["points"].as_array().unwrap().len(), 2);
+            let v = get_json(&app, "/iss/tle?sat=25544").await;
+            assert!(v["data"].as_array().unwrap().is_empty());
+            let v = get_json(&app, "/last?sat=hubble").await;
+            assert_eq!(v["error"]["code"], "INVALID_INPUT");
+
+            let v = get_json(&app, "/satellites").await;
+            let sats = v["data"].as_array().unwrap();
+            assert_eq!(sats.len(), 2);
+            assert_eq!(sats[0]["mode"], "poll");
+            assert!(sats[0]["tle_epoch"].is_null());
+            assert_eq!(sats[1]["mode"], "propagate");
+            assert!(sats[1]["poll_url"].is_null());
+            assert!(sats[1]["tle_epoch"].is_string());
+        }
+
+        #[tokio::test]
+        async fn batch_positions_use_the_set_nearest_each_timestamp() {
+            let tle = format!("{}{}", super::ISS_TLE, fresh_tle(25544, "ISS (ZARYA)"));
+            let path = std::env::temp_dir().join(format!("tle-{}.txt", uuid::Uuid::new_v4()));
+            std::fs::write(&path, tle).unwrap();
+            let (st, _) = memory_state(&[("TLE_FILE", path.to_str().unwrap())]);
+            let app = routes::build_router(st);
+            let v = get_json(&app, "/iss/tle/refresh").await;
+            assert_eq!(v["data"]["inserted"], 2, "{v}");
+            std::fs::remove_file(&path).unwrap();
+
+            let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+            let ts = ["2008-09-20T13:00:00Z", now.as_str(), "2008-09-20T13:01:00Z"];
+            let body = json!({ "ts": ts }).to_string();
+            let resp = app
+                .clone()
+                .oneshot(
+                    Request::post("/iss/at")
+                        .header(header::CONTENT_TYPE, "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let v: Value = serde_json::from_slice(&bytes).unwrap();
+            let batch = v["data"].as_array().unwrap();
+            for (at, p) in ts.iter().zip(batch) {
+                assert_eq!(p["method"], "propagated", "{p}");
+                let single = get_json(&app, &format!("/iss/at?ts={at}")).await;
+                assert_eq!(*p, single["data"]);
+            }
+            assert_ne!(batch[0]["tle_epoch"], batch[1]["tle_epoch"]);
+        }
+
+        #[tokio::test]
+        async fn predict_from_elements_or_fitted_positions() {
+            let tle = fresh_tle(25544, "ISS (ZARYA)");
+            let path = std::env::temp_dir().join(format!("tle-{}.txt", uuid::Uuid::new_v4()));
+            std::fs::write(&path, &tle).unwrap();
+            let (st, store) = memory_state(&[("TLE_FILE", path.to_str().unwrap())]);
+            let app = routes::build_router(st.clone());
+
+            let v = get_json(&app, "/iss/predict").await;
+            assert_eq!(v["error"]["code"], "INVALID_INPUT", "{v}");
+
+            // positions logged every two minutes up to now, from the same elements
+            let (tles, _) = crate::orbit::parse_tle_text(&tle);
+            let sgp4 = crate::orbit::Sgp4::new(&tles[0]).unwrap();
+            let now = Utc::now();
+            for m in (0..=10).rev().step_by(2) {
+                let at = now - Duration::minutes(m);
+                let (lat, lon, alt) = crate::orbit::geodetic(sgp4.at(at).unwrap().position, at);
+                store.push_iss(at, "test", json!({"latitude": lat, "longitude": lon, "altitude": alt}));
+            }
+            let fitted = get_json(&app, "/iss/predict?minutes=60&step=300").await;
+            assert_eq!(fitted["data"]["model"], "fit", "{fitted}");
+            assert!(fitted["data"]["tle_epoch"].is_null());
+            assert_eq!(fitted["data"]["points"].as_array().unwrap().len(), 13);
+            // a day is 16 revolutions past the positions the orbit is fitted to
+            let v = get_json(&app, "/iss/predict?minutes=1440&step=3600").await;
+            assert_eq!(v["error"]["code"], "INVALID_INPUT", "{v}");
+
+            get_json(&app, "/iss/tle/refresh").await;
+            std::fs::remove_file(&path).unwrap();
+            let v = get_json(&app, "/iss/predict?minutes=60&step=300").await;
+            let data = &v["data"];
+            assert_eq!(data["model"], "sgp4");
+            assert_eq!(data["step_seconds"], 300);
+            let points = data["points"].as_array().unwrap();
+            assert_eq!(points.len(), 13);
+            let sunlit = points.iter().filter(|p| p["sunlit"] == true).count();
+            assert!((1..13).contains(&sunlit), "an hour of ISS orbit crosses the terminator");
+            for (p, f) in points.iter().zip(fitted["data"]["points"].as_array().unwrap()) {
+                let lat = |v: &Value| v["latitude"].as_f64().unwrap();
+                assert!((lat(p) - lat(f)).abs() < 1.0, "{p} vs {f}");
+                assert!((300.0..450.0).contains(&p["altitude"].as_f64().unwrap()), "{p}");
+            }
+
+            let v = get_json(&app, "/iss/predict?minutes=100000&step=1").await;
+            assert_eq!(v["data"]["step_seconds"], 10);
+            assert_eq!(v["data"]["points"].as_array().unwrap().len(), 1440 * 6 + 1);
+        }
+
+        #[tokio::test]
+        async fn passes_over_an_observer() {
+            let tle = fresh_tle(25544, "ISS (ZARYA)");
+            let path = std::env::temp_dir().join(format!("tle-{}.txt", uuid::Uuid::new_v4()));
+            std::fs::write(&path, &tle).unwrap();
+            let (st, _) = memory_state(&[("TLE_FILE", path.to_str().unwrap())]);
+            let app = routes::build_router(st);
+            let v = get_json(&app, "/iss/passes?lat=55.75&lon=37.62").await;
+            assert_eq!(v["error"]["code"], "INVALID_INPUT", "{v}");
+            get_json(&app, "/iss/tle/refresh").await;
+            std::fs::remove_file(&path).unwrap();
+
+            for bad in ["/iss/passes?lon=37.62", "/iss/passes?lat=95&lon=0", "/iss/passes?lat=0&lon=0&alt=20000"] {
+                assert_eq!(get_json(&app, bad).await["error"]["code"], "INVALID_INPUT", "{bad}");
+            }
+
+            let v = get_json(&app, "/iss/passes?lat=55.75&lon=37.62&alt=150&days=2").await;
+            let data = &v["data"];
+            assert_eq!(data["altitude"], 150.0);
+            let passes = data["passes"].as_array().unwrap();
+            // a 51.6° orbit passes over Moscow several times a day
+            assert!((4..=16).contains(&passes.len()), "{}", passes.len());
+
+            let (tles, _) = crate::orbit::parse_tle_text(&tle);
+            let sgp4 = crate::orbit::Sgp4::new(&tles[0]).unwrap();
+            let obs = crate::orbit::Observer { latitude: 55.75, longitude: 37.62, altitude: 0.15 };
+            let elevation = |p: &Value| {
+                let at: chrono::DateTime<Utc> = p["at"].as_str().unwrap().parse().unwrap();
+                obs.look(sgp4.at(at).unwrap().position, at).elevation
+            };
+            for p in passes {
+                assert!(elevation(&p["rise"]).abs() < 0.1, "{p}");
+                assert!(elevation(&p["set"]).abs() < 0.1, "{p}");
+                let top = p["max_elevation"].as_f64().unwrap();
+                assert!((elevation(&p["culmination"]) - top).abs() < 0.01, "{p}");
+                assert!(top > 0.0 && top <= 90.0, "{p}");
+                assert!((30..=720).contains(&p["duration_seconds"].as_i64().unwrap()), "{p}");
+                assert_eq!(p["visible"].as_bool(), Some(!p["magnitude"].is_null()), "{p}");
+                let az = p["rise"]["azimuth"].as_f64().unwrap();
+                assert!((0.0..360.0).contains(&az));
+            }
+            let later = get_json(&app, "/iss/passes?lat=55.75&lon=37.62&days=2&visible=true").await;
+            let visible = later["data"]["passes"].as_array().unwrap();
+            assert!(visible.iter().all(|p| p["visible"] == true));
+            // each call starts at its own `now`: compare the window both cover
+            let time = |v: &Value| v.as_str().unwrap().parse::<chrono::DateTime<Utc>>().unwrap();
+            let (from, to) = (time(&later["data"]["from"]), time(&data["to"]));
+            let inside = |p: &&Value| time(&p["rise"]["at"]) >= from && time(&p["set"]["at"]) <= to;
+            let (a, b): (Vec<&Value>, Vec<&Value>) = (
+                visible.iter().filter(inside).collect(),
+                passes.iter().filter(|p| p["visible"] == true).filter(inside).collect(),
+            );
+            assert_eq!(a.len(), b.len());
+            // rises are bisected to the second from different scan starts
+            for (a, b) in a.iter().zip(&b) {
+                let apart = time(&a["rise"]["at"]) - time(&b["rise"]["at"]);
+                assert!(apart.num_milliseconds().abs() <= 2000, "{a} {b}");
+            }
+        }
+
+        #[tokio::test]
+        async fn geofence_events_on_ingest() {
+            use std::sync::atomic::{AtomicUsize, Ordering};
+            use std::sync::Arc;
+
+            let track = [(10.0, -40.0), (52.0, 36.0), (55.0, 38.0), (50.0, 70.0), (45.0, 90.0)];
+            let calls = Arc::new(AtomicUsize::new(0));
+            let served = calls.clone();
+            let iss = Router::new().route(
+                "/iss",
+                get(move || {
+                    let (lat, lon) = track[served.fetch_add(1, Ordering::SeqCst)];
+                    async move { Json(json!({"latitude": lat, "longitude": lon, "altitude": 420})) }
+                }),
+            );
+            let url = format!("{}/iss", upstream(iss).await);
+            let fences = r#"[
+                {"name": "station", "circle": {"lat": 55.75, "lon": 37.62, "radius_km": 1000}},
+                {"name": "box", "polygon": [[30, 50], [75, 50], [75, 70], [30, 70]]}
+            ]"#;
+            let (st, store) =
+                memory_state(&[("WHERE_ISS_URL", url.as_str()), ("GEOFENCES", fences)]);
+            let app = routes::build_router(st.clone());
+
+            let sat = st.cfg.satellites[0].clone();
+            let mut crossings = Vec::new();
+            for _ in 0..track.len() {
+                let events = st.iss.fetch_and_store(&sat).await.unwrap();
+                // events carry the time of the position they were found at
+                let logged = crate::repo::IssRepo::last(&store, sat.norad_id).await.unwrap();
+                let logged_at = logged.unwrap().1;
+                assert!(events.iter().all(|e| e.at == logged_at), "{events:?}");
+                let names = events.iter().map(|e| format!("{}:{}", e.fence, e.kind.as_str()));
+                crossings.push(names.collect::<Vec<_>>());
+            }
+            assert_eq!(
+                crossings,
+                [
+                    vec![],
+                    vec!["station:enter".to_string(), "box:enter".to_string()],
+                    vec![],
+                    vec!["station:exit".to_string()],
+                    vec!["box:exit".to_string()],
+                ]
+            );
+
+            let v = get_json(&app, "/iss/events").await;
+            let events = v["data"].as_array().unwrap();
+            assert_eq!(events.len(), 4);
+            assert_eq!((&events[0]["fence"], &events[0]["kind"]), (&json!("box"), &json!("exit")));
+            assert_eq!(events[0]["longitude"], 90.0);
+            assert_eq!(events[3]["norad_id"], 25544);
+            let v = get_json(&app, "/iss/events?fence=station&limit=1").await;
+            assert_eq!(v["data"][0]["kind"], "exit");
+            assert_eq!(v["data"].as_array().unwrap().len(), 1);
+            let v = get_json(&app, "/iss/events?sat=iss&since=2100-01-01").await;
+            assert!(v["data"].as_array().unwrap().is_empty());
+            let v = get_json(&app, "/iss/geofences").await;
+            assert_eq!(v["data"][0]["circle"]["radius_km"], 1000.0);
+            assert_eq!(v["data"][1]["polygon"][1], json!([75.0, 50.0]));
+        }
+
+        #[tokio::test]
+        async fn retention_downsamples_memory_store() {
+            let (st, store) = memory_state(&[("RETENTION_POLICIES", "iss_fetch_log:raw=1d,bucket=1h")]);
+            let now = Utc::now();
+            for m in (0..3 * 24 * 60).step_by(2) {
+                store.push_iss(now - Duration::minutes(m), "test", json!({"latitude": 0.0}));
+            }
+            let runs = st.retention.run().await.unwrap();
+            assert_eq!(runs.len(), 1);
+            assert!(runs[0].downsampled > 1_000);
+
+            let app = routes::build_router(st);
+            let status = get_json(&app, "/retention/status").await;
+            assert_eq!(status["data"]["iss_storage"], "memory");
+            let rows = status["data"]["tables"][0]["rows"].as_i64().unwrap();
+            // one day of raw 2-minute samples plus roughly one per hour before it
+            assert!((720..=720 + 50).contains(&rows), "rows = {rows}");
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    mod sqlite_backend {
+        use chrono::{Duration, Utc};
+        use serde_json::json;
+        use sqlx::sqlite::SqlitePoolOptions;
+
+        use crate::domain::{
+            GeofenceEvent, GeofenceEventQuery, GeofenceTransition, IssStorage, OsdrChange,
+            OsdrCursor, OsdrEnrichCandidate, OsdrEnrichFailure, OsdrFilter, OsdrListQuery,
+            OsdrSearchQuery, OsdrSort, OsdrSyncState, OsdrUpsert, OsdrVersionKind, TleSet,
+        };
+        use crate::repo::Repos;
+
+        async fn repos() -> (Repos, sqlx::SqlitePool) {
+            let pool = SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap();
+            let repos = Repos::sqlite(pool.clone()).await.unwrap();
+            repos.ensure_schema().await.unwrap();
+            (repos, pool)
+        }
+
+        #[tokio::test]
+        async fn repositories_roundtrip() {
+            let (repos, _) = repos().await;
+            assert_eq!(repos.iss.storage().await.unwrap(), Some(IssStorage::Sqlite));
+
+            repos.iss.insert_log(25544, "u", &json!({"latitude": 1.5})).await.unwrap();
+            repos.iss.insert_log(48274, "sgp4", &json!({"latitude": -3.0})).await.unwrap();
+            let (_, _, src, payload) = repos.iss.last(25544).await.unwrap().unwrap();
+            assert_eq!(src, "u");
+            assert_eq!(payload["latitude"], 1.5);
+            let (_, _, src, _) = repos.iss.last(48274).await.unwrap().unwrap();
+            assert_eq!(src, "sgp4");
+            assert!(repos.iss.last(20580).await.unwrap().is_none());
+            assert_eq!(repos.iss.trend(48274, 10).await.unwrap().len(), 1);
+            let now = Utc::now();
+            let day = repos.iss.range(25544, now - Duration::days(1), now).await.unwrap();
+            assert_eq!(day.len(), 1);
+            assert_eq!(day[0].latitude, Some(1.5));
+            let old = now - Duration::days(2);
+            assert!(repos.iss.range(25544, old - Duration::days(1), old).await.unwrap().is_empty());
+
+            let q = OsdrListQuery {
+                limit: 10,
+                desc: true,
+                ..Default::default()
+            };
+            for title in ["a", "b"] {
+                repos
+                    .osdr
+                    .upsert(OsdrUpsert {
+                        dataset_id: Some("OSD-1".into()),
+                        title: Some(title.into()),
+                        status: None,
+                        updated_at: Some(Utc::now()),
+                        raw: json!({"t": title}),
+                    })
+                    .await
+                    .unwrap();
+            }
+            let items = repos.osdr.list(&q).await.unwrap();
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].title.as_deref(), Some("b"));
+            assert_eq!(items[0].raw["t"], "b");
+
+            assert!(repos.cache.write("apod", json!({"x": 1})).await.unwrap());
+            assert!(!repos.cache.write("apod", json!({"x": 1})).await.unwrap());
+            assert_eq!(repos.cache.latest("apod").await.unwrap().unwrap().check_count, 2);
+
+            assert!(repos.locks.try_lock(7).await);
+            assert!(!repos.locks.try_lock(7).await);
+            assert!(repos.locks.unlock(7).await);
+            assert!(repos.locks.try_lock(7).await);
+        }
+
+        #[tokio::test]
+        async fn osdr_sync_state_only_moves_forward() {
+            let (repos, _) = repos().await;
+            assert_eq!(repos.osdr.sync_state().await.unwrap(), OsdrSyncState::default());
+            let now = Utc::now();
+            let state = OsdrSyncState {
+                watermark: Some(now - Duration::days(1)),
+                last_full_at: Some(now),
+                last_sync_at: None,
+            };
+            repos.osdr.save_sync_state(&state).await.unwrap();
+            repos.osdr.save_sync_state(&state).await.unwrap();
+            assert_eq!(repos.osdr.sync_state().await.unwrap(), state);
+
+            // an incremental pass that read the state before the full one saved
+            let stale = OsdrSyncState {
+                watermark: Some(now - Duration::days(2)),
+                last_full_at: None,
+                last_sync_at: Some(now + Duration::seconds(1)),
+            };
+            repos.osdr.save_sync_state(&stale).await.unwrap();
+            let merged = repos.osdr.sync_state().await.unwrap();
+            assert_eq!(merged.watermark, state.watermark);
+            assert_eq!(merged.last_full_at, state.last_full_at);
+            assert_eq!(merged.last_sync_at, stale.last_sync_at);
+        }
+
+        #[tokio::test]
+        async fn osdr_keyset_sqlite() {
+            let (repos, _) = repos().await;
+            let t0 = Utc::now() - Duration::days(3);
+            for (i, id) in ["OSD-1", "osd-2", "OSD-3", "OSD-4"].iter().enumerate() {
+                repos
+                    .osdr
+                    .upsert(OsdrUpsert {
+                        dataset_id: Some(id.to_string()),
+                        title: None,
+                        status: None,
+                        updated_at: (i % 2 == 0).then(|| t0 + Duration::hours(i as i64)),
+                        raw: json!({}),
+                    })
+                    .await
+                    .unwrap();
+            }
+            let mut q = OsdrListQuery {
+                sort: OsdrSort::UpdatedAt,
+                limit: 2,
+                ..Default::default()
+            };
+            let first = repos.osdr.list(&q).await.unwrap();
+            let ids: Vec<_> = first.iter().map(|i| i.dataset_id.clone().unwrap()).collect();
+            assert_eq!(ids, ["osd-2", "OSD-4"]);
+            q.after = Some(OsdrCursor {
+                sort: OsdrSort::UpdatedAt,
+                desc: false,
+                key: None,
+                id: first[1].id,
+            });
+            let second = repos.osdr.list(&q).await.unwrap();
+            let ids: Vec<_> = second.iter().map(|i| i.dataset_id.clone().unwrap()).collect();
+            assert_eq!(ids, ["OSD-1", "OSD-3"]);
+
+            q.filter.dataset_id_prefix = Some("OSD".into());
+            assert_eq!(repos.osdr.count(&q.filter).await.unwrap(), 3);
+        }
+
+        #[tokio::test]
+        async fn osdr_details_sqlite() {
+            let (repos, _) = repos().await;
+            let at = Utc::now();
+            repos
+                .osdr
+                .upsert(OsdrUpsert {
+                    dataset_id: Some("OSD-48".into()),
+                    title: None,
+                    status: None,
+                    updated_at: Some(at),
+                    raw: json!({}),
+                })
+                .await
+                .unwrap();
+            let candidates = repos.osdr.enrich_candidates(at, 5).await.unwrap();
+            let candidate = OsdrEnrichCandidate {
+                dataset_id: "OSD-48".to_string(),
+                updated_at: Some(at),
+                attempts: 0,
+            };
+            assert_eq!(candidates, std::slice::from_ref(&candidate));
+            let failure = OsdrEnrichFailure {
+                dataset_id: "OSD-48".to_string(),
+                attempts: 3,
+                failed_at: at,
+                retry_at: at + Duration::hours(1),
+                error: "timeout".to_string(),
+            };
+            repos.osdr.record_enrich_failure(&failure).await.unwrap();
+            repos.osdr.record_enrich_failure(&failure).await.unwrap();
+            assert_eq!(repos.osdr.enrich_failure("OSD-48").await.unwrap(), Some(failure.clone()));
+            assert!(repos.osdr.enrich_candidates(at, 5).await.unwrap().is_empty());
+            let due = repos.osdr.enrich_candidates(at + Duration::hours(1), 5).await.unwrap();
+            assert_eq!(due, [OsdrEnrichCandidate { attempts: 3, ..candidate.clone() }]);
+
+            let payload = json!({"OSD-48": {
+                "files": {"a.csv": {"URL": "u", "size": 3}},
+                "metadata": {
+                    "organism": ["Mus musculus", "Homo sapiens"],
+                    "mission": {"name": "SpaceX-4"},
+                    "study assay measurement type": ["m1", "m2"]
+                }
+            }});
+            let mut detail = crate::services::parse_osdr_detail("OSD-48", &payload);
+            detail.source_updated_at = Some(at);
+            repos.osdr.save_detail(&detail).await.unwrap();
+            repos.osdr.save_detail(&detail).await.unwrap();
+            assert!(repos.osdr.enrich_candidates(at, 5).await.unwrap().is_empty());
+            // the saved detail cleared the failure: a later change starts over
+            assert_eq!(repos.osdr.enrich_failure("OSD-48").await.unwrap(), None);
+            let later = at + Duration::seconds(1);
+            repos
+                .osdr
+                .upsert(OsdrUpsert {
+                    dataset_id: Some("OSD-48".into()),
+                    title: None,
+                    status: None,
+                    updated_at: Some(later),
+                    raw: json!({}),
+                })
+                .await
+                .unwrap();
+            let candidates = repos.osdr.enrich_candidates(at, 5).await.unwrap();
+            assert_eq!(candidates, [OsdrEnrichCandidate { updated_at: Some(later), ..candidate }]);
+
+            let stored = repos.osdr.detail("OSD-48").await.unwrap().unwrap();
+            assert_eq!(stored, detail);
+        }
+
+        #[tokio::test]
+        async fn osdr_versions_sqlite() {
+            let (repos, _) = repos().await;
+            for status in ["draft", "draft", "public"] {
+                repos
+                    .osdr
+                    .upsert(OsdrUpsert {
+                        dataset_id: Some("OSD-7".into()),
+                        title: Some("t".into()),
+                        status: Some(status.into()),
+                        updated_at: None,
+                        raw: json!({"status": status}),
+                    })
+                    .await
+                    .unwrap();
+            }
+            let history = repos.osdr.history("OSD-7", 10).await.unwrap();
+            assert_eq!(history.len(), 2);
+            assert_eq!(history[0].version, 2);
+            assert_eq!(history[0].diff["fields"]["status"]["new"], "public");
+            let feed = repos.osdr.recent_changes(None, 10).await.unwrap();
+            assert_eq!(feed.len(), 1);
+            assert_eq!(feed[0].version, 2);
+        }
+
+        #[tokio::test]
+        async fn osdr_batch_upsert_sqlite() {
+            let (repos, _) = repos().await;
+            let page = |status: &str, n: usize| -> Vec<OsdrUpsert> {
+                (0..n)
+                    .map(|i| OsdrUpsert {
+                        dataset_id: Some(format!("OSD-{i}")),
+                        title: Some(format!("t{i}")),
+                        status: Some(status.into()),
+                        updated_at: None,
+                        raw: json!({"i": i, "status": status}),
+                    })
+                    .collect()
+            };
+            // larger than one multi-row statement
+            let changes = repos.osdr.upsert_batch(&page("draft", 250)).await.unwrap();
+            assert!(changes.iter().all(|c| *c == OsdrChange::Created));
+            let mut next = page("draft", 251);
+            next[7].status = Some("public".into());
+            let changes = repos.osdr.upsert_batch(&next).await.unwrap();
+            assert_eq!(changes.iter().filter(|c| **c == OsdrChange::Unchanged).count(), 249);
+            assert_eq!((changes[7], changes[250]), (OsdrChange::Updated, OsdrChange::Created));
+            assert_eq!(repos.osdr.count(&Default::default()).await.unwrap(), 251);
+            assert_eq!(repos.osdr.history("OSD-7", 10).await.unwrap()[0].version, 2);
+
+            // a bad page is rejected as a whole
+            let mut bad = page("x", 2);
+            bad[1].dataset_id = bad[0].dataset_id.clone();
+            assert!(repos.osdr.upsert_batch(&bad).await.is_err());
+            assert_eq!(repos.osdr.get("OSD-0").await.unwrap().unwrap().status.as_deref(), Some("draft"));
+        }
+
+        #[tokio::test]
+        async fn osdr_tombstones_sqlite() {
+            let (repos, _) = repos().await;
+            for id in ["OSD-1", "OSD-2"] {
+                repos
+                    .osdr
+                    .upsert(OsdrUpsert {
+                        dataset_id: Some(id.into()),
+                        title: None,
+                        status: None,
+                        updated_at: None,
+                        raw: json!({}),
+                    })
+                    .await
+                    .unwrap();
+            }
+            let removed = repos.osdr.mark_removed(&["OSD-1".into()], Utc::now()).await.unwrap();
+            assert_eq!(removed, ["OSD-2"]);
+            assert!(repos.osdr.mark_removed(&["OSD-1".into()], Utc::now()).await.unwrap().is_empty());
+            assert_eq!(repos.osdr.count(&Default::default()).await.unwrap(), 1);
+            let all = OsdrFilter { include_removed: true, ..Default::default() };
+            assert_eq!(repos.osdr.count(&all).await.unwrap(), 2);
+            let latest = &repos.osdr.history("OSD-2", 10).await.unwrap()[0];
+            assert_eq!(latest.kind, OsdrVersionKind::Removed);
+        }
+
+        #[tokio::test]
+        async fn tle_sets_sqlite() {
+            let (repos, _) = repos().await;
+            let (epoch, fetched_at) = (Utc::now() - Duration::days(2), Utc::now());
+            let set = |days: i64| TleSet {
+                norad_id: 25544,
+                name: Some("ISS (ZARYA)".into()),
+                epoch: epoch + Duration::days(days),
+                line1: format!("line1 {days}"),
+                line2: format!("line2 {days}"),
+                fetched_at,
+            };
+            assert_eq!(repos.tle.insert(&[set(0), set(1), set(0)]).await.unwrap(), 2);
+            assert_eq!(repos.tle.insert(&[set(1)]).await.unwrap(), 0);
+            let near = |h: i64| repos.tle.nearest(25544, epoch + Duration::hours(h));
+            assert_eq!(near(-5).await.unwrap().unwrap().line1, "line1 0");
+            assert_eq!(near(11).await.unwrap().unwrap().line1, "line1 0");
+            assert_eq!(near(13).await.unwrap().unwrap().line1, "line1 1");
+            assert_eq!(near(100).await.unwrap().unwrap().line1, "line1 1");
+            assert!(repos.tle.nearest(20580, epoch).await.unwrap().is_none());
+            assert_eq!(repos.tle.history(25544, 5).await.unwrap(), [set(1), set(0)]);
+        }
+
+        #[tokio::test]
+        async fn geofence_events_sqlite() {
+            let (repos, _) = repos().await;
+            let at = Utc::now();
+            let event = |fence: &str, kind, minutes| GeofenceEvent {
+                id: 0,
+                norad_id: 25544,
+                fence: fence.into(),
+                kind,
+                at: at + Duration::minutes(minutes),
+                latitude: 55.0,
+                longitude: 37.5,
+            };
+            repos
+                .events
+                .insert(&[
+                    event("a", GeofenceTransition::Enter, 0),
+                    event("b", GeofenceTransition::Enter, 0),
+                ])
+                .await
+                .unwrap();
+            repos.events.insert(&[event("a", GeofenceTransition::Exit, 5)]).await.unwrap();
+            assert_eq!(repos.events.inside(25544).await.unwrap(), ["b"]);
+            assert!(repos.events.inside(48274).await.unwrap().is_empty());
+
+            let q = GeofenceEventQuery { limit: 10, ..Default::default() };
+            let all = repos.events.list(&q).await.unwrap();
+            assert_eq!(all.len(), 3);
+            assert_eq!((all[0].fence.as_str(), all[0].kind), ("a", GeofenceTransition::Exit));
+            assert_eq!(all[0].at, at + Duration::minutes(5));
+            let q = GeofenceEventQuery {
+                fence: Some("a".into()),
+                since: Some(at + Duration::minutes(1)),
+                limit: 10,
+                ..Default::default()
+            };
+            assert_eq!(repos.events.list(&q).await.unwrap().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn osdr_search_sqlite() {
+            let (repos, pool) = repos().await;
+            for (id, desc) in [("OSD-1", "Мыши на МКС"), ("OSD-2", "Plants in orbit")] {
+                repos
+                    .osdr
+                    .upsert(OsdrUpsert {
+                        dataset_id: Some(id.into()),
+                        title: None,
+                        status: None,
+                        updated_at: None,
+                        raw: json!({"description": desc}),
+                    })
+                    .await
+                    .unwrap();
+            }
+            let q = OsdrSearchQuery {
+                text: "мыши".into(),
+                limit: 10,
+                ..Default::default()
+            };
+            let hits = repos.osdr.search(&q).await.unwrap();
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].item.dataset_id.as_deref(), Some("OSD-1"));
+            assert_eq!(hits[0].snippet.as_deref(), Some("<b>Мыши</b> на МКС"));
+            assert_eq!(repos.osdr.search_count(&q).await.unwrap(), 1);
+
+            // rows from before search get their body at startup
+            sqlx::query("UPDATE osdr_items SET search_body = NULL").execute(&pool).await.unwrap();
+            assert!(repos.osdr.search(&q).await.unwrap().is_empty());
+            repos.osdr.ensure_schema().await.unwrap();
+            let bodies: Vec<String> =
+                sqlx::query_scalar("SELECT search_body FROM osdr_items ORDER BY dataset_id")
+                    .fetch_all(&pool)
+                    .await
+                    .unwrap();
+            assert_eq!(bodies, ["Мыши на МКС", "Plants in orbit"]);
+            assert_eq!(repos.osdr.search(&q).await.unwrap().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn retention_downsamples_sqlite() {
+            let (repos, pool) = repos().await;
+            let now = Utc::now();
+            for m in (0..3 * 24 * 60).step_by(2) {
+                sqlx::query("INSERT INTO iss_fetch_log(fetched_at, source_url, payload) VALUES (?,?,?)")
+                    .bind(now - Duration::minutes(m))
+                    .bind("t")
+                    .bind(json!({}))
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+            }
+            let policy = crate::config::parse_retention_policies("iss_fetch_log:raw=1d,bucket=1h")
+                .unwrap()
+                .remove(0);
+            let (down, expired) = repos.retention.apply(&policy, &[], now).await.unwrap();
+            assert!(down > 1_000);
+            assert_eq!(expired, 0);
+            let rows = repos.retention.table_stats().await.unwrap()[0].rows;
+            assert!((720..=720 + 50).contains(&rows), "rows = {rows}");
+        }
+    }
+}
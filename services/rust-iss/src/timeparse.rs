@@ -0,0 +1,229 @@
+//! Timestamp parsing for upstream payloads. Every normalizer goes through
+//! [`TimeParser`] so that APIs mixing RFC 3339, RFC 2822, bare dates and
+//! epochs in any unit end up as the same `DateTime<Utc>`.
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Why a value was not accepted as a timestamp.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum TimeError {
+    #[error("empty string")]
+    Empty,
+    #[error("expected a string or number, got {0}")]
+    Type(&'static str),
+    #[error("epoch {value} read as {unit} is out of range")]
+    EpochRange { value: String, unit: EpochUnit },
+    #[error("`{0}` matches no known format")]
+    NoMatch(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EpochUnit {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl EpochUnit {
+    /// Guesses the unit from the magnitude: seconds cover years up to 5138,
+    /// so anything bigger is read as the next finer unit.
+    pub fn detect(abs: f64) -> Self {
+        if abs < 1e11 {
+            EpochUnit::Seconds
+        } else if abs < 1e14 {
+            EpochUnit::Millis
+        } else if abs < 1e17 {
+            EpochUnit::Micros
+        } else {
+            EpochUnit::Nanos
+        }
+    }
+
+    fn per_second(self) -> f64 {
+        match self {
+            EpochUnit::Seconds => 1.0,
+            EpochUnit::Millis => 1e3,
+            EpochUnit::Micros => 1e6,
+            EpochUnit::Nanos => 1e9,
+        }
+    }
+}
+
+impl std::fmt::Display for EpochUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EpochUnit::Seconds => "seconds",
+            EpochUnit::Millis => "milliseconds",
+            EpochUnit::Micros => "microseconds",
+            EpochUnit::Nanos => "nanoseconds",
+        })
+    }
+}
+
+/// Formats carrying an offset, tried after RFC 3339 and RFC 2822.
+const OFFSET_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M%#z",
+    "%Y-%m-%d %H:%M%#z",
+    "%Y-%m-%d %H:%M:%S%.f%#z",
+    "%Y-%m-%d %H:%M:%S%.f %#z",
+];
+
+/// Formats without an offset, read as UTC.
+const NAIVE_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Date-only formats, read as midnight UTC.
+const DATE_FORMATS: [&str; 2] = ["%Y-%m-%d", "%d %b %Y"];
+
+/// Built-in formats plus `extra` strftime formats, tried in that order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimeParser {
+    extra: Vec<String>,
+}
+
+impl TimeParser {
+    pub fn new(extra: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            extra: extra.into_iter().collect(),
+        }
+    }
+
+    /// A parser that also tries `more` after this one's formats.
+    pub fn with(&self, more: &[String]) -> Self {
+        Self::new(self.extra.iter().chain(more).cloned())
+    }
+
+    /// Strings are parsed as text; numbers (and all-digit strings that are
+    /// not a `YYYYMMDD` date) as epochs.
+    pub fn parse_value(&self, v: &Value) -> Result<DateTime<Utc>, TimeError> {
+        match v {
+            Value::String(s) => self.parse_str(s),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => epoch_int(i),
+                None => epoch_float(n.as_f64().unwrap_or(f64::NAN), &n.to_string()),
+            },
+            Value::Null => Err(TimeError::Type("null")),
+            Value::Bool(_) => Err(TimeError::Type("a boolean")),
+            Value::Array(_) => Err(TimeError::Type("an array")),
+            Value::Object(_) => Err(TimeError::Type("an object")),
+        }
+    }
+
+    pub fn parse_str(&self, s: &str) -> Result<DateTime<Utc>, TimeError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(TimeError::Empty);
+        }
+        if let Some(dt) = self.parse_text(s) {
+            return Ok(dt);
+        }
+        if let Some(dt) = compact_date(s) {
+            return Ok(dt);
+        }
+        if is_number(s) {
+            if let Ok(i) = s.parse::<i64>() {
+                return epoch_int(i);
+            }
+            if let Ok(f) = s.parse::<f64>() {
+                return epoch_float(f, s);
+            }
+        }
+        Err(TimeError::NoMatch(s.to_string()))
+    }
+
+    fn parse_text(&self, s: &str) -> Option<DateTime<Utc>> {
+        if let Ok(dt) = s.parse::<DateTime<FixedOffset>>() {
+            return Some(dt.to_utc());
+        }
+        if let Ok(dt) = DateTime::parse_from_rfc2822(s) {
+            return Some(dt.to_utc());
+        }
+        OFFSET_FORMATS
+            .iter()
+            .chain(&NAIVE_FORMATS)
+            .chain(&DATE_FORMATS)
+            .copied()
+            .chain(self.extra.iter().map(String::as_str))
+            .find_map(|f| with_format(s, f))
+    }
+}
+
+/// `s` read with one strftime format: with an offset, as a UTC date-time or
+/// as a date at midnight UTC.
+fn with_format(s: &str, f: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_str(s, f) {
+        return Some(dt.to_utc());
+    }
+    if let Ok(ndt) = NaiveDateTime::parse_from_str(s, f) {
+        return Some(ndt.and_utc());
+    }
+    NaiveDate::parse_from_str(s, f)
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|ndt| ndt.and_utc())
+}
+
+/// `YYYYMMDD`, which would otherwise read as an epoch in 1970.
+fn compact_date(s: &str) -> Option<DateTime<Utc>> {
+    if s.len() != 8 || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let d = NaiveDate::from_ymd_opt(s[..4].parse().ok()?, s[4..6].parse().ok()?, s[6..].parse().ok()?)?;
+    Some(d.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+fn is_number(s: &str) -> bool {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    !digits.is_empty()
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && digits.chars().filter(|c| *c == '.').count() <= 1
+}
+
+fn epoch_int(n: i64) -> Result<DateTime<Utc>, TimeError> {
+    let unit = EpochUnit::detect(n.unsigned_abs() as f64);
+    let dt = match unit {
+        EpochUnit::Seconds => DateTime::from_timestamp(n, 0),
+        EpochUnit::Millis => DateTime::from_timestamp_millis(n),
+        EpochUnit::Micros => DateTime::from_timestamp_micros(n),
+        EpochUnit::Nanos => Some(DateTime::from_timestamp_nanos(n)),
+    };
+    dt.ok_or(TimeError::EpochRange {
+        value: n.to_string(),
+        unit,
+    })
+}
+
+/// `text` is the value as received, for the error.
+fn epoch_float(n: f64, text: &str) -> Result<DateTime<Utc>, TimeError> {
+    let unit = EpochUnit::detect(n.abs());
+    let out_of_range = || TimeError::EpochRange {
+        value: text.to_string(),
+        unit,
+    };
+    if !n.is_finite() {
+        return Err(out_of_range());
+    }
+    let secs = n / unit.per_second();
+    let whole = secs.floor();
+    let nanos = (((secs - whole) * 1e9).round() as u32).min(999_999_999);
+    if whole.abs() > i64::MAX as f64 {
+        return Err(out_of_range());
+    }
+    DateTime::from_timestamp(whole as i64, nanos).ok_or_else(out_of_range)
+}
+
+/// Rejects strftime formats chrono cannot interpret.
+pub(crate) fn check_format(f: &str) -> anyhow::Result<()> {
+    if f.is_empty() || StrftimeItems::new(f).any(|i| matches!(i, Item::Error)) {
+        anyhow::bail!("time format `{f}` is not a valid strftime pattern");
+    }
+    Ok(())
+}
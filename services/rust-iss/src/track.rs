@@ -0,0 +1,172 @@
+//! Ground tracks as files for GIS tools: GeoJSON, KML and GPX, cut where
+//! the track crosses the antimeridian so that maps do not draw a line
+//! across the whole world, and where the log has a gap.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+use crate::domain::{IssPoint, TrackFormat};
+use crate::services::INTERPOLATION_MAX_GAP_SECONDS;
+
+/// One vertex of an exported track; altitude in km as stored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackVertex {
+    pub at: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+/// Splits the positions into runs that do not cross ±180° or skip a gap in
+/// the log longer than positions are interpolated over. Each crossing ends
+/// one run and starts the next at the interpolated point on the meridian,
+/// so the pieces meet at the edge of the map; a gap just ends the run.
+/// Positions without coordinates are skipped.
+pub fn split_track(points: &[IssPoint]) -> Vec<Vec<TrackVertex>> {
+    let mut runs: Vec<Vec<TrackVertex>> = Vec::new();
+    let mut run: Vec<TrackVertex> = Vec::new();
+    for p in points {
+        let (Some(latitude), Some(longitude)) = (p.latitude, p.longitude) else {
+            continue;
+        };
+        let v = TrackVertex { at: p.at, latitude, longitude, altitude: p.altitude };
+        if let Some(prev) = run.last().copied() {
+            let step = v.longitude - prev.longitude;
+            if (v.at - prev.at).num_seconds() > INTERPOLATION_MAX_GAP_SECONDS {
+                runs.push(std::mem::take(&mut run));
+            } else if step.abs() > 180.0 {
+                // eastward when the longitude jumps down from +180 to -180
+                let edge = if step < 0.0 { 180.0 } else { -180.0 };
+                let far = v.longitude + 2.0 * edge;
+                let f = (edge - prev.longitude) / (far - prev.longitude);
+                let lerp = |a: f64, b: f64| a + (b - a) * f;
+                let span = (v.at - prev.at).num_milliseconds() as f64;
+                let cut = TrackVertex {
+                    at: prev.at + chrono::Duration::milliseconds((span * f) as i64),
+                    latitude: lerp(prev.latitude, v.latitude),
+                    longitude: edge,
+                    altitude: prev.altitude.zip(v.altitude).map(|(a, b)| lerp(a, b)),
+                };
+                run.push(cut);
+                runs.push(std::mem::take(&mut run));
+                run.push(TrackVertex { longitude: -edge, ..cut });
+            }
+        }
+        run.push(v);
+    }
+    if !run.is_empty() {
+        runs.push(run);
+    }
+    runs.retain(|r| r.len() > 1);
+    runs
+}
+
+/// Renders the runs of one satellite's track in `format`. Altitudes are
+/// written in metres, as GIS tools expect.
+pub fn render(
+    format: TrackFormat,
+    name: &str,
+    norad_id: i64,
+    runs: &[Vec<TrackVertex>],
+) -> String {
+    match format {
+        TrackFormat::GeoJson => geojson(name, norad_id, runs).to_string(),
+        TrackFormat::Kml => kml(name, norad_id, runs),
+        TrackFormat::Gpx => gpx(name, norad_id, runs),
+    }
+}
+
+fn geojson(name: &str, norad_id: i64, runs: &[Vec<TrackVertex>]) -> Value {
+    let coords: Vec<Vec<Value>> = runs
+        .iter()
+        .map(|r| {
+            r.iter()
+                .map(|v| match v.altitude {
+                    Some(alt) => json!([v.longitude, v.latitude, alt * 1000.0]),
+                    None => json!([v.longitude, v.latitude]),
+                })
+                .collect()
+        })
+        .collect();
+    let times: Vec<Vec<String>> =
+        runs.iter().map(|r| r.iter().map(|v| timestamp(v.at)).collect()).collect();
+    // `coordTimes` as written by togeojson and read by Mapbox and QGIS
+    let (geometry, coord_times) = if runs.len() == 1 {
+        (json!({"type": "LineString", "coordinates": coords[0]}), json!(times[0]))
+    } else {
+        (json!({"type": "MultiLineString", "coordinates": coords}), json!(times))
+    };
+    json!({
+        "type": "Feature",
+        "geometry": geometry,
+        "properties": {
+            "name": name,
+            "norad_id": norad_id,
+            "start": runs.first().and_then(|r| r.first()).map(|v| timestamp(v.at)),
+            "end": runs.last().and_then(|r| r.last()).map(|v| timestamp(v.at)),
+            "coordTimes": coord_times,
+        },
+    })
+}
+
+fn kml(name: &str, norad_id: i64, runs: &[Vec<TrackVertex>]) -> String {
+    let name = xml_escape(name);
+    let mut out = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<kml xmlns="http://www.opengis.net/kml/2.2"><Document>"#,
+        "\n",
+    ));
+    out.push_str(&format!("<name>{name} ({norad_id})</name>\n"));
+    for (i, run) in runs.iter().enumerate() {
+        let (begin, end) = (timestamp(run[0].at), timestamp(run[run.len() - 1].at));
+        out.push_str(&format!(
+            "<Placemark><name>{name} #{}</name><TimeSpan><begin>{begin}</begin><end>{end}</end>\
+             </TimeSpan><LineString><tessellate>1</tessellate><altitudeMode>absolute\
+             </altitudeMode><coordinates>",
+            i + 1
+        ));
+        let coords: Vec<String> = run
+            .iter()
+            .map(|v| {
+                let alt = v.altitude.map_or(0.0, |a| a * 1000.0);
+                format!("{},{},{alt:.0}", v.longitude, v.latitude)
+            })
+            .collect();
+        out.push_str(&coords.join(" "));
+        out.push_str("</coordinates></LineString></Placemark>\n");
+    }
+    out.push_str("</Document></kml>\n");
+    out
+}
+
+fn gpx(name: &str, norad_id: i64, runs: &[Vec<TrackVertex>]) -> String {
+    let mut out = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<gpx version="1.1" creator="rust_iss" xmlns="http://www.topografix.com/GPX/1/1">"#,
+        "\n",
+    ));
+    out.push_str(&format!("<trk><name>{} ({norad_id})</name>\n", xml_escape(name)));
+    for run in runs {
+        out.push_str("<trkseg>\n");
+        for v in run {
+            out.push_str(&format!(r#"<trkpt lat="{}" lon="{}">"#, v.latitude, v.longitude));
+            if let Some(alt) = v.altitude {
+                out.push_str(&format!("<ele>{:.0}</ele>", alt * 1000.0));
+            }
+            out.push_str(&format!("<time>{}</time></trkpt>\n", timestamp(v.at)));
+        }
+        out.push_str("</trkseg>\n");
+    }
+    out.push_str("</trk></gpx>\n");
+    out
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}